@@ -1,3 +1,4 @@
+use crate::utils;
 /// alignment filtering module
 /// runs in three phases:
 /// 1) Read all alignments, store them if they pass basic filters (no self-alignment, overlap length, identity), only store the longest alignment per read pair
@@ -6,9 +7,11 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read as _, Write};
+use std::ops::Range;
 
 // enable serialization for debugging purposes
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::io::BufWriter;
 
@@ -17,11 +20,141 @@ struct Read {
     id: usize,
     name: String,
     length: u32,
-    per_base_coverage: Vec<u32>,
+    coverage: CoverageProfile,
     coverage_start: u32,
     coverage_end: u32,
 }
 
+/// Coverage of a read by its alignments, counted per bin of `bin_size` bases.
+/// A bin size of 1 is the exact per-base coverage. Larger bins bound the memory of long reads, an alignment then
+/// counts for every bin it touches, so the coverage near alignment ends is overestimated by at most one bin.
+struct CoverageProfile {
+    bin_size: usize,
+    length: usize,
+    bins: Vec<u32>,
+}
+
+impl CoverageProfile {
+    fn new(length: usize, bin_size: usize) -> Self {
+        let bin_size = bin_size.max(1);
+        Self {
+            bin_size,
+            length,
+            bins: vec![0; length.div_ceil(bin_size)],
+        }
+    }
+
+    /// Add `count` to the coverage of the bases in `range`
+    fn add(&mut self, range: Range<usize>, count: u32) {
+        if range.is_empty() {
+            return;
+        }
+        let first = range.start / self.bin_size;
+        let last = ((range.end - 1) / self.bin_size).min(self.bins.len().saturating_sub(1));
+        for c in self.bins.iter_mut().take(last + 1).skip(first) {
+            *c += count;
+        }
+    }
+
+    /// Bases covered by bin `i`, the last bin may be shorter
+    fn bin_range(&self, i: usize) -> Range<usize> {
+        i * self.bin_size..((i + 1) * self.bin_size).min(self.length)
+    }
+
+    fn max(&self) -> u32 {
+        self.bins.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Mean coverage over the read length
+    fn mean(&self) -> f64 {
+        let total: u64 = (0..self.bins.len())
+            .map(|i| self.bins[i] as u64 * self.bin_range(i).len() as u64)
+            .sum();
+        total as f64 / self.length as f64
+    }
+
+    /// Longest region with coverage above `threshold`, as a base range (empty if there is none)
+    fn longest_run_above(&self, threshold: u32) -> Range<usize> {
+        let mut cur_len = 0;
+        let mut cur_start = 0;
+        let mut best_len = 0;
+        let mut best = (0, 0);
+        for (i, &x) in self.bins.iter().enumerate() {
+            if x > threshold {
+                if cur_len == 0 {
+                    cur_start = i;
+                }
+                cur_len += 1;
+                if cur_len > best_len {
+                    best_len = cur_len;
+                    best = (cur_start, i + 1); // end is exclusive
+                }
+            } else {
+                cur_len = 0;
+            }
+        }
+        if best_len == 0 {
+            return 0..0;
+        }
+        self.bin_range(best.0).start..self.bin_range(best.1 - 1).end
+    }
+}
+
+/// Parameters of the chimeric read detection
+pub struct ChimeraParams {
+    // minimum coverage of the flanks on both sides of a coverage valley
+    pub min_flank_cov: u32,
+    // size of the flanks in bases
+    pub window: usize,
+    // positions with coverage below this value are part of a valley
+    pub max_valley_cov: u32,
+}
+
+/// Options of the alignment filtering, the defaults are those of the alignment-filtering command
+pub struct FilteringOptions {
+    pub min_overlap_length: u32,
+    // reads are trimmed to their longest stretch covered by at least this many alignments
+    pub min_overlap_count: u32,
+    pub min_percent_identity: f32,
+    pub min_mapq: u8,
+    pub overhang_ratio: f32,
+    pub max_overhang: u32,
+    pub containment_fuzz: u32,
+    // alignments of a read to itself mark the read as a repeat
+    pub self_overlaps_as_repeats: bool,
+    pub max_alignments_per_pair: usize,
+    // None disables the chimeric read detection
+    pub chimera: Option<ChimeraParams>,
+    // record a container read for every contained read
+    pub record_contained: bool,
+    // merge alignments of a pair split by a gap of at most this many bases
+    pub merge_split_gap: Option<u32>,
+    pub coverage_bin_size: usize,
+    // clip this many bases from both read ends before classifying the alignments, 0 disables it
+    pub trim_overhangs: u32,
+}
+
+impl Default for FilteringOptions {
+    fn default() -> Self {
+        FilteringOptions {
+            min_overlap_length: 2000,
+            min_overlap_count: 3,
+            min_percent_identity: 5.0,
+            min_mapq: 0,
+            overhang_ratio: 0.8,
+            max_overhang: 1000,
+            containment_fuzz: 0,
+            self_overlaps_as_repeats: false,
+            max_alignments_per_pair: 1,
+            chimera: None,
+            record_contained: false,
+            merge_split_gap: None,
+            coverage_bin_size: 1,
+            trim_overhangs: 0,
+        }
+    }
+}
+
 /// Struct to hold an alignment
 #[derive(Clone)]
 struct Alignment {
@@ -37,42 +170,301 @@ struct Alignment {
     num_matching: u32,
     alignment_block_length: u32,
     mapq: u8,
+    // sequence divergence from the de:f (preferred) or dv:f tag, if present
+    divergence: Option<f32>,
+    // false for secondary alignments (tp:A:S), alignments without a tp:A tag count as primary
+    is_primary: bool,
+    // number of PAF lines reporting this alignment (once from each read's perspective), each adds to the coverage
+    reported: u32,
+    // overlap length from the cg:Z: CIGAR, the shorter of the aligned query and target spans
+    cigar_overlap_len: Option<u32>,
+}
+
+/// Aligned spans of a CIGAR string: bases of the query and the target consumed by the whole CIGAR and by the
+/// insertions and deletions before the first and after the last aligned column (M, = or X)
+struct CigarSpans {
+    query: i64,
+    target: i64,
+    // (query, target) bases consumed before the first aligned column
+    leading: (i64, i64),
+    // (query, target) bases consumed after the last aligned column
+    trailing: (i64, i64),
+}
+
+/// Parse a CIGAR string, None if it is malformed or has no aligned column
+fn cigar_spans(cigar: &str) -> Option<CigarSpans> {
+    let mut spans = CigarSpans {
+        query: 0,
+        target: 0,
+        leading: (0, 0),
+        trailing: (0, 0),
+    };
+    let mut aligned = false;
+    let mut len = 0i64;
+    for c in cigar.bytes() {
+        if c.is_ascii_digit() {
+            len = len.checked_mul(10)?.checked_add((c - b'0') as i64)?;
+            continue;
+        }
+        let (query, target) = match c {
+            b'M' | b'=' | b'X' => (len, len),
+            b'I' => (len, 0),
+            b'D' | b'N' => (0, len),
+            _ => return None,
+        };
+        if matches!(c, b'M' | b'=' | b'X') {
+            aligned = true;
+            spans.trailing = (0, 0);
+        } else if aligned {
+            spans.trailing.0 += query;
+            spans.trailing.1 += target;
+        } else {
+            spans.leading.0 += query;
+            spans.leading.1 += target;
+        }
+        spans.query += query;
+        spans.target += target;
+        len = 0;
+    }
+    aligned.then_some(spans)
+}
+
+/// Reason a PAF line could not be parsed
+#[derive(Debug)]
+enum PafParseError {
+    // less than the 12 mandatory columns
+    TooFewFields,
+    // a numeric column does not hold a valid number
+    InvalidNumber,
+}
+
+/// Parse a numeric PAF column
+fn parse_field<T: std::str::FromStr>(field: &str) -> Result<T, PafParseError> {
+    field.parse().map_err(|_| PafParseError::InvalidNumber)
 }
 
 // Implement methods for Alignment (kind of like class methods)
 impl Alignment {
-    fn from_line(line: &str) -> Option<Self> {
+    fn from_line(line: &str) -> Result<Self, PafParseError> {
         let fields: Vec<&str> = line.split('\t').collect();
         if fields.len() < 12 {
-            return None;
+            return Err(PafParseError::TooFewFields);
         }
 
-        Some(Self {
+        // optional tags: gap-compressed divergence (de:f) is preferred over approximate divergence (dv:f)
+        let tag_value = |tag: &str| -> Option<f32> {
+            fields[12..]
+                .iter()
+                .find_map(|f| f.strip_prefix(tag))
+                .and_then(|v| v.parse::<f32>().ok())
+        };
+        let divergence = tag_value("de:f:").or_else(|| tag_value("dv:f:"));
+        // alignment type: P(rimary), S(econdary), I(nversion) or i(nversion), only secondaries are demoted
+        let is_primary = fields[12..]
+            .iter()
+            .find_map(|f| f.strip_prefix("tp:A:"))
+            .is_none_or(|t| t != "S");
+        let cigar = fields[12..]
+            .iter()
+            .find_map(|f| f.strip_prefix("cg:Z:"))
+            .and_then(cigar_spans);
+
+        let mut alignment = Self {
             query_name: fields[0].to_string(),
-            query_length: fields[1].parse().ok()?,
-            query_start: fields[2].parse::<i64>().ok()?,
-            query_end: fields[3].parse::<i64>().ok()?,
+            query_length: parse_field(fields[1])?,
+            query_start: parse_field(fields[2])?,
+            query_end: parse_field(fields[3])?,
             strand: fields[4].chars().next().unwrap_or('+'),
             target_name: fields[5].to_string(),
-            target_length: fields[6].parse::<u32>().ok()?,
-            target_start: fields[7].parse::<i64>().ok()?,
-            target_end: fields[8].parse::<i64>().ok()?,
-            num_matching: fields[9].parse().ok()?,
-            alignment_block_length: fields[10].parse().ok()?,
-            mapq: fields[11].parse().ok()?,
-        })
+            target_length: parse_field(fields[6])?,
+            target_start: parse_field(fields[7])?,
+            target_end: parse_field(fields[8])?,
+            num_matching: parse_field(fields[9])?,
+            alignment_block_length: parse_field(fields[10])?,
+            mapq: parse_field(fields[11])?,
+            divergence,
+            is_primary,
+            reported: 1,
+            cigar_overlap_len: None,
+        };
+        if let Some(spans) = cigar {
+            alignment.apply_cigar(&spans);
+        }
+        Ok(alignment)
+    }
+
+    /// Move the alignment boundaries past insertions and deletions at the ends of the CIGAR and take the overlap
+    /// length from the aligned spans. A CIGAR that doesn't match the coordinates is ignored.
+    fn apply_cigar(&mut self, spans: &CigarSpans) {
+        if spans.query != self.query_end - self.query_start
+            || spans.target != self.target_end - self.target_start
+        {
+            return;
+        }
+        if self.strand == '+' {
+            self.query_start += spans.leading.0;
+            self.target_start += spans.leading.1;
+            self.query_end -= spans.trailing.0;
+            self.target_end -= spans.trailing.1;
+        } else {
+            // the CIGAR runs along the forward target and the reverse complemented query
+            self.query_end -= spans.leading.0;
+            self.target_start += spans.leading.1;
+            self.query_start += spans.trailing.0;
+            self.target_end -= spans.trailing.1;
+        }
+        let query_span = self.query_end - self.query_start;
+        let target_span = self.target_end - self.target_start;
+        self.cigar_overlap_len = u32::try_from(query_span.min(target_span)).ok();
+    }
+
+    /// The same alignment with query and target swapped
+    fn swapped(&self) -> Self {
+        Self {
+            query_name: self.target_name.clone(),
+            query_length: self.target_length,
+            query_start: self.target_start,
+            query_end: self.target_end,
+            strand: self.strand,
+            target_name: self.query_name.clone(),
+            target_length: self.query_length,
+            target_start: self.query_start,
+            target_end: self.query_end,
+            num_matching: self.num_matching,
+            alignment_block_length: self.alignment_block_length,
+            mapq: self.mapq,
+            divergence: self.divergence,
+            is_primary: self.is_primary,
+            reported: self.reported,
+            cigar_overlap_len: self.cigar_overlap_len,
+        }
+    }
+
+    /// Check if two alignments between the same reads cover the same coordinates
+    fn same_coordinates(&self, other: &Self) -> bool {
+        self.strand == other.strand
+            && self.query_start == other.query_start
+            && self.query_end == other.query_end
+            && self.target_start == other.target_start
+            && self.target_end == other.target_end
+    }
+
+    /// Gaps between this alignment and a later one on the same strand, in query and target.
+    /// A negative gap means the two alignments overlap.
+    fn gaps_to(&self, next: &Self) -> (i64, i64) {
+        let query_gap = next.query_start - self.query_end;
+        // on the reverse strand the target coordinates decrease along the query
+        let target_gap = if self.strand == '+' {
+            next.target_start - self.target_end
+        } else {
+            self.target_start - next.target_end
+        };
+        (query_gap, target_gap)
+    }
+
+    /// Extend this alignment with a colinear alignment, matches and block lengths are summed
+    fn merge(&mut self, next: &Self) {
+        let divergence = match (self.divergence, next.divergence) {
+            (Some(a), Some(b)) => Some(
+                (a * self.alignment_block_length as f32 + b * next.alignment_block_length as f32)
+                    / (self.alignment_block_length + next.alignment_block_length) as f32,
+            ),
+            _ => None,
+        };
+        self.query_start = self.query_start.min(next.query_start);
+        self.query_end = self.query_end.max(next.query_end);
+        self.target_start = self.target_start.min(next.target_start);
+        self.target_end = self.target_end.max(next.target_end);
+        self.num_matching += next.num_matching;
+        self.alignment_block_length += next.alignment_block_length;
+        self.mapq = self.mapq.min(next.mapq);
+        self.divergence = divergence;
+        self.is_primary |= next.is_primary;
+        self.reported = self.reported.min(next.reported);
+        self.cigar_overlap_len = match (self.cigar_overlap_len, next.cigar_overlap_len) {
+            (Some(a), Some(b)) => Some(a + b),
+            _ => None,
+        };
+    }
+
+    /// Clip `query_clip` and `target_clip` bases from both ends of the reads, the coordinates move onto the clipped reads.
+    /// Where the alignment reaches into a clipped end it is truncated along its diagonal, so the offset between the reads
+    /// (and the edge lengths measured from it) doesn't change. An alignment within the clipped ends becomes empty.
+    fn clip_ends(&mut self, query_clip: i64, target_clip: i64) {
+        let query_len = (self.query_length as i64 - 2 * query_clip).max(0);
+        let target_len = (self.target_length as i64 - 2 * target_clip).max(0);
+        let (mut qs, mut qe) = (self.query_start - query_clip, self.query_end - query_clip);
+        let (mut ts, mut te) = (
+            self.target_start - target_clip,
+            self.target_end - target_clip,
+        );
+        if self.strand == '+' {
+            // the query start pairs with the target start
+            let start = (-qs).max(-ts).max(0);
+            qs += start;
+            ts += start;
+            let end = (qe - query_len).max(te - target_len).max(0);
+            qe -= end;
+            te -= end;
+        } else {
+            // the query start pairs with the target end
+            let start = (-qs).max(te - target_len).max(0);
+            qs += start;
+            te -= start;
+            let end = (qe - query_len).max(-ts).max(0);
+            qe -= end;
+            ts += end;
+        }
+        self.query_start = qs.clamp(0, query_len);
+        self.query_end = qe.clamp(self.query_start, query_len);
+        self.target_start = ts.clamp(0, target_len);
+        self.target_end = te.clamp(self.target_start, target_len);
+        self.query_length = query_len as u32;
+        self.target_length = target_len as u32;
+        let clipped_span =
+            (self.query_end - self.query_start).min(self.target_end - self.target_start);
+        self.cigar_overlap_len = self.cigar_overlap_len.map(|l| l.min(clipped_span as u32));
+    }
+
+    /// Coordinates with the read that sorts first by name first, the same for both perspectives of an alignment
+    fn canonical_coordinates(&self) -> (i64, i64, i64, i64, char) {
+        if self.query_name <= self.target_name {
+            (
+                self.query_start,
+                self.query_end,
+                self.target_start,
+                self.target_end,
+                self.strand,
+            )
+        } else {
+            (
+                self.target_start,
+                self.target_end,
+                self.query_start,
+                self.query_end,
+                self.strand,
+            )
+        }
     }
 
     fn is_self_alignment(&self) -> bool {
         self.query_name == self.target_name
     }
 
+    /// Percent identity (0-100), from the divergence tag when available, otherwise matches / block length
     fn percent_identity(&self) -> f32 {
-        (self.num_matching as f32 / self.alignment_block_length as f32) * 100.0
+        let identity = match self.divergence {
+            Some(d) => (1.0 - d) * 100.0,
+            None if self.alignment_block_length == 0 => 0.0,
+            None => (self.num_matching as f32 / self.alignment_block_length as f32) * 100.0,
+        };
+        // a malformed tag or match count can't leave the range
+        identity.clamp(0.0, 100.0)
     }
 }
 
 /// Enum for alignment classification
+#[derive(PartialEq, Debug)]
 enum AlignmentType {
     Filtered,
     InternalMatch,
@@ -91,34 +483,269 @@ pub struct Overlap {
     pub edge_len: u32,
     pub rc_edge_len: u32,
     pub overlap_len: u32,
+    // percent identity (0-100), like every identity in the graph
     pub identity: f64,
 }
 
+/// Check that the forward and reverse complement edges of every overlap describe the same overlap:
+/// all four nodes carry an orientation, the RC source is the flipped sink, the RC sink is the flipped source
+/// and the overlap connects two different reads. Returns one message per inconsistent overlap.
+pub fn verify_overlap_orientations(overlaps: &[Overlap]) -> Vec<String> {
+    let mut problems: Vec<String> = Vec::new();
+    for o in overlaps {
+        let edge = format!("{} -> {}", o.source_name, o.sink_name);
+        let (Some(sink_rc), Some(source_rc)) =
+            (utils::rc_node(&o.sink_name), utils::rc_node(&o.source_name))
+        else {
+            problems.push(format!("overlap {} has a node without orientation", edge));
+            continue;
+        };
+        if o.rc_source_name != sink_rc || o.rc_sink_name != source_rc {
+            problems.push(format!(
+                "overlap {} has the reverse complement {} -> {}, expected {} -> {}",
+                edge, o.rc_source_name, o.rc_sink_name, sink_rc, source_rc
+            ));
+        } else if o.source_name == o.sink_name || o.source_name == sink_rc {
+            problems.push(format!("overlap {} connects a read to itself", edge));
+        }
+    }
+    problems
+}
+
+/// Self-alignments of a single read
+/// alignments off the main diagonal indicate an internal (tandem) repeat
+#[derive(Serialize, Deserialize, Default)]
+pub struct SelfAlignments {
+    pub count: u32,
+    // (query start, target start, strand) of each self-alignment
+    pub offsets: Vec<(i64, i64, char)>,
+}
+
+impl SelfAlignments {
+    /// A read contains a repeat if any self-alignment is not the trivial diagonal
+    pub fn is_repeat(&self) -> bool {
+        self.offsets
+            .iter()
+            .any(|(q_start, t_start, strand)| *strand != '+' || q_start != t_start)
+    }
+}
+
+/// Position of a contained read on the read that contains it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContainedPlacement {
+    pub container: String,
+    // orientation of the contained read relative to the + strand of the container
+    pub strand: char,
+    // start of the (oriented) contained read on the + strand of the container, can be slightly
+    // negative or run past the container end when the containment is fuzzy
+    pub offset: i64,
+}
+
+/// Counts collected during alignment filtering, used for the run statistics
+#[derive(Serialize, Default, Clone)]
+pub struct FilteringStats {
+    pub alignments_read: usize,
+    pub too_few_fields_lines: usize,
+    pub invalid_number_lines: usize,
+    pub self_alignments_skipped: usize,
+    pub length_filter_skipped: usize,
+    pub identity_filter_skipped: usize,
+    pub mapq_filter_skipped: usize,
+    pub reads_kept: usize,
+    pub alignments_kept: usize,
+    pub split_alignments_merged: usize,
+    pub chimeric_reads: usize,
+    pub trim_reclassified_alignments: usize,
+    pub overlaps_after_classification: usize,
+    pub overlaps_after_containment: usize,
+    pub rescued_contained_reads: usize,
+    pub overlaps_for_graph: usize,
+}
+
+/// Start of every overlaps file, followed by the format version and the filtering parameters
+const OVERLAPS_MAGIC: &[u8; 8] = b"ILESTAOV";
+/// Bump when the layout of the overlaps file changes
+const OVERLAPS_FORMAT_VERSION: u8 = 1;
+
+/// Filtering parameters stored in the header of the overlaps file
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilteringParams {
+    pub min_overlap_length: u32,
+    pub min_percent_identity: f32,
+    pub overhang_ratio: f32,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct AlignmentFilteringOutput {
-    pub overlaps: HashMap<(usize, usize), Overlap>,
+    // overlaps per read pair, more than one if multiple alignments per pair are kept
+    pub overlaps: HashMap<(usize, usize), Vec<Overlap>>,
+    // true if more than one alignment per read pair was kept, the graph then stores multi-edges
+    pub multi_alignments: bool,
+    // self-alignments per read name, only recorded when self-alignments are used as repeat annotation
+    pub self_alignments: HashMap<String, SelfAlignments>,
+    // contained read name -> placement on a graph read that contains it, only recorded when requested
+    // the graph topology doesn't change, the table lets later steps place contained reads on their container
+    pub contained_in: HashMap<String, ContainedPlacement>,
+    // read name -> mean per-base coverage of the read by its alignments to other reads
+    pub read_coverage: HashMap<String, f64>,
+    // statistics of the filtering run, not stored in the overlaps file
+    #[serde(skip)]
+    pub stats: FilteringStats,
+    // read name -> number of reads it has an alignment with after the crude filters, sorted by name
+    // not stored in the overlaps file
+    #[serde(skip)]
+    pub overlap_degrees: Vec<(String, usize)>,
+    // parameters the overlaps were filtered with, stored in the header of the overlaps file
+    #[serde(skip)]
+    pub params: FilteringParams,
 }
 
 impl AlignmentFilteringOutput {
-    // serialize the overlaps
+    /// Names of the reads that contain an internal repeat
+    pub fn repeat_reads(&self) -> HashSet<String> {
+        self.self_alignments
+            .iter()
+            .filter(|(_, s)| s.is_repeat())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Write the number of overlapping reads per read as a TSV (read_name, degree)
+    pub fn write_overlap_degrees(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "read_name\tdegree")?;
+        for (name, degree) in &self.overlap_degrees {
+            writeln!(w, "{}\t{}", name, degree)?;
+        }
+        Ok(())
+    }
+
+    // serialize the overlaps, after a header with the magic bytes, the format version and the filtering parameters
     pub fn serialize_overlaps(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, &self.overlaps)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(OVERLAPS_MAGIC)?;
+        writer.write_all(&[OVERLAPS_FORMAT_VERSION])?;
+        bincode::serialize_into(&mut writer, &self.params)?;
+        bincode::serialize_into(&mut writer, self)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Warn if the overlaps were filtered with other parameters than the requested ones
+    pub fn warn_on_param_mismatch(&self, path: &str, requested: &FilteringParams) {
+        let cached = &self.params;
+        let mut differences: Vec<String> = Vec::new();
+        if cached.min_overlap_length != requested.min_overlap_length {
+            differences.push(format!(
+                "min_overlap_length {} (requested {})",
+                cached.min_overlap_length, requested.min_overlap_length
+            ));
+        }
+        if cached.min_percent_identity != requested.min_percent_identity {
+            differences.push(format!(
+                "min_percent_identity {} (requested {})",
+                cached.min_percent_identity, requested.min_percent_identity
+            ));
+        }
+        if cached.overhang_ratio != requested.overhang_ratio {
+            differences.push(format!(
+                "overhang_ratio {} (requested {})",
+                cached.overhang_ratio, requested.overhang_ratio
+            ));
+        }
+        if !differences.is_empty() {
+            warn!(
+                "{} was filtered with other parameters, the requested ones are ignored: {}",
+                path,
+                differences.join(", ")
+            );
+        }
+    }
 
+    /// Write the overlaps as a tab-separated table, sorted by read pair: one line per overlap with the
+    /// oriented source and sink reads as the graph builder sees them. Alignment coordinates are not stored.
+    /// `-` writes to stdout.
+    pub fn write_overlaps_tsv(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let writer: Box<dyn Write> = if path == "-" {
+            Box::new(std::io::stdout().lock())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        let mut w = BufWriter::new(writer);
+        writeln!(
+            w,
+            "query_name\tquery_orientation\ttarget_name\ttarget_orientation\tedge_len\trc_edge_len\toverlap_len\tidentity"
+        )?;
+        let mut pairs: Vec<(&(usize, usize), &Vec<Overlap>)> = self.overlaps.iter().collect();
+        pairs.sort_unstable_by_key(|(ids, _)| **ids);
+        // overlaps only connect oriented nodes, '?' marks a malformed id instead of failing the dump
+        fn split(node: &str) -> (&str, char) {
+            utils::split_node_id(node).unwrap_or((node, '?'))
+        }
+        for o in pairs.into_iter().flat_map(|(_, overlaps)| overlaps) {
+            let (query, query_orientation) = split(&o.source_name);
+            let (target, target_orientation) = split(&o.sink_name);
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.2}",
+                query,
+                query_orientation,
+                target,
+                target_orientation,
+                o.edge_len,
+                o.rc_edge_len,
+                o.overlap_len,
+                o.identity
+            )?;
+        }
+        w.flush()?;
         Ok(())
     }
+
+    // deserialize overlaps written by serialize_overlaps, fails on a file without the header or of another version
+    pub fn deserialize_overlaps(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        let mut version = [0u8; 1];
+        if reader.read_exact(&mut magic).is_err() || &magic != OVERLAPS_MAGIC {
+            return Err(format!("{} is not an overlaps file", path).into());
+        }
+        reader.read_exact(&mut version)?;
+        if version[0] != OVERLAPS_FORMAT_VERSION {
+            return Err(format!(
+                "{} has overlaps format version {}, expected {}; rerun the alignment filtering",
+                path, version[0], OVERLAPS_FORMAT_VERSION
+            )
+            .into());
+        }
+        let params: FilteringParams = bincode::deserialize_from(&mut reader)?;
+        let mut output: Self = bincode::deserialize_from(reader)?;
+        output.params = params;
+
+        Ok(output)
+    }
 }
 
-/// Classify alignment and update contained reads set
+/// Convert both edge lengths of an overlap to u32, None if either is not positive or doesn't fit.
+/// A negative edge length would wrap around to a huge value when cast.
+fn positive_edge_lengths(edge_len: i64, rc_edge_len: i64) -> Option<(u32, u32)> {
+    let edge_len = u32::try_from(edge_len).ok().filter(|&l| l > 0)?;
+    let rc_edge_len = u32::try_from(rc_edge_len).ok().filter(|&l| l > 0)?;
+    Some((edge_len, rc_edge_len))
+}
+
+/// Classify alignment and update contained reads set, with the overhang, minimum overlap and containment
+/// fuzz thresholds of `options`
 fn classify_alignment(
     r: &Alignment,
     query_id: usize,
     target_id: usize,
-    overlaps: &mut HashMap<(usize, usize), Overlap>,
-    _max_overhang: u32,
-    overhang_ratio: f64,
-    reads: &Vec<Read>,
-    min_overlap_length: u32,
+    overlaps: &mut HashMap<(usize, usize), Vec<Overlap>>,
+    reads: &[Read],
+    options: &FilteringOptions,
 ) -> AlignmentType {
     // overlaps are a subset of alignments where (in theory) two read edges, one from each read, are part of the alignment
     // this function tries to differentiate between proper overlaps, internal matches, and containments
@@ -138,38 +765,55 @@ fn classify_alignment(
 
     // define overlap beginning and end based on orientation
     let (b2, e2, l2) = if r.strand == '+' {
-        (target_start, target_end, target_length as i64)
+        (target_start, target_end, target_length)
     } else {
         // reverse complement coordinates on the target
         (
-            target_length as i64 - target_end,
-            target_length as i64 - target_start,
-            target_length as i64,
+            target_length - target_end,
+            target_length - target_start,
+            target_length,
         )
     };
 
+    // the same coordinates against the full (untrimmed) reads, unitig sequences are built from full reads,
+    // so the edge lengths have to be measured on them
+    let full_query_length = reads[query_id].length as i64;
+    let full_target_length = reads[target_id].length as i64;
+    let (full_b2, full_e2) = if r.strand == '+' {
+        (target_start, target_end)
+    } else {
+        (
+            full_target_length - target_end,
+            full_target_length - target_start,
+        )
+    };
+    let query_tail = full_query_length - e1;
+    let target_tail = full_target_length - full_e2;
+
     // overhang is the part next to the overlap where the reads don't align, but should in case of perfect overlap
     // overhang: min(b1,b2) + min(l1 - e1, l2 - e2)
     let overhang_left = std::cmp::min(b1, b2);
     let overhang_right = std::cmp::min(l1 - e1, l2 - e2);
     let overhang = overhang_left + overhang_right;
 
-    // miniasm uses a hard cutoff on max overhang, we don't for now
-    //if overhang_left > max_overhang as i64 || overhang_right > max_overhang as i64 {
-    //    return AlignmentType::InternalMatch;
-    //}
-
     // longest overlap length (max aligned spans on either read)
     let overlap_length1 = e1 - b1;
     let overlap_length2 = e2 - b2;
+
+    // the alignment lies (partly) outside the well covered region of a read
+    if overlap_length1 <= 0 || overlap_length2 <= 0 {
+        return AlignmentType::Filtered;
+    }
     let overlap_length = std::cmp::max(overlap_length1, overlap_length2) as f64;
 
-    // decide overhang threshold: max_overhang or maplen * overhang_ratio
-    // we only use the ratio for now
-    let overhang_threshold = (overlap_length * overhang_ratio).ceil() as i64;
-    //let max_overhang_i64 = max_overhang as i64;
-    //let allowed_overhang = std::cmp::min(max_overhang_i64, overhang_threshold);
-    let allowed_overhang = overhang_threshold;
+    // decide overhang threshold: the smaller of max_overhang and maplen * overhang_ratio (as in miniasm)
+    // a max_overhang of 0 disables the absolute cutoff, so only the ratio is used
+    let overhang_threshold = (overlap_length * options.overhang_ratio as f64).ceil() as i64;
+    let allowed_overhang = if options.max_overhang == 0 {
+        overhang_threshold
+    } else {
+        std::cmp::min(options.max_overhang as i64, overhang_threshold)
+    };
 
     // classification of the overlap
 
@@ -179,11 +823,12 @@ fn classify_alignment(
         return AlignmentType::InternalMatch;
     }
 
-    // conditions for containment:
+    // conditions for containment, a read may stick out up to containment_fuzz bases on either side:
     // first contained in second:
-    let first_contained = (b1 <= b2) && ((l1 - e1) <= (l2 - e2));
+    let fuzz = options.containment_fuzz as i64;
+    let first_contained = (b1 <= b2 + fuzz) && ((l1 - e1) <= (l2 - e2) + fuzz);
     // second contained in first:
-    let second_contained = (b1 >= b2) && ((l1 - e1) >= (l2 - e2));
+    let second_contained = (b1 + fuzz >= b2) && ((l1 - e1) + fuzz >= (l2 - e2));
 
     // filter out containments
     if first_contained {
@@ -193,8 +838,8 @@ fn classify_alignment(
     }
 
     // filter out alignments with very small overlaps
-    if overlap_length1 + overhang_left + overhang_right < min_overlap_length as i64
-        || overlap_length2 + overhang_left + overhang_right < min_overlap_length as i64
+    if overlap_length1 + overhang_left + overhang_right < options.min_overlap_length as i64
+        || overlap_length2 + overhang_left + overhang_right < options.min_overlap_length as i64
     {
         return AlignmentType::Filtered;
     }
@@ -209,8 +854,7 @@ fn classify_alignment(
         let t_orient = format!("{}{}", r.target_name, r.strand);
 
         // edge length = b1 - b2 (non-overlapping prefix length)
-        let edge1_len_i64 = b1 - b2;
-        let edge1_len = edge1_len_i64 as u32;
+        let edge1_len_i64 = b1 - full_b2;
 
         // reverse complement counterpart:
         // direction: t_rc -> q_minus
@@ -219,11 +863,14 @@ fn classify_alignment(
         let t_rc = format!("{}{}", r.target_name, rc_strand);
 
         // edge length = (l2 - e2) - (l1 - e1)
-        let edge2_len_i64 = (l2 - e2) - (l1 - e1);
-        let edge2_len = edge2_len_i64 as u32;
+        let edge2_len_i64 = target_tail - query_tail;
+        let Some((edge1_len, edge2_len)) = positive_edge_lengths(edge1_len_i64, edge2_len_i64)
+        else {
+            return AlignmentType::Filtered;
+        };
 
         // shared stats
-        let _overlap_len = r.alignment_block_length as u32;
+        let overlap_len = r.cigar_overlap_len.unwrap_or(r.alignment_block_length);
         let identity = r.percent_identity() as f64;
 
         // create overlap object and store if the overlap is valid
@@ -232,20 +879,19 @@ fn classify_alignment(
             rc_sink_name: q_minus,
             sink_name: t_orient,
             rc_source_name: t_rc,
-            edge_len: edge1_len as u32,
-            rc_edge_len: edge2_len as u32,
-            overlap_len: overlap_length as u32,
+            edge_len: edge1_len,
+            rc_edge_len: edge2_len,
+            overlap_len,
             identity,
         };
-        overlaps.insert((query_id, target_id), ov);
+        overlaps.entry((query_id, target_id)).or_default().push(ov);
     } else {
         // second to first overlap (target -> query)
         // direction t -> q
         let q_plus = format!("{}+", r.query_name);
         let t_orient = format!("{}{}", r.target_name, r.strand);
 
-        let edge1_len_i64 = b2 - b1;
-        let edge1_len = edge1_len_i64 as u32;
+        let edge1_len_i64 = full_b2 - b1;
 
         // reverse complement counterpart:
         // direction q_minus -> t_rc
@@ -253,11 +899,14 @@ fn classify_alignment(
         let rc_strand = if r.strand == '+' { '-' } else { '+' };
         let t_rc = format!("{}{}", r.target_name, rc_strand);
 
-        let edge2_len_i64 = (l1 - e1) - (l2 - e2);
-        let edge2_len = edge2_len_i64 as u32;
+        let edge2_len_i64 = query_tail - target_tail;
+        let Some((edge1_len, edge2_len)) = positive_edge_lengths(edge1_len_i64, edge2_len_i64)
+        else {
+            return AlignmentType::Filtered;
+        };
 
         // shared stats
-        let _overlap_len = r.alignment_block_length as u32;
+        let overlap_len = r.cigar_overlap_len.unwrap_or(r.alignment_block_length);
         let identity = r.percent_identity() as f64;
 
         // create overlap object and store
@@ -266,47 +915,279 @@ fn classify_alignment(
             rc_source_name: q_minus,
             sink_name: q_plus,
             rc_sink_name: t_rc,
-            edge_len: edge1_len as u32,
-            rc_edge_len: edge2_len as u32,
-            overlap_len: overlap_length as u32,
+            edge_len: edge1_len,
+            rc_edge_len: edge2_len,
+            overlap_len,
             identity,
         };
-        overlaps.insert((query_id, target_id), ov);
+        overlaps.entry((query_id, target_id)).or_default().push(ov);
     }
     AlignmentType::ProperOverlap
 }
 
-/// Filter PAF file based on overlap quality criteria
+/// Re-add contained reads whose containers did not survive the low coverage filter.
+/// A read is only rescued if all of its containers are gone, its best proper overlap
+/// to a surviving read is put back. Returns the number of rescued reads.
+fn rescue_contained_reads(
+    contained_reads: &HashMap<usize, HashSet<usize>>,
+    low_coverage_reads: &HashSet<usize>,
+    contained_overlaps: &mut HashMap<(usize, usize), Vec<Overlap>>,
+    overlaps: &mut HashMap<(usize, usize), Vec<Overlap>>,
+) -> usize {
+    // contained reads that lost all their containers
+    let mut rescued: Vec<usize> = contained_reads
+        .iter()
+        .filter(|(id, containers)| {
+            !low_coverage_reads.contains(id)
+                && containers.iter().all(|c| low_coverage_reads.contains(c))
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    rescued.sort_unstable();
+    let rescued_set: HashSet<usize> = rescued.iter().copied().collect();
+
+    // the other read of an overlap has to survive as well
+    let survives = |id: &usize| {
+        !low_coverage_reads.contains(id)
+            && (!contained_reads.contains_key(id) || rescued_set.contains(id))
+    };
+
+    let mut n_rescued = 0;
+    for read_id in rescued {
+        // best proper overlap of this read: longest overlap, ties broken by identity
+        let best = contained_overlaps
+            .iter()
+            .filter(|((q_id, t_id), _)| {
+                (*q_id == read_id && survives(t_id)) || (*t_id == read_id && survives(q_id))
+            })
+            .flat_map(|(ids, ovs)| ovs.iter().enumerate().map(move |(i, ov)| (*ids, i, ov)))
+            .max_by(|a, b| {
+                a.2.overlap_len
+                    .cmp(&b.2.overlap_len)
+                    .then(a.2.identity.total_cmp(&b.2.identity))
+                    // make ties deterministic
+                    .then(b.0.cmp(&a.0))
+            })
+            .map(|(ids, i, _)| (ids, i));
+
+        if let Some((ids, i)) = best {
+            if let Some(ovs) = contained_overlaps.get_mut(&ids) {
+                let ov = ovs.remove(i);
+                overlaps.entry(ids).or_default().push(ov);
+                n_rescued += 1;
+            }
+        }
+    }
+
+    n_rescued
+}
+
+/// Merge the colinear alignments of one read pair, e.g. the segments of a split read alignment.
+/// Alignments on the same strand are merged when the next one (by query start) continues the previous one
+/// in both reads with a gap or overlap of at most `max_gap` bases.
+/// Returns the query and target gap bridged by every merge (empty ranges if the alignments touch or overlap),
+/// with the number of PAF lines reporting both merged alignments.
+fn merge_colinear_alignments(
+    alignments: &mut Vec<Alignment>,
+    max_gap: u32,
+) -> Vec<(Range<usize>, Range<usize>, u32)> {
+    let mut gaps = Vec::new();
+    if alignments.len() < 2 {
+        return gaps;
+    }
+    let max_gap = max_gap as i64;
+    alignments.sort_by_key(|a| (a.strand, a.query_start, a.target_start));
+    let mut merged: Vec<Alignment> = Vec::with_capacity(alignments.len());
+    for alignment in alignments.drain(..) {
+        let colinear = merged.last().is_some_and(|last| {
+            let (query_gap, target_gap) = last.gaps_to(&alignment);
+            last.strand == alignment.strand
+                && query_gap.abs() <= max_gap
+                && target_gap.abs() <= max_gap
+        });
+        match merged.last_mut() {
+            Some(last) if colinear => {
+                let (first, second) = if last.strand == '+' {
+                    (&*last, &alignment)
+                } else {
+                    (&alignment, &*last)
+                };
+                let query_gap =
+                    last.query_end as usize..alignment.query_start.max(last.query_end) as usize;
+                let target_gap =
+                    first.target_end as usize..second.target_start.max(first.target_end) as usize;
+                gaps.push((query_gap, target_gap, last.reported.min(alignment.reported)));
+                last.merge(&alignment);
+            }
+            _ => merged.push(alignment),
+        }
+    }
+    *alignments = merged;
+    gaps
+}
+
+/// Pick a container for every contained read that is not rescued.
+/// Nested containments are followed until a read that is not contained itself, so the container ends up in the graph.
+/// Among several containers the longest read is preferred, ties are broken by read id.
+/// Returns contained read -> (container, strand, offset), the placement is composed along nested containments.
+fn resolve_containers(
+    contained_reads: &HashMap<usize, HashSet<usize>>,
+    placements: &HashMap<(usize, usize), (char, i64)>,
+    low_coverage_reads: &HashSet<usize>,
+    reads: &[Read],
+) -> HashMap<usize, (usize, char, i64)> {
+    // best direct container per contained read, low coverage containers are gone
+    let direct: HashMap<usize, usize> = contained_reads
+        .iter()
+        .filter_map(|(&read_id, containers)| {
+            containers
+                .iter()
+                .copied()
+                .filter(|c| !low_coverage_reads.contains(c))
+                .min_by_key(|&c| (std::cmp::Reverse(reads[c].length), c))
+                .map(|c| (read_id, c))
+        })
+        .collect();
+
+    let mut contained_in: HashMap<usize, (usize, char, i64)> = HashMap::new();
+    for &read_id in direct.keys() {
+        let mut container = direct[&read_id];
+        let (mut strand, mut offset) = placements[&(read_id, container)];
+        let mut seen: HashSet<usize> = HashSet::from([read_id]);
+        // follow nested containments, a containment cycle (identical reads) ends at the last new read
+        while let Some(&next) = direct.get(&container) {
+            if !seen.insert(container) || seen.contains(&next) {
+                break;
+            }
+            // place the read on the next container, through the current one
+            let (link_strand, link_offset) = placements[&(container, next)];
+            if link_strand == '+' {
+                offset += link_offset;
+            } else {
+                offset = link_offset + reads[container].length as i64
+                    - offset
+                    - reads[read_id].length as i64;
+                strand = if strand == '+' { '-' } else { '+' };
+            }
+            container = next;
+        }
+        contained_in.insert(read_id, (container, strand, offset));
+    }
+    contained_in
+}
+
+/// Detect chimeric reads from their coverage profile.
+/// A read is chimeric if it has an internal valley with coverage < max_valley_cov,
+/// where the `window` bases on both sides of the valley all have coverage >= min_flank_cov.
+/// With binned coverage the window is rounded up to whole bins.
+fn detect_chimeras(
+    reads: &[Read],
+    min_flank_cov: u32,
+    window: usize,
+    max_valley_cov: u32,
+) -> HashSet<usize> {
+    let mut chimeras: HashSet<usize> = HashSet::new();
+
+    for (id, read) in reads.iter().enumerate() {
+        let cov = &read.coverage.bins;
+        let window = window.div_ceil(read.coverage.bin_size).max(1);
+        let mut i = 0;
+        while i < cov.len() {
+            if cov[i] >= max_valley_cov {
+                i += 1;
+                continue;
+            }
+            // valley [start, end)
+            let start = i;
+            while i < cov.len() && cov[i] < max_valley_cov {
+                i += 1;
+            }
+            let end = i;
+
+            // the valley has to be internal with a full flank on both sides
+            if start < window || end + window > cov.len() {
+                continue;
+            }
+            let left_ok = cov[start - window..start]
+                .iter()
+                .all(|&c| c >= min_flank_cov);
+            let right_ok = cov[end..end + window].iter().all(|&c| c >= min_flank_cov);
+            if left_ok && right_ok {
+                chimeras.insert(id);
+                break;
+            }
+        }
+    }
+
+    chimeras
+}
+
+/// Keep the best `max_alignments` alignments of a read pair.
+/// Primary alignments go first, then the longest, then the one with the most matching bases and finally the one
+/// with the smallest coordinates, so the kept alignments don't depend on the order of the PAF lines.
+fn keep_best_alignments(alignments: &mut Vec<Alignment>, max_alignments: usize) {
+    alignments.sort_by_key(|a| {
+        (
+            std::cmp::Reverse(a.is_primary),
+            std::cmp::Reverse(a.alignment_block_length),
+            std::cmp::Reverse(a.num_matching),
+            a.canonical_coordinates(),
+        )
+    });
+    alignments.truncate(max_alignments);
+}
+
+// every alignment with its read pair, in the iteration order of the map
+fn alignments_in_order(
+    alignments: &HashMap<(usize, usize), Vec<Alignment>>,
+) -> Vec<(&(usize, usize), &Alignment)> {
+    alignments
+        .iter()
+        .flat_map(|(ids, alns)| alns.iter().map(move |a| (ids, a)))
+        .collect()
+}
+
+/// Filter PAF file based on overlap quality criteria.
+/// Duplicate reads in `read_aliases` are replaced by their representative before filtering.
 pub fn run_alignment_filtering(
     paf_in: &str,
-    min_overlap_length: &u32,
-    min_overlap_count: &u32,
-    min_percent_identity: &f32,
-    overhang_ratio: &f32,
+    options: &FilteringOptions,
+    read_aliases: &HashMap<String, String>,
 ) -> Result<AlignmentFilteringOutput, Box<dyn std::error::Error>> {
     // Setup data structures
     // read name to read id mapping
     let mut read_name2read_id: HashMap<String, usize> = HashMap::new();
     // vector of read objects, indexed by read id
     let mut reads: Vec<Read> = Vec::new();
-    // maps tuples of read ids to alignment objects
-    let mut alignments: HashMap<(usize, usize), Alignment> = HashMap::new();
+    // maps tuples of read ids to alignment objects, sorted from longest to shortest
+    let mut alignments: HashMap<(usize, usize), Vec<Alignment>> = HashMap::new();
     // vector to track existing alignments per read id
     // useful for querying alignments
     let mut alignment_ids_per_read: Vec<HashSet<usize>> = Vec::new();
     // initialize read id
     let mut next_id: usize = 0;
-    // keep track of contained reads
-    let mut contained_reads: HashSet<usize> = HashSet::new();
+    // keep track of contained reads and the reads they are contained in
+    let mut contained_reads: HashMap<usize, HashSet<usize>> = HashMap::new();
+    // (contained read, container) -> strand and offset of the contained read on the container
+    let mut placements: HashMap<(usize, usize), (char, i64)> = HashMap::new();
     // initialize overlap storage
-    let mut overlaps: HashMap<(usize, usize), Overlap> = HashMap::new();
+    let mut overlaps: HashMap<(usize, usize), Vec<Overlap>> = HashMap::new();
+    let max_alignments_per_pair = options.max_alignments_per_pair.max(1);
+
+    // self-alignments per read, used to annotate repeat-containing reads
+    let mut self_alignments: HashMap<String, SelfAlignments> = HashMap::new();
 
     let mut self_alignments_skipped: usize = 0;
     let mut alignment_length_skipped: usize = 0;
     let mut percent_identity_skipped: usize = 0;
+    let mut mapq_skipped: usize = 0;
+    let mut invalid_coordinates_skipped: usize = 0;
+    let mut too_few_fields_lines: usize = 0;
+    let mut invalid_number_lines: usize = 0;
+    let mut stats = FilteringStats::default();
 
     // read the alignments from the PAF file
-    let reader = BufReader::new(File::open(paf_in)?);
+    let reader = utils::open_reader(paf_in)?;
     for line in reader.lines() {
         let line = line?;
 
@@ -315,172 +1196,334 @@ pub fn run_alignment_filtering(
             continue;
         }
 
-        if let Some(record) = Alignment::from_line(&line) {
-            // skip self alignments
-            if record.is_self_alignment() {
-                self_alignments_skipped += 1;
+        let mut record = match Alignment::from_line(&line) {
+            Ok(record) => record,
+            Err(PafParseError::TooFewFields) => {
+                too_few_fields_lines += 1;
+                continue;
+            }
+            Err(PafParseError::InvalidNumber) => {
+                invalid_number_lines += 1;
                 continue;
             }
+        };
+        stats.alignments_read += 1;
+        // collapse duplicate reads into their representative, alignments between duplicates become self-alignments
+        if let Some(representative) = read_aliases.get(&record.query_name) {
+            record.query_name = representative.clone();
+        }
+        if let Some(representative) = read_aliases.get(&record.target_name) {
+            record.target_name = representative.clone();
+        }
+        // skip self alignments
+        if record.is_self_alignment() {
+            self_alignments_skipped += 1;
+            if options.self_overlaps_as_repeats {
+                let entry = self_alignments
+                    .entry(record.query_name.clone())
+                    .or_default();
+                entry.count += 1;
+                entry
+                    .offsets
+                    .push((record.query_start, record.target_start, record.strand));
+            }
+            continue;
+        }
 
-            let query_overlap_length = record.query_end - record.query_start;
-            let target_overlap_length = record.target_end - record.target_start;
+        let query_overlap_length = record.query_end - record.query_start;
+        let target_overlap_length = record.target_end - record.target_start;
 
-            // skip low overlap length alignments
-            if query_overlap_length < (*min_overlap_length).into()
-                || target_overlap_length < (*min_overlap_length).into()
-            {
-                alignment_length_skipped += 1;
-                continue;
+        // skip low overlap length alignments
+        if query_overlap_length < options.min_overlap_length.into()
+            || target_overlap_length < options.min_overlap_length.into()
+        {
+            alignment_length_skipped += 1;
+            continue;
+        }
+
+        // skip low percent identity alignments
+        if record.percent_identity() < options.min_percent_identity {
+            percent_identity_skipped += 1;
+            continue;
+        }
+
+        // skip ambiguous alignments, 255 means the mapping quality is not available
+        if record.mapq < options.min_mapq && record.mapq != 255 {
+            mapq_skipped += 1;
+            continue;
+        }
+        // Get or create read ids for query and target
+        let query_id = match read_name2read_id.get(&record.query_name) {
+            // we have seen this read before, get its id
+            Some(&id) => id,
+
+            // new read, assign new id and create read object
+            None => {
+                let id = next_id;
+                next_id += 1;
+                read_name2read_id.insert(record.query_name.clone(), id);
+                alignment_ids_per_read.push(HashSet::new());
+                // create new read object
+                reads.push(Read {
+                    id,
+                    name: record.query_name.clone(),
+                    length: record.query_length,
+                    coverage: CoverageProfile::new(
+                        record.query_length as usize,
+                        options.coverage_bin_size,
+                    ),
+                    coverage_start: 0,
+                    coverage_end: record.query_length,
+                });
+                id
             }
+        };
 
-            // skip low percent identity alignments
-            if record.percent_identity() < *min_percent_identity {
-                percent_identity_skipped += 1;
-                continue;
+        let target_id = match read_name2read_id.get(&record.target_name) {
+            Some(&id) => id,
+            None => {
+                let id = next_id;
+                next_id += 1;
+                read_name2read_id.insert(record.target_name.clone(), id);
+                alignment_ids_per_read.push(HashSet::new());
+                // create new read object
+                reads.push(Read {
+                    id,
+                    name: record.target_name.clone(),
+                    length: record.target_length,
+                    coverage: CoverageProfile::new(
+                        record.target_length as usize,
+                        options.coverage_bin_size,
+                    ),
+                    coverage_start: 0,
+                    coverage_end: record.target_length,
+                });
+                id
             }
-            // Get or create read ids for query and target
-            let query_id = match read_name2read_id.get(&record.query_name) {
-                // we have seen this read before, get its id
-                Some(&id) => id,
-
-                // new read, assign new id and create read object
-                None => {
-                    let id = next_id;
-                    next_id += 1;
-                    read_name2read_id.insert(record.query_name.clone(), id);
-                    alignment_ids_per_read.push(HashSet::new());
-                    // create new read object
-                    reads.push(Read {
-                        id,
-                        name: record.query_name.clone(),
-                        length: record.query_length,
-                        per_base_coverage: vec![0; record.query_length as usize],
-                        coverage_start: 0,
-                        coverage_end: record.query_length,
-                    });
-                    id
-                }
-            };
+        };
 
-            let target_id = match read_name2read_id.get(&record.target_name) {
-                Some(&id) => id,
-                None => {
-                    let id = next_id;
-                    next_id += 1;
-                    read_name2read_id.insert(record.target_name.clone(), id);
-                    alignment_ids_per_read.push(HashSet::new());
-                    // create new read object
-                    reads.push(Read {
-                        id,
-                        name: record.target_name.clone(),
-                        length: record.target_length,
-                        per_base_coverage: vec![0; record.target_length as usize],
-                        coverage_start: 0,
-                        coverage_end: record.target_length,
-                    });
-                    id
-                }
-            };
+        // clamp coordinates to the read lengths, some mappers report ends past the read end
+        let query_len = reads[query_id].length as i64;
+        let target_len = reads[target_id].length as i64;
+        record.query_start = record.query_start.clamp(0, query_len);
+        record.query_end = record.query_end.clamp(0, query_len);
+        record.target_start = record.target_start.clamp(0, target_len);
+        record.target_end = record.target_end.clamp(0, target_len);
+        if record.query_start > record.query_end || record.target_start > record.target_end {
+            invalid_coordinates_skipped += 1;
+            continue;
+        }
 
-            // extract needed info from the record
-            let (qstart, qend) = (record.query_start as usize, record.query_end as usize);
-            let (tstart, tend) = (record.target_start as usize, record.target_end as usize);
-
-            // store alignment record
-            // if multiple alignments exist between the same read pair, keep the longest one
-
-            if alignment_ids_per_read[query_id].contains(&target_id) {
-                // an alignment between these reads already exists, it may be stored under
-                // (query_id, target_id) or (target_id, query_id)
-                if let Some(existing) = alignments.get(&(query_id, target_id)) {
-                    if record.alignment_block_length > existing.alignment_block_length {
-                        // replace the existing directed entry
-                        alignments.insert((query_id, target_id), record.clone());
-                    }
-                } else if let Some(existing) = alignments.get(&(target_id, query_id)) {
-                    if record.alignment_block_length > existing.alignment_block_length {
-                        // remove the old reversed entry and store the new (keeps orientation of current record)
-                        alignments.remove(&(target_id, query_id));
-                        alignments.insert((query_id, target_id), record.clone());
-                    }
-                } else {
-                    println!("Warning: alignment existence inconsistency detected.");
+        // extract needed info from the record
+        let (qstart, qend) = (record.query_start as usize, record.query_end as usize);
+        let (tstart, tend) = (record.target_start as usize, record.target_end as usize);
+
+        // store alignment record
+        // if multiple alignments exist between the same read pair, keep the longest ones (by default only one)
+
+        if alignment_ids_per_read[query_id].contains(&target_id) {
+            // an alignment between these reads already exists, it may be stored under
+            // (query_id, target_id) or (target_id, query_id)
+            // the same alignment may be reported once from each read's perspective, store it once
+            let existing = if let Some(existing) = alignments.get_mut(&(query_id, target_id)) {
+                match existing.iter_mut().find(|a| a.same_coordinates(&record)) {
+                    Some(stored) => stored.reported += 1,
+                    None => existing.push(record.clone()),
                 }
+                Some(existing)
+            } else if let Some(existing) = alignments.get_mut(&(target_id, query_id)) {
+                // store the record with query and target swapped to match the key
+                let swapped = record.swapped();
+                match existing.iter_mut().find(|a| a.same_coordinates(&swapped)) {
+                    Some(stored) => stored.reported += 1,
+                    None => existing.push(swapped),
+                }
+                Some(existing)
+            } else {
+                warn!("alignment existence inconsistency detected.");
+                None
+            };
+            // split alignments are kept until they are merged
+            if let Some(existing) = existing.filter(|_| options.merge_split_gap.is_none()) {
+                keep_best_alignments(existing, max_alignments_per_pair);
             }
-            // we don't have an alignment between these reads yet
-            else {
-                alignment_ids_per_read[query_id].insert(target_id);
-                alignment_ids_per_read[target_id].insert(query_id);
-                alignments.insert((query_id, target_id), record);
-            }
-
-            // update read coverage statistics
-            reads[query_id].per_base_coverage[qstart as usize..qend as usize]
-                .iter_mut()
-                .for_each(|c| *c += 1);
-            reads[target_id].per_base_coverage[tstart as usize..tend as usize]
-                .iter_mut()
-                .for_each(|c| *c += 1);
         }
+        // we don't have an alignment between these reads yet
+        else {
+            alignment_ids_per_read[query_id].insert(target_id);
+            alignment_ids_per_read[target_id].insert(query_id);
+            alignments.insert((query_id, target_id), vec![record]);
+        }
+
+        // update read coverage statistics
+        reads[query_id].coverage.add(qstart..qend, 1);
+        reads[target_id].coverage.add(tstart..tend, 1);
     }
 
-    println!("=== ALIGNMENT FILTERING ===");
-    println!("=== PHASE 1: CRUDE FILTERING ===");
-    println!("Total self-alignments skipped: {}", self_alignments_skipped);
-    println!(
+    info!("=== ALIGNMENT FILTERING ===");
+    info!("=== PHASE 1: CRUDE FILTERING ===");
+    if let Some(max_gap) = options.merge_split_gap {
+        for ((query_id, target_id), existing) in alignments.iter_mut() {
+            // the bridged gaps count as covered, like the rest of the merged alignment
+            for (query_gap, target_gap, reported) in merge_colinear_alignments(existing, max_gap) {
+                reads[*query_id].coverage.add(query_gap, reported);
+                reads[*target_id].coverage.add(target_gap, reported);
+                stats.split_alignments_merged += 1;
+            }
+            keep_best_alignments(existing, max_alignments_per_pair);
+        }
+        info!(
+            "Merged {} colinear split alignments (max gap {})",
+            stats.split_alignments_merged, max_gap
+        );
+    }
+    // lines that are not valid PAF records, a high fraction usually means the input is not PAF at all
+    let malformed_lines = too_few_fields_lines + invalid_number_lines;
+    if malformed_lines > 0 {
+        let total_lines = malformed_lines + stats.alignments_read;
+        let msg = format!(
+            "{} of {} PAF lines could not be parsed ({} with fewer than 12 fields, {} with an invalid number)",
+            malformed_lines, total_lines, too_few_fields_lines, invalid_number_lines
+        );
+        if malformed_lines * 100 > total_lines {
+            warn!("{}", msg);
+        } else {
+            info!("{}", msg);
+        }
+    }
+    info!("Total self-alignments skipped: {}", self_alignments_skipped);
+    if options.self_overlaps_as_repeats {
+        info!(
+            "Reads with self-alignments: {} ({} with an internal repeat)",
+            self_alignments.len(),
+            self_alignments.values().filter(|s| s.is_repeat()).count()
+        );
+    }
+    info!(
         "Total alignments skipped due to length filter: {}",
         alignment_length_skipped
     );
-    println!(
+    info!(
         "Total alignments skipped due to percent identity filter: {}",
         percent_identity_skipped
     );
-    println!("Total reads kept: {}", reads.len());
-    println!("Total alignments kept: {}", alignments.len());
-    println!("=== PHASE 1 FINISHED ===");
-    println!("=== PHASE 2: COVERAGE CALCULATION ===");
+    info!(
+        "Total alignments skipped due to mapping quality filter: {}",
+        mapq_skipped
+    );
+    if invalid_coordinates_skipped > 0 {
+        warn!(
+            "{} alignments skipped due to start > end after clamping to the read length",
+            invalid_coordinates_skipped
+        );
+    }
+    stats.too_few_fields_lines = too_few_fields_lines;
+    stats.invalid_number_lines = invalid_number_lines;
+    stats.self_alignments_skipped = self_alignments_skipped;
+    stats.length_filter_skipped = alignment_length_skipped;
+    stats.identity_filter_skipped = percent_identity_skipped;
+    stats.mapq_filter_skipped = mapq_skipped;
+    stats.reads_kept = reads.len();
+    stats.alignments_kept = alignments.values().map(Vec::len).sum();
+    info!("Total reads kept: {}", stats.reads_kept);
+    info!("Total alignments kept: {}", stats.alignments_kept);
+    // overlap degree per read, helps choosing --min-overlap-count
+    let mut overlap_degrees: Vec<(String, usize)> = reads
+        .iter()
+        .map(|r| (r.name.clone(), alignment_ids_per_read[r.id].len()))
+        .collect();
+    overlap_degrees.sort_unstable();
+    let mut degrees: Vec<usize> = overlap_degrees.iter().map(|(_, d)| *d).collect();
+    degrees.sort_unstable();
+    if let Some(&median) = degrees.get(degrees.len() / 2) {
+        info!(
+            "Overlapping reads per read: median {}, mean {:.2}",
+            median,
+            degrees.iter().sum::<usize>() as f64 / degrees.len() as f64
+        );
+    }
+    info!("=== PHASE 1 FINISHED ===");
+    info!("=== PHASE 2: COVERAGE CALCULATION ===");
 
     // all alignments have been read
     // store subregions with coverage >= 3 per read
     for read in &mut reads {
-        let mut cur_len = 0;
-        let mut cur_start = 0;
-        let mut best_len = 0;
-        let mut best = (0, 0);
-        for (i, x) in read.per_base_coverage.iter().enumerate() {
-            if x > min_overlap_count {
-                if cur_len == 0 {
-                    cur_start = i;
-                }
-                cur_len += 1;
-                if cur_len > best_len {
-                    best_len = cur_len;
-                    best = (cur_start, i + 1); // end is exclusive
-                }
-            } else {
-                cur_len = 0;
+        let best = read.coverage.longest_run_above(options.min_overlap_count);
+        read.coverage_start = best.start as u32;
+        read.coverage_end = best.end as u32;
+    }
+
+    // drop all alignments of chimeric reads
+    if let Some(params) = &options.chimera {
+        let chimeras = detect_chimeras(
+            &reads,
+            params.min_flank_cov,
+            params.window,
+            params.max_valley_cov,
+        );
+        info!("Chimeric reads detected: {}", chimeras.len());
+        stats.chimeric_reads = chimeras.len();
+        alignments.retain(|(q_id, t_id), _| !chimeras.contains(q_id) && !chimeras.contains(t_id));
+    }
+
+    info!("=== PHASE 2 FINISHED ===");
+    info!("=== PHASE 3: ALIGNMENT CLASSIFICATION ===");
+
+    // clip the read ends before the classification, the coverage was counted over the full reads
+    let mut unclipped_types: Vec<AlignmentType> = Vec::new();
+    if options.trim_overhangs > 0 {
+        // the classification without clipping, to report the alignments that change
+        let mut scratch: HashMap<(usize, usize), Vec<Overlap>> = HashMap::new();
+        unclipped_types = alignments_in_order(&alignments)
+            .into_iter()
+            .map(|((query_id, target_id), alignment)| {
+                classify_alignment(
+                    alignment,
+                    *query_id,
+                    *target_id,
+                    &mut scratch,
+                    &reads,
+                    options,
+                )
+            })
+            .collect();
+
+        // at most half of a read is clipped from either end, so the clipped length never goes negative
+        let clips: Vec<u32> = reads
+            .iter()
+            .map(|r| options.trim_overhangs.min(r.length / 2))
+            .collect();
+        // only the values change, the iteration order of the alignments stays the same
+        for ((query_id, target_id), alns) in alignments.iter_mut() {
+            for alignment in alns {
+                alignment.clip_ends(clips[*query_id] as i64, clips[*target_id] as i64);
             }
         }
-        read.coverage_start = best.0 as u32;
-        read.coverage_end = best.1 as u32;
+        for (read, clip) in reads.iter_mut().zip(clips) {
+            read.length -= 2 * clip;
+            read.coverage_start = read.coverage_start.max(clip) - clip;
+            read.coverage_end = (read.coverage_end.min(read.length + clip))
+                .saturating_sub(clip)
+                .max(read.coverage_start);
+        }
     }
 
-    println!("=== PHASE 2 FINISHED ===");
-    println!("=== PHASE 3: ALIGNMENT CLASSIFICATION ===");
-
     // classify alignments and update contained reads set
-    for ((query_id, target_id), alignment) in &alignments {
-        //let alignment_type = match classify_alignment(alignment, *query_id, *target_id, &mut overlaps, *max_overhang, *overhang_ratio, &reads, min_overlap_length) {
-        let _alignment_type = match classify_alignment(
+    for (i, ((query_id, target_id), alignment)) in
+        alignments_in_order(&alignments).into_iter().enumerate()
+    {
+        let alignment_type = classify_alignment(
             alignment,
             *query_id,
             *target_id,
             &mut overlaps,
-            4000000000,
-            (*overhang_ratio) as f64,
             &reads,
-            *min_overlap_length,
-        ) {
+            options,
+        );
+        if unclipped_types.get(i).is_some_and(|t| *t != alignment_type) {
+            stats.trim_reclassified_alignments += 1;
+        }
+        match alignment_type {
             AlignmentType::Filtered => {
                 continue; // skip filtered alignments
             }
@@ -489,12 +1532,36 @@ pub fn run_alignment_filtering(
             }
             AlignmentType::FirstContained => {
                 // mark first read as contained
-                contained_reads.insert(*query_id);
+                contained_reads
+                    .entry(*query_id)
+                    .or_default()
+                    .insert(*target_id);
+                // start of the oriented query on the target
+                let offset = if alignment.strand == '+' {
+                    alignment.target_start - alignment.query_start
+                } else {
+                    alignment.target_start - (alignment.query_length as i64 - alignment.query_end)
+                };
+                placements
+                    .entry((*query_id, *target_id))
+                    .or_insert((alignment.strand, offset));
                 continue;
             }
             AlignmentType::SecondContained => {
                 // mark second read as contained
-                contained_reads.insert(*target_id);
+                contained_reads
+                    .entry(*target_id)
+                    .or_default()
+                    .insert(*query_id);
+                // start of the oriented target on the query
+                let offset = if alignment.strand == '+' {
+                    alignment.query_start - alignment.target_start
+                } else {
+                    alignment.query_start - (alignment.target_length as i64 - alignment.target_end)
+                };
+                placements
+                    .entry((*target_id, *query_id))
+                    .or_insert((alignment.strand, offset));
                 continue;
             }
             AlignmentType::ProperOverlap => {
@@ -502,46 +1569,316 @@ pub fn run_alignment_filtering(
             }
         };
     }
+    if options.trim_overhangs > 0 {
+        info!(
+            "Clipping {} bp from the read ends changed the classification of {} alignments",
+            options.trim_overhangs, stats.trim_reclassified_alignments
+        );
+    }
 
-    println!("Total overlaps after classification: {}", overlaps.len());
+    let overlap_count = |overlaps: &HashMap<(usize, usize), Vec<Overlap>>| -> usize {
+        overlaps.values().map(Vec::len).sum()
+    };
+    stats.overlaps_after_classification = overlap_count(&overlaps);
+    info!(
+        "Total overlaps after classification: {}",
+        stats.overlaps_after_classification
+    );
 
     // filter contained reads from overlaps
-    overlaps.retain(|(q_id, t_id), _| {
-        !contained_reads.contains(q_id) && !contained_reads.contains(t_id)
-    });
+    // the removed overlaps are kept aside, they are needed to rescue contained reads later on
+    let (kept_overlaps, mut contained_overlaps): (HashMap<_, _>, HashMap<_, _>) =
+        overlaps.into_iter().partition(|((q_id, t_id), _)| {
+            !contained_reads.contains_key(q_id) && !contained_reads.contains_key(t_id)
+        });
+    overlaps = kept_overlaps;
 
-    println!(
+    stats.overlaps_after_containment = overlap_count(&overlaps);
+    info!(
         "Total overlaps after removing contained reads: {}",
-        overlaps.len()
+        stats.overlaps_after_containment
     );
 
-    // get unique reads from overlaps
-    let unique_reads: HashSet<usize> = overlaps
-        .keys()
-        .flat_map(|(q_id, t_id)| vec![*q_id, *t_id])
-        .collect();
-
     // filter low coverage reads
-    let threshold = *min_overlap_count as u32;
+    let threshold = options.min_overlap_count;
     let low_coverage_reads: Vec<_> = reads
         .iter()
         .enumerate()
-        .filter(|(_, r)| r.per_base_coverage.iter().copied().max().unwrap_or(0) < threshold)
+        .filter(|(_, r)| r.coverage.max() < threshold)
         .map(|(id, _)| id)
         .collect();
     overlaps.retain(|(q_id, t_id), _| {
         !low_coverage_reads.contains(q_id) && !low_coverage_reads.contains(t_id)
     });
-    println!(
+
+    // rescue contained reads whose containers were all removed as low coverage
+    let low_coverage_reads: HashSet<usize> = low_coverage_reads.into_iter().collect();
+    let rescued = rescue_contained_reads(
+        &contained_reads,
+        &low_coverage_reads,
+        &mut contained_overlaps,
+        &mut overlaps,
+    );
+    info!(
+        "Rescued {} contained reads whose containers were removed",
+        rescued
+    );
+    // reads left in the overlaps, after the low coverage filter and including the rescued reads
+    let unique_reads: HashSet<usize> = overlaps
+        .keys()
+        .flat_map(|(q_id, t_id)| [*q_id, *t_id])
+        .collect();
+    info!(
         "Total number of reads for graph creation: {}",
         unique_reads.len()
     );
-    println!(
+    stats.rescued_contained_reads = rescued;
+
+    // side table of contained reads and their containers
+    let contained_in: HashMap<String, ContainedPlacement> = if options.record_contained {
+        resolve_containers(&contained_reads, &placements, &low_coverage_reads, &reads)
+            .into_iter()
+            .map(|(read_id, (container, strand, offset))| {
+                (
+                    reads[read_id].name.clone(),
+                    ContainedPlacement {
+                        container: reads[container].name.clone(),
+                        strand,
+                        offset,
+                    },
+                )
+            })
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    if options.record_contained {
+        info!(
+            "Recorded the containers of {} contained reads",
+            contained_in.len()
+        );
+    }
+    // mean coverage per read, over the full read length
+    let read_coverage: HashMap<String, f64> = reads
+        .iter()
+        .filter(|r| r.length > 0)
+        .map(|r| (r.name.clone(), r.coverage.mean()))
+        .collect();
+
+    stats.overlaps_for_graph = overlap_count(&overlaps);
+    info!(
         "Total number of overlaps for graph creation: {}",
-        overlaps.len()
+        stats.overlaps_for_graph
     );
-    println!("=== PHASE 3 FINISHED ===");
-    println!("=== ALIGNMENT FILTERING FINISHED ===");
+    info!("=== PHASE 3 FINISHED ===");
+    info!("=== ALIGNMENT FILTERING FINISHED ===");
+
+    Ok(AlignmentFilteringOutput {
+        overlaps,
+        multi_alignments: max_alignments_per_pair > 1,
+        self_alignments,
+        contained_in,
+        read_coverage,
+        stats,
+        overlap_degrees,
+        params: FilteringParams {
+            min_overlap_length: options.min_overlap_length,
+            min_percent_identity: options.min_percent_identity,
+            overhang_ratio: options.overhang_ratio,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read covered from end to end
+    fn read(id: usize, name: &str, length: u32) -> Read {
+        Read {
+            id,
+            name: name.to_string(),
+            length,
+            coverage: CoverageProfile::new(length as usize, 1),
+            coverage_start: 0,
+            coverage_end: length,
+        }
+    }
+
+    /// Reads q (id 0) and t (id 1)
+    fn reads(query_length: u32, target_length: u32) -> Vec<Read> {
+        vec![read(0, "q", query_length), read(1, "t", target_length)]
+    }
 
-    Ok(AlignmentFilteringOutput { overlaps })
+    /// Alignment of q to t parsed from a PAF line, every aligned base matches
+    fn alignment(query: (u32, i64, i64), strand: char, target: (u32, i64, i64)) -> Alignment {
+        let block = (query.2 - query.1).max(target.2 - target.1);
+        let line = format!(
+            "q\t{}\t{}\t{}\t{}\tt\t{}\t{}\t{}\t{}\t{}\t60\ttp:A:P",
+            query.0, query.1, query.2, strand, target.0, target.1, target.2, block, block
+        );
+        Alignment::from_line(&line).unwrap()
+    }
+
+    /// Classify with an overhang ratio of 0.8 and a minimum overlap of 500,
+    /// returns the type and the overlaps stored for the pair
+    fn classify(
+        r: &Alignment,
+        reads: &[Read],
+        max_overhang: u32,
+        fuzz: u32,
+    ) -> (AlignmentType, Vec<Overlap>) {
+        let options = FilteringOptions {
+            min_overlap_length: 500,
+            overhang_ratio: 0.8,
+            max_overhang,
+            containment_fuzz: fuzz,
+            ..FilteringOptions::default()
+        };
+        let mut overlaps: HashMap<(usize, usize), Vec<Overlap>> = HashMap::new();
+        let alignment_type = classify_alignment(r, 0, 1, &mut overlaps, reads, &options);
+        (alignment_type, overlaps.remove(&(0, 1)).unwrap_or_default())
+    }
+
+    /// (source, sink, rc source, rc sink, edge length, rc edge length) of an overlap
+    fn edges(o: &Overlap) -> (&str, &str, &str, &str, u32, u32) {
+        (
+            &o.source_name,
+            &o.sink_name,
+            &o.rc_source_name,
+            &o.rc_sink_name,
+            o.edge_len,
+            o.rc_edge_len,
+        )
+    }
+
+    #[test]
+    fn identity_from_divergence_tags() {
+        let line = "q\t2000\t0\t1000\t+\tt\t2000\t0\t1000\t800\t1000\t60";
+        let identity = |tags: &str| {
+            Alignment::from_line(&format!("{}{}", line, tags))
+                .unwrap()
+                .percent_identity()
+        };
+        assert!((identity("\tde:f:0.01") - 99.0).abs() < 1e-4);
+        assert!((identity("\tdv:f:0.05") - 95.0).abs() < 1e-4);
+        // de:f is preferred over dv:f, in either order
+        assert!((identity("\tdv:f:0.05\tde:f:0.01") - 99.0).abs() < 1e-4);
+        assert!((identity("\tde:f:0.01\tdv:f:0.05") - 99.0).abs() < 1e-4);
+        // without a tag the identity is matches / block length
+        assert!((identity("\ttp:A:P") - 80.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn internal_match() {
+        // 500 bp unaligned on both sides of both reads, more than 0.8 * 1000
+        let r = alignment((2000, 500, 1500), '+', (2000, 500, 1500));
+        let (alignment_type, overlaps) = classify(&r, &reads(2000, 2000), 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::InternalMatch);
+        assert!(overlaps.is_empty());
+    }
+
+    #[test]
+    fn contained_reads() {
+        let r = alignment((1000, 0, 1000), '+', (2000, 500, 1500));
+        assert_eq!(
+            classify(&r, &reads(1000, 2000), 1000, 0).0,
+            AlignmentType::FirstContained
+        );
+        let r = alignment((2000, 500, 1500), '+', (1000, 0, 1000));
+        assert_eq!(
+            classify(&r, &reads(2000, 1000), 1000, 0).0,
+            AlignmentType::SecondContained
+        );
+        // on the reverse strand the target is contained in its reverse complement orientation
+        let r = alignment((2000, 500, 1500), '-', (1000, 0, 1000));
+        assert_eq!(
+            classify(&r, &reads(2000, 1000), 1000, 0).0,
+            AlignmentType::SecondContained
+        );
+    }
+
+    #[test]
+    fn short_overlap_is_filtered() {
+        let r = alignment((2000, 1700, 2000), '+', (2000, 0, 300));
+        let (alignment_type, overlaps) = classify(&r, &reads(2000, 2000), 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::Filtered);
+        assert!(overlaps.is_empty());
+    }
+
+    #[test]
+    fn proper_overlap_from_query_to_target() {
+        // the suffix of q overlaps the prefix of t, t extends 1000 bp past the end of q
+        let r = alignment((2000, 500, 2000), '+', (2500, 0, 1500));
+        let (alignment_type, overlaps) = classify(&r, &reads(2000, 2500), 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::ProperOverlap);
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(edges(&overlaps[0]), ("q+", "t+", "t-", "q-", 500, 1000));
+        assert_eq!(overlaps[0].overlap_len, 1500);
+
+        // t aligns on the reverse strand, the overlap leads to t-
+        let r = alignment((2000, 500, 2000), '-', (2500, 1000, 2500));
+        let (alignment_type, overlaps) = classify(&r, &reads(2000, 2500), 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::ProperOverlap);
+        assert_eq!(edges(&overlaps[0]), ("q+", "t-", "t+", "q-", 500, 1000));
+    }
+
+    #[test]
+    fn proper_overlap_from_target_to_query() {
+        // the suffix of t overlaps the prefix of q, q extends 1000 bp past the end of t
+        let r = alignment((2500, 0, 1500), '+', (2000, 500, 2000));
+        let (alignment_type, overlaps) = classify(&r, &reads(2500, 2000), 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::ProperOverlap);
+        assert_eq!(edges(&overlaps[0]), ("t+", "q+", "q-", "t-", 500, 1000));
+    }
+
+    #[test]
+    fn max_overhang_bounds_the_ratio() {
+        // t has 500 bp before the overlap that don't align to q, the overlap is 1500 bp long
+        let r = alignment((2500, 1000, 2500), '+', (3000, 500, 2000));
+        let reads = reads(2500, 3000);
+        // 500 bp is within the default maximum of 1000 and within 0.8 * 1500
+        let (alignment_type, overlaps) = classify(&r, &reads, 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::ProperOverlap);
+        assert_eq!(edges(&overlaps[0]), ("q+", "t+", "t-", "q-", 500, 1000));
+        // 0 leaves only the ratio
+        assert_eq!(classify(&r, &reads, 0, 0).0, AlignmentType::ProperOverlap);
+        assert_eq!(classify(&r, &reads, 400, 0).0, AlignmentType::InternalMatch);
+    }
+
+    #[test]
+    fn containment_fuzz_boundary() {
+        // q sticks out `overhang` bases before the start of t and ends inside t
+        let classify_overhang = |overhang: i64, fuzz: u32| {
+            let r = alignment((1000, overhang, 1000), '+', (2000, 0, 1000 - overhang));
+            classify(&r, &reads(1000, 2000), 1000, fuzz)
+        };
+        assert_eq!(classify_overhang(0, 0).0, AlignmentType::FirstContained);
+        for overhang in [9, 10] {
+            assert_eq!(
+                classify_overhang(overhang, 10).0,
+                AlignmentType::FirstContained,
+                "overhang {}",
+                overhang
+            );
+        }
+        // one base past the fuzz is an overlap with an edge as long as the overhang
+        let (alignment_type, overlaps) = classify_overhang(11, 10);
+        assert_eq!(alignment_type, AlignmentType::ProperOverlap);
+        assert_eq!(edges(&overlaps[0]), ("q+", "t+", "t-", "q-", 11, 1011));
+        assert_eq!(classify_overhang(1, 0).0, AlignmentType::ProperOverlap);
+    }
+
+    #[test]
+    fn non_positive_edge_length_is_filtered() {
+        // the coverage window of q ends at 1500, q looks like it ends 100 bp after the overlap while the full
+        // read runs 1600 bp past it, longer than the 1100 bp t runs past the overlap. The rc edge t- -> q-
+        // would get a negative length
+        let mut reads = reads(3000, 2000);
+        reads[0].coverage_end = 1500;
+        let r = alignment((3000, 500, 1400), '+', (2000, 0, 900));
+        let (alignment_type, overlaps) = classify(&r, &reads, 1000, 0);
+        assert_eq!(alignment_type, AlignmentType::Filtered);
+        assert!(overlaps.is_empty());
+    }
 }
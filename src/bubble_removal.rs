@@ -1,59 +1,132 @@
-use crate::create_overlap_graph::OverlapGraph;
+use crate::create_overlap_graph::{NodeIndex, OverlapGraph, rc_index};
 use crate::utils;
 /// Bubble removal module
 /// using a "tour bus" style (BFS) search
 /// 1. for each node `u` with out-degree >= 2, consider every pair of distinct
-///   outgoing neighbors (v, w).
+///    outgoing neighbors (v, w).
 /// 2. perform a bounded BFS from v and from w (depth limited by `max_bubble_len`) to
-///   discover meeting nodes m where the two searches converge.
+///    discover meeting nodes m where the two searches converge.
 /// 3. for the best meeting node (smallest combined depth), reconstruct the two
-///   u->...->m paths, compute a simple path score (number of nodes, percentage identity) and pick
-///   the higher-scoring path to keep. If the higher score is at least
-///   `min_support_ratio * lower_score` (e.g. 1.1 to require 10% stronger), then the
-///   lower-scoring path is removed (internal nodes removed, excluding u and m).
+///    u->...->m paths, compute a simple path score (number of nodes, percentage identity) and pick
+///    the higher-scoring path to keep. If the higher score is at least
+///    `min_support_ratio * lower_score` (e.g. 1.1 to require 10% stronger), then the
+///    lower-scoring path is removed (internal nodes removed, excluding u and m).
 /// 4. removals are RC-aware: the reverse-complement node for each removed node is
-///   also removed, and all incoming edges to removed nodes are purged.
+///    also removed, and all incoming edges to removed nodes are purged.
+/// 5. afterwards, superbubbles (nested or multi-path bubbles) are detected and resolved by keeping
+///    the best scoring path from their entrance to their exit.
+use log::info;
 use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Path metrics for scoring
 #[derive(Clone, Default)]
 struct PathMetrics {
     read_count: u32,
-    total_overlap_len: u32,
-    avg_identity: f64,
+    // u64, the overlaps of a long path can sum past u32::MAX
+    total_overlap_len: u64,
+    // sum of identity * overlap length, the average is derived from it
+    weighted_identity: f64,
+    // plain sum of identities and edge count, used when all overlaps have length 0
+    identity_sum: f64,
+    edge_count: u32,
 }
 
+impl PathMetrics {
+    /// Metrics of the path extended by one edge
+    fn extend(&self, overlap_len: u32, identity: f64) -> Self {
+        Self {
+            read_count: self.read_count + 1,
+            total_overlap_len: self.total_overlap_len + overlap_len as u64,
+            weighted_identity: self.weighted_identity + identity * overlap_len as f64,
+            identity_sum: self.identity_sum + identity,
+            edge_count: self.edge_count + 1,
+        }
+    }
+
+    /// Identity of the path, averaged over its edges weighted by overlap length
+    fn avg_identity(&self) -> f64 {
+        if self.total_overlap_len > 0 {
+            self.weighted_identity / self.total_overlap_len as f64
+        } else if self.edge_count > 0 {
+            self.identity_sum / self.edge_count as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Scale of the identity term of the path score, brings percentages to the magnitude of overlap lengths.
+/// Unscaled, identity is lost next to overlaps of thousands of bases: two branches with the same overlaps
+/// and 80 vs 99 percent identity would never reach a `min_support_ratio` of 1.1.
+/// With the default weights one percentage point of identity weighs as much as 200 bases of overlap.
+const IDENTITY_SCALE: f64 = 100.0;
+
+/// Weights of the path metrics in the bubble path score
+pub struct BubbleScoringWeights {
+    pub overlap: f64,
+    pub identity: f64,
+    pub read_count: f64,
+}
+
+impl Default for BubbleScoringWeights {
+    fn default() -> Self {
+        Self {
+            overlap: 1.0,
+            identity: 2.0,
+            read_count: 1.5,
+        }
+    }
+}
+
+impl BubbleScoringWeights {
+    /// Composite score of a path, higher is better.
+    /// Identities are percentages (0-100) like everywhere in the graph, one percentage point of identity
+    /// weighs as much as IDENTITY_SCALE * identity weight bases of overlap.
+    fn score(&self, metrics: &PathMetrics) -> f64 {
+        (metrics.total_overlap_len as f64 * self.overlap)
+            + (metrics.avg_identity() * self.identity * IDENTITY_SCALE)
+            + (metrics.read_count as f64 * self.read_count)
+    }
+}
+
+/// Result of a bounded BFS: parent map, depth map and path metrics map
+type BfsResult = (
+    HashMap<NodeIndex, Option<NodeIndex>>,
+    HashMap<NodeIndex, usize>,
+    HashMap<NodeIndex, PathMetrics>,
+);
+
 /// Bounded BFS from a start node
 ///   - parent map (node -> parent)
 ///   - depth map (node -> depth from start)
 ///   - path metrics map (node -> PathMetrics)
+///
+/// The metrics include the entry edge (overlap length, identity) that leads into the start node,
+/// so a path consisting of a single edge still gets a meaningful score.
+/// The `forbidden` node (the divergence node of the bubble) is never expanded into, so the search can't
+/// walk back through it into the other branch.
 fn bfs_limited(
     graph: &OverlapGraph,
-    start: &str,
+    start: NodeIndex,
     max_depth: usize,
-) -> (
-    HashMap<String, Option<String>>,
-    HashMap<String, usize>,
-    HashMap<String, PathMetrics>,
-) {
+    entry_edge: (u32, f64),
+    forbidden: NodeIndex,
+) -> BfsResult {
     // initialize
-    let mut parent: HashMap<String, Option<String>> = HashMap::new(); // map a node to its previous node in the path
-    let mut depth: HashMap<String, usize> = HashMap::new(); // map a node to its depth from start
-    let mut metrics: HashMap<String, PathMetrics> = HashMap::new(); // map a node to its path metrics
+    let mut parent: HashMap<NodeIndex, Option<NodeIndex>> = HashMap::new(); // map a node to its previous node in the path
+    let mut depth: HashMap<NodeIndex, usize> = HashMap::new(); // map a node to its depth from start
+    let mut metrics: HashMap<NodeIndex, PathMetrics> = HashMap::new(); // map a node to its path metrics
 
     // vecdeque is a double ended queue, allows efficient popping from front
-    let mut q: VecDeque<String> = VecDeque::new();
-    parent.insert(start.to_string(), None);
-    depth.insert(start.to_string(), 0);
+    let mut q: VecDeque<NodeIndex> = VecDeque::new();
+    parent.insert(start, None);
+    depth.insert(start, 0);
     metrics.insert(
-        start.to_string(),
-        PathMetrics {
-            read_count: 1,
-            total_overlap_len: 0,
-            avg_identity: 1.0,
-        },
+        start,
+        // the entry edge counts as the first edge of the path
+        PathMetrics::default().extend(entry_edge.0, entry_edge.1),
     );
-    q.push_back(start.to_string());
+    q.push_back(start);
 
     // BFS loop (uses a fifo queue, push to the back, pop from the front)
     while let Some(cur) = q.pop_front() {
@@ -64,30 +137,22 @@ fn bfs_limited(
         }
 
         // expand neighbours
-        if let Some(node) = graph.nodes.get(&cur) {
+        if let Some(node) = graph.node(cur) {
             for edge in &node.edges {
                 // if the edge target is unseen, add it to parent/depth/metrics
-                if !depth.contains_key(&edge.target_id) {
-                    parent.insert(edge.target_id.clone(), Some(cur.clone()));
-                    depth.insert(edge.target_id.clone(), cur_depth + 1);
+                if edge.target != forbidden && !depth.contains_key(&edge.target) {
+                    parent.insert(edge.target, Some(cur));
+                    depth.insert(edge.target, cur_depth + 1);
 
                     // update path metrics, get metrics from current node and update them for the target node
-                    let mut new_metrics = metrics.get(&cur).cloned().unwrap_or_default();
-                    new_metrics.read_count += 1;
-                    new_metrics.total_overlap_len += edge.overlap_len;
-                    // update running average of identity
-                    let old_weight = new_metrics.total_overlap_len as f64 - edge.overlap_len as f64;
-                    let new_identity = if old_weight > 0.0 {
-                        (new_metrics.avg_identity * old_weight
-                            + edge.identity * edge.overlap_len as f64)
-                            / new_metrics.total_overlap_len as f64
-                    } else {
-                        edge.identity
-                    };
-                    new_metrics.avg_identity = new_identity;
-
-                    metrics.insert(edge.target_id.clone(), new_metrics);
-                    q.push_back(edge.target_id.clone());
+                    let new_metrics = metrics
+                        .get(&cur)
+                        .cloned()
+                        .unwrap_or_default()
+                        .extend(edge.overlap_len, edge.identity);
+
+                    metrics.insert(edge.target, new_metrics);
+                    q.push_back(edge.target);
                 }
             }
         }
@@ -97,23 +162,27 @@ fn bfs_limited(
 }
 
 /// Reconstruct path from source to sink using the parent map returned by bfs_limited
-/// If sink is not reachable, returns an empty Vec
+/// If sink is not reachable or the parent chain loops, returns an empty Vec
 fn reconstruct_path(
-    parent: &HashMap<String, Option<String>>,
-    source: &str,
-    sink: &str,
-) -> Vec<String> {
+    parent: &HashMap<NodeIndex, Option<NodeIndex>>,
+    source: NodeIndex,
+    sink: NodeIndex,
+) -> Vec<NodeIndex> {
     // the path will be reconstructed in reverse
-    let mut path_rev: Vec<String> = Vec::new();
-    let mut cur = sink.to_string();
-    path_rev.push(cur.clone());
-
-    while let Some(Some(p)) = parent.get(&cur) {
-        cur = p.clone();
-        path_rev.push(cur.clone());
+    let mut path_rev: Vec<NodeIndex> = Vec::new();
+    let mut cur = sink;
+    path_rev.push(cur);
+
+    while let Some(&Some(p)) = parent.get(&cur) {
+        // a chain longer than the number of nodes in the map must contain a cycle
+        if path_rev.len() > parent.len() {
+            return Vec::new();
+        }
+        cur = p;
+        path_rev.push(cur);
     }
     // We expect the last item to be source
-    if path_rev.last().map(|s| s.as_str()) != Some(source) {
+    if path_rev.last() != Some(&source) {
         return Vec::new();
     }
 
@@ -121,139 +190,638 @@ fn reconstruct_path(
     path_rev
 }
 
-/// Remove simple bubbles in the overlap graph.
-pub fn remove_bubbles(graph: &mut OverlapGraph, max_bubble_len: usize, min_support_ratio: f64) {
-    if max_bubble_len == 0 {
-        return;
+/// Superbubble check of Onodera et al. (2013) for a single entrance node.
+/// Walks the graph from `entrance`, only visiting a node once all its parents are visited, until a single
+/// node is left that every open path leads to. Returns that exit and the interior nodes in topological
+/// order, or None if the region contains a tip, a cycle through the entrance or more than `max_size` nodes.
+fn superbubble_exit(
+    graph: &OverlapGraph,
+    entrance: NodeIndex,
+    max_size: usize,
+) -> Option<(NodeIndex, Vec<NodeIndex>)> {
+    let mut stack: Vec<NodeIndex> = vec![entrance];
+    // the nodes on the stack, to check membership without scanning it
+    let mut on_stack: HashSet<NodeIndex> = HashSet::from([entrance]);
+    let mut visited: HashSet<NodeIndex> = HashSet::new();
+    // nodes reached by an edge but not visited yet
+    let mut seen: HashSet<NodeIndex> = HashSet::new();
+    let mut order: Vec<NodeIndex> = Vec::new();
+
+    while let Some(v) = stack.pop() {
+        on_stack.remove(&v);
+        seen.remove(&v);
+        visited.insert(v);
+        order.push(v);
+        if order.len() > max_size {
+            return None;
+        }
+
+        let node = graph.node(v)?;
+        // a tip inside the region
+        if node.edges.is_empty() {
+            return None;
+        }
+        for edge in &node.edges {
+            let u = edge.target;
+            // a cycle back to the entrance
+            if u == entrance {
+                return None;
+            }
+            seen.insert(u);
+            let parents_visited = graph
+                .node(u)
+                .is_some_and(|n| n.in_edges().iter().all(|p| visited.contains(p)));
+            if parents_visited && on_stack.insert(u) {
+                stack.push(u);
+            }
+        }
+
+        // every open path converges in the single node on the stack
+        if stack.len() == 1 && seen.len() == 1 && seen.contains(&stack[0]) {
+            let exit = stack.pop()?;
+            // the exit may not link straight back to the entrance
+            if graph
+                .node(exit)
+                .is_some_and(|n| n.edges.iter().any(|e| e.target == entrance))
+            {
+                return None;
+            }
+            order.remove(0);
+            return Some((exit, order));
+        }
     }
+    None
+}
 
-    // snapshot of nodes to iterate safely
-    let node_keys: Vec<String> = graph.nodes.keys().cloned().collect();
+/// Find superbubbles: (entrance, exit) pairs where every path leaving the entrance reaches the exit
+/// without leaving the region, and the region is acyclic and has no tips.
+/// Only entrances with at least 2 outgoing edges are tried, so plain paths aren't reported.
+/// Each entrance runs the bounded check of `superbubble_exit`, which visits at most `max_size` nodes,
+/// so the work is bounded per entrance for a fixed `max_size`.
+pub fn find_superbubbles(graph: &OverlapGraph, max_size: usize) -> Vec<(String, String)> {
+    superbubbles(graph, max_size)
+        .into_iter()
+        .map(|(entrance, exit)| (graph.node_id(entrance), graph.node_id(exit)))
+        .collect()
+}
 
-    for n in node_keys.iter() {
-        // get outgoing neighbors (clone so we don't borrow across mutation)
-        let outgoing = match graph.nodes.get(n) {
-            Some(n) => n
-                .edges
-                .iter()
-                .map(|e| (e.target_id.clone(), e.edge_len))
-                .collect::<Vec<_>>(),
-            None => continue,
+/// Superbubbles as (entrance, exit) node indices, see `find_superbubbles`
+fn superbubbles(graph: &OverlapGraph, max_size: usize) -> Vec<(NodeIndex, NodeIndex)> {
+    graph
+        .nodes()
+        .filter(|(_, n)| n.edges.len() >= 2)
+        .filter_map(|(idx, _)| superbubble_exit(graph, idx, max_size).map(|(exit, _)| (idx, exit)))
+        .collect()
+}
+
+/// Best scoring path through a superbubble that avoids the `excluded` nodes.
+/// Returns the interior nodes on the path and its score, or None if the exit can't be reached.
+/// The interior is acyclic, so the best metrics per node are propagated in topological order.
+fn best_superbubble_path(
+    graph: &OverlapGraph,
+    entrance: NodeIndex,
+    exit: NodeIndex,
+    interior: &[NodeIndex],
+    excluded: &HashSet<NodeIndex>,
+    weights: &BubbleScoringWeights,
+) -> Option<(HashSet<NodeIndex>, f64)> {
+    // node -> (metrics of the best path reaching it, previous node on that path)
+    let mut best: HashMap<NodeIndex, (PathMetrics, Option<NodeIndex>)> = HashMap::new();
+    best.insert(
+        entrance,
+        (
+            PathMetrics {
+                read_count: 1,
+                ..Default::default()
+            },
+            None,
+        ),
+    );
+    for v in std::iter::once(entrance).chain(interior.iter().copied()) {
+        let (Some((metrics, _)), Some(node)) = (best.get(&v).cloned(), graph.node(v)) else {
+            continue;
         };
+        for edge in node.edges.iter().filter(|e| !excluded.contains(&e.target)) {
+            let candidate = metrics.extend(edge.overlap_len, edge.identity);
+            let better = best
+                .get(&edge.target)
+                .is_none_or(|(m, _)| weights.score(&candidate) > weights.score(m));
+            if better {
+                best.insert(edge.target, (candidate, Some(v)));
+            }
+        }
+    }
 
-        // skip if less than 2 outgoing edges
-        if outgoing.len() < 2 {
+    // walk back from the exit
+    let (exit_metrics, mut cur) = best.get(&exit).cloned()?;
+    let mut keep: HashSet<NodeIndex> = HashSet::new();
+    while let Some(v) = cur {
+        if v == entrance || !keep.insert(v) {
+            break;
+        }
+        cur = best.get(&v).and_then(|(_, prev)| *prev);
+    }
+    Some((keep, weights.score(&exit_metrics)))
+}
+
+/// Resolve superbubbles by keeping the best scoring path from entrance to exit, returns how many were resolved.
+/// As for simple bubbles, the best path needs `min_support_ratio` times the score of the runner-up, the best
+/// path that shares no interior node with it. Superbubbles whose removal would also hit their own kept path
+/// (through reverse complements) are skipped.
+fn resolve_superbubbles(
+    graph: &mut OverlapGraph,
+    max_size: usize,
+    min_support_ratio: f64,
+    weights: &BubbleScoringWeights,
+) -> usize {
+    let mut resolved = 0usize;
+    for (entrance, exit) in superbubbles(graph, max_size) {
+        // an earlier resolution may have removed or reshaped this superbubble
+        let Some((current_exit, interior)) = superbubble_exit(graph, entrance, max_size) else {
+            continue;
+        };
+        if current_exit != exit {
             continue;
         }
 
-        // consider every unordered pair of outgoing neighbors
-        for i in 0..outgoing.len() {
-            for j in (i + 1)..outgoing.len() {
-                let start_a = &outgoing[i].0;
-                let start_b = &outgoing[j].0;
+        let no_exclusions = HashSet::new();
+        let Some((keep, best_score)) =
+            best_superbubble_path(graph, entrance, exit, &interior, &no_exclusions, weights)
+        else {
+            continue;
+        };
+        if let Some((_, runner_up_score)) =
+            best_superbubble_path(graph, entrance, exit, &interior, &keep, weights)
+        {
+            if runner_up_score * min_support_ratio > best_score {
+                continue;
+            }
+        }
+        let to_remove: HashSet<NodeIndex> =
+            interior.into_iter().filter(|n| !keep.contains(n)).collect();
+        if to_remove.is_empty() {
+            continue;
+        }
+        let hits_kept = to_remove
+            .iter()
+            .map(|&n| rc_index(n))
+            .any(|rc| keep.contains(&rc) || rc == entrance || rc == exit);
+        if hits_kept {
+            continue;
+        }
 
-                // skip identical starts, shouldn't happen though
-                if start_a == start_b {
-                    continue;
-                }
+        utils::delete_nodes_and_edges(graph, &to_remove);
+        resolved += 1;
+    }
+    resolved
+}
 
-                let (parent_a, depth_a, score_a) = bfs_limited(graph, start_a, max_bubble_len);
-                let (parent_b, depth_b, score_b) = bfs_limited(graph, start_b, max_bubble_len);
+/// A poppable simple bubble
+pub struct BubbleCandidate {
+    // node where the two paths diverge
+    pub source: NodeIndex,
+    // path that is kept, from the neighbor of the source up to where the paths meet
+    pub kept: Vec<NodeIndex>,
+    // nodes of the other path that are removed (with their reverse complements)
+    pub removed: HashSet<NodeIndex>,
+}
 
-                // find meeting nodes
-                let reached_a: HashSet<&String> = depth_a.keys().collect();
-                let reached_b: HashSet<&String> = depth_b.keys().collect();
+/// Meeting node of two bounded searches: the node reached by both with the smallest combined depth,
+/// ties are broken by node index. Nodes with a depth on only one side, or whose combined depth overflows,
+/// are skipped. None if no node was reached by both searches.
+fn best_meeting_node(
+    depth_a: &HashMap<NodeIndex, usize>,
+    depth_b: &HashMap<NodeIndex, usize>,
+) -> Option<NodeIndex> {
+    depth_a
+        .iter()
+        .filter_map(|(&node, da)| Some((da.checked_add(*depth_b.get(&node)?)?, node)))
+        .min()
+        .map(|(_, node)| node)
+}
 
-                // check intersection of reached nodes
-                let mut meetings: Vec<(&String, usize)> = Vec::new(); // (node, combined_depth)
-                for node in reached_a.intersection(&reached_b) {
-                    let d = depth_a.get(*node).unwrap_or(&usize::MAX)
-                        + depth_b.get(*node).unwrap_or(&usize::MAX);
-                    meetings.push((node.clone(), d));
-                }
+/// Check if two outgoing branches of `n`, given as (target, overlap length, identity), form a poppable bubble.
+/// Returns the kept path and the nodes of the losing path that would be removed.
+fn evaluate_bubble(
+    graph: &OverlapGraph,
+    n: NodeIndex,
+    branch_a: &(NodeIndex, u32, f64),
+    branch_b: &(NodeIndex, u32, f64),
+    max_bubble_len: usize,
+    min_support_ratio: f64,
+    weights: &BubbleScoringWeights,
+) -> Option<BubbleCandidate> {
+    let start_a = branch_a.0;
+    let start_b = branch_b.0;
+
+    // skip identical starts (shouldn't happen though) and self-loops back into n
+    if start_a == start_b || start_a == n || start_b == n {
+        return None;
+    }
 
-                // skip if no common node was reached
-                if meetings.is_empty() {
-                    continue;
-                }
+    let entry_a = (branch_a.1, branch_a.2);
+    let entry_b = (branch_b.1, branch_b.2);
+    let (parent_a, depth_a, score_a) = bfs_limited(graph, start_a, max_bubble_len, entry_a, n);
+    let (parent_b, depth_b, score_b) = bfs_limited(graph, start_b, max_bubble_len, entry_b, n);
 
-                // pick best sink node: minimal combined depth
-                meetings.sort_unstable_by_key(|k| k.1);
-                let (meet_node, _meet_depth) = meetings[0];
+    // pick best sink node: minimal combined depth, skip if no common node was reached
+    let meet_node = best_meeting_node(&depth_a, &depth_b)?;
 
-                // reconstruct paths start_a -> meet_node and start_b -> meet_node
-                let path_a = reconstruct_path(&parent_a, start_a, meet_node);
-                let path_b = reconstruct_path(&parent_b, start_b, meet_node);
-                if path_a.is_empty() || path_b.is_empty() {
-                    continue;
-                }
+    // reconstruct paths start_a -> meet_node and start_b -> meet_node
+    let path_a = reconstruct_path(&parent_a, start_a, meet_node);
+    let path_b = reconstruct_path(&parent_b, start_b, meet_node);
+    if path_a.is_empty() || path_b.is_empty() {
+        return None;
+    }
 
-                // get metrics for both paths
-                let metrics_a = score_a.get(meet_node).cloned().unwrap_or_default();
-                let metrics_b = score_b.get(meet_node).cloned().unwrap_or_default();
-                let depth_a = *depth_a.get(meet_node).unwrap_or(&usize::MAX);
-                let depth_b = *depth_b.get(meet_node).unwrap_or(&usize::MAX);
-
-                // calculate composite scores
-                // weight factors can be adjusted based on importance
-                const OVERLAP_WEIGHT: f64 = 1.0;
-                const IDENTITY_WEIGHT: f64 = 2.0;
-                const READ_COUNT_WEIGHT: f64 = 1.5;
-
-                let score_a = (metrics_a.total_overlap_len as f64 * OVERLAP_WEIGHT)
-                    + (metrics_a.avg_identity * IDENTITY_WEIGHT * 100.0)
-                    + (metrics_a.read_count as f64 * READ_COUNT_WEIGHT);
-
-                let score_b = (metrics_b.total_overlap_len as f64 * OVERLAP_WEIGHT)
-                    + (metrics_b.avg_identity * IDENTITY_WEIGHT * 100.0)
-                    + (metrics_b.read_count as f64 * READ_COUNT_WEIGHT);
-
-                // if both paths have no score (unexpected), skip
-                if score_a == 0.0 && score_b == 0.0 {
-                    continue;
-                }
+    // get metrics for both paths
+    let metrics_a = score_a.get(&meet_node).cloned().unwrap_or_default();
+    let metrics_b = score_b.get(&meet_node).cloned().unwrap_or_default();
+    let depth_a = *depth_a.get(&meet_node).unwrap_or(&usize::MAX);
+    let depth_b = *depth_b.get(&meet_node).unwrap_or(&usize::MAX);
 
-                // compare paths: higher score wins
-                // if scores equal, shorter path (less depth) wins
-                let (loser_path, winner_score) =
-                    if score_a > score_b || (score_a == score_b && depth_a < depth_b) {
-                        (path_b.clone(), score_a)
-                    } else if score_b > score_a || (score_a == score_b && depth_b < depth_a) {
-                        (path_a.clone(), score_b)
-                    } else {
-                        // Exactly equal - skip this bubble
-                        continue;
-                    };
+    // calculate composite scores
+    let score_a = weights.score(&metrics_a);
+    let score_b = weights.score(&metrics_b);
 
-                // require that the winner has enough support (based on score difference and min_support_ratio)
-                let loser_score = if score_a > score_b { score_b } else { score_a };
-                if loser_score * min_support_ratio > winner_score {
-                    continue;
-                }
+    // if both paths have no score (unexpected), skip
+    if score_a == 0.0 && score_b == 0.0 {
+        return None;
+    }
 
-                // nodes to remove: all nodes on loser_path excluding the sink node
-                // also exclude the source node n, typically the path starts at the neighbor of n
-                let mut to_remove: HashSet<String> = HashSet::new();
-                for node in loser_path.into_iter() {
-                    if node == *meet_node {
-                        break;
-                    }
-                    // defensive: don't remove n
-                    if node == *n {
+    // compare paths: higher score wins
+    // if scores equal, shorter path (less depth) wins
+    let (winner_path, loser_path, winner_score) =
+        if score_a > score_b || (score_a == score_b && depth_a < depth_b) {
+            (path_a, path_b, score_a)
+        } else if score_b > score_a || (score_a == score_b && depth_b < depth_a) {
+            (path_b, path_a, score_b)
+        } else {
+            // Exactly equal - skip this bubble
+            return None;
+        };
+
+    // require that the winner has enough support (based on score difference and min_support_ratio)
+    let loser_score = if score_a > score_b { score_b } else { score_a };
+    if loser_score * min_support_ratio > winner_score {
+        return None;
+    }
+
+    // nodes to remove: all nodes on loser_path excluding the sink node
+    // also exclude the source node n, typically the path starts at the neighbor of n
+    let mut to_remove: HashSet<NodeIndex> = HashSet::new();
+    for node in loser_path.into_iter() {
+        if node == meet_node {
+            break;
+        }
+        // defensive: don't remove n
+        if node == n {
+            return None;
+        }
+        to_remove.insert(node);
+    }
+
+    if to_remove.is_empty() {
+        return None;
+    }
+
+    Some(BubbleCandidate {
+        source: n,
+        kept: winner_path,
+        removed: to_remove,
+    })
+}
+
+/// Simple bubbles of the graph as they are now, without changing it (dry run of remove_bubbles).
+/// Popping one bubble can change the others, so overlapping candidates (e.g. both strands of a bubble) are all listed.
+pub fn find_bubbles(
+    graph: &OverlapGraph,
+    max_bubble_len: usize,
+    min_support_ratio: f64,
+    weights: &BubbleScoringWeights,
+) -> Vec<BubbleCandidate> {
+    let mut candidates = Vec::new();
+    if max_bubble_len == 0 {
+        return candidates;
+    }
+    for (n, node) in graph.nodes() {
+        let outgoing: Vec<(NodeIndex, u32, f64)> = node
+            .edges
+            .iter()
+            .map(|e| (e.target, e.overlap_len, e.identity))
+            .collect();
+        for i in 0..outgoing.len() {
+            for j in (i + 1)..outgoing.len() {
+                candidates.extend(evaluate_bubble(
+                    graph,
+                    n,
+                    &outgoing[i],
+                    &outgoing[j],
+                    max_bubble_len,
+                    min_support_ratio,
+                    weights,
+                ));
+            }
+        }
+    }
+    candidates
+}
+
+/// Remove simple bubbles in the overlap graph, then resolve the remaining superbubbles.
+/// Paths are compared with a composite score, weighted by `weights`.
+pub fn remove_bubbles(
+    graph: &mut OverlapGraph,
+    max_bubble_len: usize,
+    min_support_ratio: f64,
+    weights: &BubbleScoringWeights,
+) {
+    if max_bubble_len == 0 {
+        return;
+    }
+
+    // sorted snapshot of nodes to iterate safely and reproducibly
+    let node_keys: Vec<NodeIndex> = graph.sorted_node_indices();
+
+    for &n in node_keys.iter() {
+        // keep popping bubbles at n until no pair of outgoing paths forms a poppable bubble
+        // every pop removes at least one node, so this terminates
+        // get outgoing neighbors from the live graph (clone so we don't borrow across mutation)
+        while let Some(node) = graph.node(n) {
+            let outgoing = node
+                .edges
+                .iter()
+                .map(|e| (e.target, e.overlap_len, e.identity))
+                .collect::<Vec<_>>();
+
+            // stop if less than 2 outgoing edges
+            if outgoing.len() < 2 {
+                break;
+            }
+
+            // consider every unordered pair of outgoing neighbors
+            let mut popped = false;
+            'pairs: for i in 0..outgoing.len() {
+                for j in (i + 1)..outgoing.len() {
+                    let Some(candidate) = evaluate_bubble(
+                        graph,
+                        n,
+                        &outgoing[i],
+                        &outgoing[j],
+                        max_bubble_len,
+                        min_support_ratio,
+                        weights,
+                    ) else {
                         continue;
-                    }
-                    to_remove.insert(node);
-                }
+                    };
 
-                if to_remove.is_empty() {
-                    continue;
-                }
+                    // perform RC-aware removal
+                    utils::delete_nodes_and_edges(graph, &candidate.removed);
 
-                // perform RC-aware removal
-                utils::delete_nodes_and_edges(graph, &to_remove);
+                    // the edges of n changed, recompute them before looking at the remaining pairs
+                    popped = true;
+                    break 'pairs;
+                }
+            }
 
+            if !popped {
                 break;
             }
         }
     }
+
+    // superbubbles, a simple bubble has up to max_bubble_len interior nodes per branch
+    let resolved = resolve_superbubbles(graph, 2 * max_bubble_len + 2, min_support_ratio, weights);
+    if resolved > 0 {
+        info!("Resolved {} superbubbles", resolved);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alignment_filtering::Overlap;
+    use crate::create_overlap_graph::build_overlap_graph_from;
+
+    /// Graph of forward edges (from, to, overlap length, identity) between 2000 bp reads,
+    /// the reverse complement edges are added by the graph builder
+    fn graph(edges: &[(&str, &str, u32, f64)]) -> OverlapGraph {
+        let overlaps: Vec<Overlap> = edges
+            .iter()
+            .map(|&(from, to, overlap_len, identity)| Overlap {
+                source_name: from.to_string(),
+                sink_name: to.to_string(),
+                rc_source_name: utils::rc_node(to).unwrap(),
+                rc_sink_name: utils::rc_node(from).unwrap(),
+                edge_len: 2000 - overlap_len,
+                rc_edge_len: 2000 - overlap_len,
+                overlap_len,
+                identity,
+            })
+            .collect();
+        build_overlap_graph_from(&overlaps, false)
+    }
+
+    /// Index of a node id, panics if the node is not in the graph
+    fn idx(g: &OverlapGraph, id: &str) -> NodeIndex {
+        g.index(id).unwrap()
+    }
+
+    /// Node ids of a sequence of node indices
+    fn ids<'a>(g: &OverlapGraph, nodes: impl IntoIterator<Item = &'a NodeIndex>) -> Vec<String> {
+        nodes.into_iter().map(|&n| g.node_id(n)).collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn nested_superbubble() {
+        // s -> x -> {p, q} -> j -> t is nested in s -> {x .. j, y} -> t
+        // the overlaps through q are longer than through p, the path through x is longer than through y
+        let mut g = graph(&[
+            ("s+", "x+", 1000, 99.0),
+            ("s+", "y+", 1000, 99.0),
+            ("x+", "p+", 1000, 99.0),
+            ("x+", "q+", 1500, 99.0),
+            ("p+", "j+", 1000, 99.0),
+            ("q+", "j+", 1500, 99.0),
+            ("j+", "t+", 1000, 99.0),
+            ("y+", "t+", 1000, 99.0),
+        ]);
+
+        // both bubbles are found, on both strands
+        assert_eq!(
+            find_superbubbles(&g, 20),
+            pairs(&[("j-", "x-"), ("s+", "t+"), ("t-", "s-"), ("x+", "j+")])
+        );
+        let (exit, interior) = superbubble_exit(&g, idx(&g, "s+"), 20).unwrap();
+        assert_eq!(g.node_id(exit), "t+");
+        let mut interior_sorted = ids(&g, &interior);
+        interior_sorted.sort();
+        assert_eq!(interior_sorted, ["j+", "p+", "q+", "x+", "y+"]);
+        // too small a region is not a superbubble
+        assert_eq!(superbubble_exit(&g, idx(&g, "s+"), 5), None);
+
+        // the inner bubble is resolved first (j- sorts first), then the outer one
+        let weights = BubbleScoringWeights::default();
+        assert_eq!(resolve_superbubbles(&mut g, 20, 1.0, &weights), 2);
+        assert_eq!(
+            g.sorted_node_ids(),
+            ["j+", "j-", "q+", "q-", "s+", "s-", "t+", "t-", "x+", "x-"]
+        );
+        for (from, to) in [("s+", "x+"), ("x+", "q+"), ("q+", "j+"), ("j+", "t+")] {
+            let edges = &g.node(idx(&g, from)).unwrap().edges;
+            assert_eq!(edges.len(), 1);
+            assert_eq!(g.node_id(edges[0].target), to);
+        }
+    }
+
+    #[test]
+    fn bubble_with_a_tip_is_not_a_superbubble() {
+        // s -> {a, b} -> t, but a also leads to the dead end z
+        let mut g = graph(&[
+            ("s+", "a+", 1000, 99.0),
+            ("s+", "b+", 1000, 99.0),
+            ("a+", "t+", 1000, 99.0),
+            ("b+", "t+", 1000, 99.0),
+            ("a+", "z+", 1000, 99.0),
+        ]);
+        assert_eq!(superbubble_exit(&g, idx(&g, "s+"), 20), None);
+        assert!(find_superbubbles(&g, 20).is_empty());
+
+        let weights = BubbleScoringWeights::default();
+        assert_eq!(resolve_superbubbles(&mut g, 20, 1.0, &weights), 0);
+        assert_eq!(g.node_count(), 10);
+    }
+
+    #[test]
+    fn cycle_back_to_the_entrance_is_not_a_superbubble() {
+        // s -> {a, b} -> t -> s
+        let mut g = graph(&[
+            ("s+", "a+", 1000, 99.0),
+            ("s+", "b+", 1000, 99.0),
+            ("a+", "t+", 1000, 99.0),
+            ("b+", "t+", 1000, 99.0),
+            ("t+", "s+", 1000, 99.0),
+        ]);
+        assert_eq!(superbubble_exit(&g, idx(&g, "s+"), 20), None);
+        assert!(find_superbubbles(&g, 20).is_empty());
+        let weights = BubbleScoringWeights::default();
+        assert_eq!(resolve_superbubbles(&mut g, 20, 1.0, &weights), 0);
+        assert_eq!(g.node_count(), 8);
+
+        // a branch that loops back into the entrance, s -> {a, b} -> t and b -> s
+        let g = graph(&[
+            ("s+", "a+", 1000, 99.0),
+            ("s+", "b+", 1000, 99.0),
+            ("a+", "t+", 1000, 99.0),
+            ("b+", "t+", 1000, 99.0),
+            ("b+", "s+", 1000, 99.0),
+        ]);
+        assert_eq!(superbubble_exit(&g, idx(&g, "s+"), 20), None);
+    }
+
+    #[test]
+    fn search_does_not_walk_back_through_the_divergence_node() {
+        // diamond u -> {v, w} -> m with the back-edge v -> u, the overlaps through w are longer
+        let g = graph(&[
+            ("u+", "v+", 1000, 99.0),
+            ("u+", "w+", 1200, 99.0),
+            ("v+", "m+", 1000, 99.0),
+            ("w+", "m+", 1200, 99.0),
+            ("v+", "u+", 1000, 99.0),
+        ]);
+
+        let [u, v, w, m] = ["u+", "v+", "w+", "m+"].map(|id| idx(&g, id));
+
+        // without the forbidden node the search from v reaches w through u
+        let (_, depth, _) = bfs_limited(&g, v, 5, (1000, 99.0), NodeIndex::MAX);
+        assert!(depth.contains_key(&w));
+
+        let (parent_v, depth_v, _) = bfs_limited(&g, v, 5, (1000, 99.0), u);
+        let (parent_w, depth_w, _) = bfs_limited(&g, w, 5, (1000, 99.0), u);
+        assert!(!depth_v.contains_key(&u) && !depth_v.contains_key(&w));
+        assert!(!depth_w.contains_key(&u) && !depth_w.contains_key(&v));
+
+        // the two reconstructed paths only share the meeting node
+        assert_eq!(reconstruct_path(&parent_v, v, m), [v, m]);
+        assert_eq!(reconstruct_path(&parent_w, w, m), [w, m]);
+
+        // the bubble at u keeps the branch through w and removes v, nothing of the kept path is removed
+        let weights = BubbleScoringWeights::default();
+        let bubbles: Vec<BubbleCandidate> = find_bubbles(&g, 5, 1.0, &weights)
+            .into_iter()
+            .filter(|b| b.source == u)
+            .collect();
+        assert_eq!(bubbles.len(), 1);
+        assert_eq!(bubbles[0].kept, [w, m]);
+        assert_eq!(bubbles[0].removed, HashSet::from([v]));
+    }
+
+    #[test]
+    fn overflowing_depth_sum_is_not_a_meeting_node() {
+        let depths = |entries: &[(NodeIndex, usize)]| -> HashMap<NodeIndex, usize> {
+            entries.iter().copied().collect()
+        };
+        // nodes 0 (far), 1 (wrap), 2 (m), 3 (only in a) and 4 (only in b)
+        // usize::MAX + usize::MAX wraps around to usize::MAX - 1, 2 + usize::MAX wraps to 1
+        let depth_a = depths(&[(0, usize::MAX), (1, 2), (2, 3), (3, 0)]);
+        let depth_b = depths(&[(0, usize::MAX), (1, usize::MAX), (2, 4), (4, 0)]);
+        assert_eq!(best_meeting_node(&depth_a, &depth_b), Some(2));
+
+        // equal depths are broken by node index, whatever the hash map order
+        let depth_a = depths(&[(6, 1), (5, 1)]);
+        let depth_b = depths(&[(5, 2), (6, 2)]);
+        assert_eq!(best_meeting_node(&depth_a, &depth_b), Some(5));
+
+        // nothing in common
+        assert_eq!(best_meeting_node(&depth_a, &depths(&[(7, 0)])), None);
+    }
+
+    #[test]
+    fn identity_scale_lets_identity_decide_between_equal_overlaps() {
+        // u -> {v, w} -> m with the same overlaps, the branch through w has 80 percent identity
+        let g = graph(&[
+            ("u+", "v+", 1000, 99.0),
+            ("v+", "m+", 1000, 99.0),
+            ("u+", "w+", 1000, 80.0),
+            ("w+", "m+", 1000, 80.0),
+        ]);
+        let weights = BubbleScoringWeights::default();
+        let metrics = |identity: f64| {
+            PathMetrics::default()
+                .extend(1000, identity)
+                .extend(1000, identity)
+        };
+        // 2000 + 99 * 2 * 100 + 2 * 1.5 and 2000 + 80 * 2 * 100 + 2 * 1.5
+        assert_eq!(weights.score(&metrics(99.0)), 21803.0);
+        assert_eq!(weights.score(&metrics(80.0)), 18003.0);
+
+        let at_u = |weights: &BubbleScoringWeights| -> Vec<BubbleCandidate> {
+            find_bubbles(&g, 5, 1.1, weights)
+                .into_iter()
+                .filter(|b| b.source == idx(&g, "u+"))
+                .collect()
+        };
+        let bubbles = at_u(&weights);
+        assert_eq!(bubbles.len(), 1);
+        assert_eq!(ids(&g, &bubbles[0].kept), ["v+", "m+"]);
+        assert_eq!(ids(&g, &bubbles[0].removed), ["w+"]);
+
+        // without the scale the identity term is 19 * 2 points apart on scores of about 2200, not enough support
+        let unscaled = BubbleScoringWeights {
+            identity: 2.0 / IDENTITY_SCALE,
+            ..BubbleScoringWeights::default()
+        };
+        assert!(at_u(&unscaled).is_empty());
+
+        // one percentage point weighs as much as 200 bases of overlap: 200 bp more overlap evens out a point
+        let a = PathMetrics::default().extend(1200, 98.0);
+        let b = PathMetrics::default().extend(1000, 99.0);
+        assert_eq!(weights.score(&a), weights.score(&b));
+    }
 }
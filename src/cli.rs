@@ -1,4 +1,4 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(
@@ -17,12 +17,83 @@ pub enum Commands {
     AlignmentFiltering(AlignmentFilteringArgs),
 
     /// Full genome assembly pipeline
-    Assemble(AssembleArgs),
+    Assemble(Box<AssembleArgs>),
+
+    /// Print statistics of the raw overlap graph, without any cleanup
+    GraphStats(GraphStatsArgs),
+
+    /// Dump the overlaps of an overlaps binary file as a tab-separated table
+    OverlapsToPaf(OverlapsToPafArgs),
+}
+
+/// Stage of the assemble pipeline to resume from, the earlier stages are loaded from their intermediate files
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResumeStage {
+    // build the graph from the overlaps (--overlaps or <prefix>.overlaps.bin)
+    Graph,
+    // clean up the graph in <prefix>.graph.bin
+    Cleanup,
+    // compress the cleaned graph in <prefix>.cleaned_graph.bin
+    Compress,
+}
+
+/// Read technology, sets the defaults of the filtering and bubble scoring parameters
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+    // noisy Oxford Nanopore reads (~90% identity)
+    Ont,
+    // PacBio HiFi reads (~99.9% identity)
+    Hifi,
+}
+
+/// Parameter values that depend on the preset, an explicitly given option overrides them
+struct PresetDefaults {
+    min_percent_identity: f32,
+    overhang_ratio: f32,
+    max_overhang: u32,
+    bubble_overlap_weight: f64,
+    bubble_identity_weight: f64,
+    bubble_read_count_weight: f64,
+}
+
+impl PresetDefaults {
+    fn for_preset(preset: Option<Preset>) -> Self {
+        match preset {
+            None => Self {
+                min_percent_identity: 5.0,
+                overhang_ratio: 0.8,
+                max_overhang: 1000,
+                bubble_overlap_weight: 1.0,
+                bubble_identity_weight: 2.0,
+                bubble_read_count_weight: 1.5,
+            },
+            // identities from the dv:f divergence tag of minimap2, ONT overlaps are mostly above 80%
+            // identity differences between branches are mostly noise, rely on the read support
+            Some(Preset::Ont) => Self {
+                min_percent_identity: 75.0,
+                overhang_ratio: 0.8,
+                max_overhang: 1000,
+                bubble_overlap_weight: 1.0,
+                bubble_identity_weight: 1.0,
+                bubble_read_count_weight: 2.0,
+            },
+            // overlaps between HiFi reads are close to 99.9% identical (dv:f below 0.002)
+            // alignments reach the read ends and identity differences are real variants
+            Some(Preset::Hifi) => Self {
+                min_percent_identity: 98.0,
+                overhang_ratio: 0.3,
+                max_overhang: 200,
+                bubble_overlap_weight: 1.0,
+                bubble_identity_weight: 4.0,
+                bubble_read_count_weight: 1.0,
+            },
+        }
+    }
 }
 
 #[derive(Args)]
 pub struct AlignmentFilteringArgs {
-    /// Input PAF file
+    /// Input PAF file (- reads from stdin)
     #[arg(short = 'f', long)]
     pub input_paf: String,
 
@@ -30,6 +101,10 @@ pub struct AlignmentFilteringArgs {
     #[arg(long, default_value = "overlaps.bin")]
     pub output_overlaps: String,
 
+    /// Write the number of overlapping reads per read to this TSV file (read_name, degree)
+    #[arg(long)]
+    pub overlaps_per_read: Option<String>,
+
     /// Minimum overlap length
     #[arg(short = 'l', long, default_value_t = 2000)]
     pub min_overlap_length: u32,
@@ -42,9 +117,57 @@ pub struct AlignmentFilteringArgs {
     #[arg(short = 'i', long, default_value_t = 5.0)]
     pub min_percent_identity: f32,
 
+    /// Minimum mapping quality of an alignment, alignments with MAPQ 255 (not available) are kept
+    #[arg(long, default_value_t = 0u8)]
+    pub min_mapq: u8,
+
     /// Overhang ratio
     #[arg(long, default_value_t = 0.8)]
     pub overhang_ratio: f32,
+
+    /// Maximum overhang in bases, the allowed overhang is the smaller of this and overhang ratio * overlap length (0 uses only the ratio)
+    #[arg(long, default_value_t = 1000)]
+    pub max_overhang: u32,
+
+    /// Number of bases a read may stick out of another read and still count as contained
+    #[arg(long, default_value_t = 0u32)]
+    pub containment_fuzz: u32,
+
+    /// Record self-alignments and annotate reads with internal repeats
+    #[arg(long)]
+    pub paf_self_overlaps_as_repeats: bool,
+
+    /// Maximum number of alignments kept per read pair (>1 keeps alternative overlaps as multi-edges)
+    #[arg(long, default_value_t = 1usize)]
+    pub max_alignments_per_pair: usize,
+
+    /// Record a container read for every contained read, written alongside the overlaps
+    #[arg(long)]
+    pub keep_contained_reads: bool,
+
+    /// Merge colinear alignments of a read pair (split alignments) whose gaps in both reads are at most this many bases (disabled if not set)
+    #[arg(long)]
+    pub merge_split_alignments: Option<u32>,
+
+    /// Drop reads with an internal coverage valley as chimeric, minimum coverage of the flanks around the valley (disabled if not set)
+    #[arg(long)]
+    pub chimera_min_flank_coverage: Option<u32>,
+
+    /// Flank size in bases for chimera detection
+    #[arg(long, default_value_t = 100u32)]
+    pub chimera_window: u32,
+
+    /// Count the read coverage in bins of this many bases instead of per base, bounds the memory of long reads at the cost of coarser coverage trimming and chimera detection
+    #[arg(long, default_value_t = 1u32, value_parser = clap::value_parser!(u32).range(1..))]
+    pub coverage_bin_size: u32,
+
+    /// Clip this many bases from both ends of every read before classifying its alignments, for adapters or low quality ends (at most half of a read)
+    #[arg(long, default_value_t = 0u32)]
+    pub trim_overhangs: u32,
+
+    /// Coverage below which a position is part of a valley for chimera detection
+    #[arg(long, default_value_t = 1u32)]
+    pub chimera_max_valley_coverage: u32,
 }
 
 impl From<&AlignmentFilteringArgs> for crate::configs::AlignmentFilteringConfig {
@@ -52,21 +175,37 @@ impl From<&AlignmentFilteringArgs> for crate::configs::AlignmentFilteringConfig
         Self {
             input_paf: args.input_paf.clone(),
             output_overlaps: args.output_overlaps.clone(),
+            overlaps_per_read: args.overlaps_per_read.clone(),
             min_overlap_length: args.min_overlap_length,
             min_overlap_count: args.min_overlap_count,
             min_percent_identity: args.min_percent_identity,
+            min_mapq: args.min_mapq,
             overhang_ratio: args.overhang_ratio,
+            max_overhang: args.max_overhang,
+            containment_fuzz: args.containment_fuzz,
+            paf_self_overlaps_as_repeats: args.paf_self_overlaps_as_repeats,
+            max_alignments_per_pair: args.max_alignments_per_pair,
+            keep_contained_reads: args.keep_contained_reads,
+            merge_split_alignments: args.merge_split_alignments,
+            chimera_min_flank_coverage: args.chimera_min_flank_coverage,
+            chimera_window: args.chimera_window,
+            coverage_bin_size: args.coverage_bin_size,
+            trim_overhangs: args.trim_overhangs,
+            chimera_max_valley_coverage: args.chimera_max_valley_coverage,
         }
     }
 }
 
 #[derive(Args)]
-pub struct AssembleArgs {
-    // Alignment filtering parameters (optional if --overlaps is provided)
-    /// Input PAF file (optional if --overlaps is provided)
+pub struct GraphStatsArgs {
+    /// Input PAF file, - reads from stdin (optional if --overlaps is provided)
     #[arg(short = 'f', long)]
     pub input_paf: Option<String>,
 
+    /// Pre-computed overlaps binary file (optional, if provided skips alignment filtering)
+    #[arg(long, conflicts_with = "input_paf")]
+    pub overlaps: Option<String>,
+
     /// Minimum overlap length
     #[arg(short = 'l', long, default_value_t = 2000)]
     pub min_overlap_length: u32,
@@ -79,14 +218,115 @@ pub struct AssembleArgs {
     #[arg(short = 'i', long, default_value_t = 5.0)]
     pub min_percent_identity: f32,
 
+    /// Minimum mapping quality of an alignment, alignments with MAPQ 255 (not available) are kept
+    #[arg(long, default_value_t = 0u8)]
+    pub min_mapq: u8,
+
     /// Overhang ratio
     #[arg(long, default_value_t = 0.8)]
     pub overhang_ratio: f32,
 
+    /// Maximum overhang in bases, the allowed overhang is the smaller of this and overhang ratio * overlap length (0 uses only the ratio)
+    #[arg(long, default_value_t = 1000)]
+    pub max_overhang: u32,
+
+    /// Number of bases a read may stick out of another read and still count as contained
+    #[arg(long, default_value_t = 0u32)]
+    pub containment_fuzz: u32,
+
+    /// Number of branching nodes and components to list
+    #[arg(long, default_value_t = 10usize)]
+    pub top: usize,
+}
+
+impl From<&GraphStatsArgs> for crate::configs::GraphStatsConfig {
+    fn from(args: &GraphStatsArgs) -> Self {
+        Self {
+            input_paf: args.input_paf.clone(),
+            overlaps: args.overlaps.clone(),
+            min_overlap_length: args.min_overlap_length,
+            min_overlap_count: args.min_overlap_count,
+            min_percent_identity: args.min_percent_identity,
+            min_mapq: args.min_mapq,
+            overhang_ratio: args.overhang_ratio,
+            max_overhang: args.max_overhang,
+            containment_fuzz: args.containment_fuzz,
+            top: args.top,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct OverlapsToPafArgs {
+    /// Overlaps binary file written by alignment-filtering or assemble
+    #[arg(long)]
+    pub overlaps: String,
+
+    /// Output table, - writes to stdout
+    #[arg(short = 'o', long, default_value = "-")]
+    pub output: String,
+}
+
+impl From<&OverlapsToPafArgs> for crate::configs::OverlapsToPafConfig {
+    fn from(args: &OverlapsToPafArgs) -> Self {
+        Self {
+            overlaps: args.overlaps.clone(),
+            output: args.output.clone(),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct AssembleArgs {
+    // Alignment filtering parameters (optional if --overlaps is provided)
+    /// Input PAF file, - reads from stdin (optional if --overlaps is provided)
+    #[arg(short = 'f', long)]
+    pub input_paf: Option<String>,
+
+    /// Read technology, sets the defaults of the identity, overhang and bubble weight options (explicit options take precedence)
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
+
+    /// Minimum overlap length
+    #[arg(short = 'l', long, default_value_t = 2000)]
+    pub min_overlap_length: u32,
+
+    /// Minimum overlap count
+    #[arg(short = 'c', long, default_value_t = 3)]
+    pub min_overlap_count: u32,
+
+    /// Minimum percent identity [default: 5, ont preset: 75, hifi preset: 98]
+    #[arg(short = 'i', long)]
+    pub min_percent_identity: Option<f32>,
+
+    /// Minimum mapping quality of an alignment, alignments with MAPQ 255 (not available) are kept
+    #[arg(long, default_value_t = 0u8)]
+    pub min_mapq: u8,
+
+    /// Overhang ratio [default: 0.8, hifi preset: 0.3]
+    #[arg(long)]
+    pub overhang_ratio: Option<f32>,
+
+    /// Maximum overhang in bases, the allowed overhang is the smaller of this and overhang ratio * overlap length (0 uses only the ratio) [default: 1000, hifi preset: 200]
+    #[arg(long)]
+    pub max_overhang: Option<u32>,
+
+    /// Number of bases a read may stick out of another read and still count as contained
+    #[arg(long, default_value_t = 0u32)]
+    pub containment_fuzz: u32,
+
     /// Pre-computed overlaps binary file (optional, if provided skips alignment filtering)
     #[arg(long)]
     pub overlaps: Option<String>,
 
+    /// Overlap graph in GFA1 format, segments named after the reads (optional, if provided skips alignment filtering and graph creation)
+    #[arg(long, conflicts_with_all = ["input_paf", "overlaps"])]
+    pub input_gfa: Option<String>,
+
+    /// Skip the stages before this one by loading the intermediate written to the output directory by an earlier run
+    #[arg(long, value_enum, conflicts_with = "input_gfa")]
+    pub resume_from: Option<ResumeStage>,
+
     /// Input reads in FASTQ format
     #[arg(short = 'r', long)]
     pub reads_fq: String,
@@ -107,6 +347,18 @@ pub struct AssembleArgs {
     #[arg(long, default_value_t = 1.1f64)]
     pub min_support_ratio: f64,
 
+    /// Weight of the total overlap length in the bubble path score [default: 1]
+    #[arg(long)]
+    pub bubble_overlap_weight: Option<f64>,
+
+    /// Weight of the average identity in the bubble path score [default: 2, ont preset: 1, hifi preset: 4]
+    #[arg(long)]
+    pub bubble_identity_weight: Option<f64>,
+
+    /// Weight of the read count in the bubble path score [default: 1.5, ont preset: 2, hifi preset: 1]
+    #[arg(long)]
+    pub bubble_read_count_weight: Option<f64>,
+
     /// Maximum tip length for tip trimming
     #[arg(long, default_value_t = 4u32)]
     pub max_tip_len: u32,
@@ -115,33 +367,265 @@ pub struct AssembleArgs {
     #[arg(long, default_value_t = 10u32)]
     pub fuzz: u32,
 
+    /// Don't let transitive edge reduction remove n1->n3 only because n2->n3 is the shortest outgoing edge of n2, keeps more edges on noisy data
+    #[arg(long)]
+    pub no_smallest_edge_rule: bool,
+
     /// Number of cleanup iterations to run
     #[arg(long, default_value_t = 2u32)]
     pub cleanup_iterations: u32,
 
+    /// Skip transitive edge reduction in the cleanup
+    #[arg(long)]
+    pub no_transitive_reduction: bool,
+
+    /// Skip bubble removal in the cleanup
+    #[arg(long)]
+    pub no_bubble_removal: bool,
+
+    /// Skip tip trimming in the cleanup (by node count and by length in bases, not the low identity tip trimming)
+    #[arg(long)]
+    pub no_tip_trimming: bool,
+
+    /// Skip short edge removal in the cleanup
+    #[arg(long)]
+    pub no_short_edge_removal: bool,
+
+    /// Report what transitive reduction and bubble removal would remove from the overlap graph, then stop without cleaning up
+    #[arg(long)]
+    pub dry_run: bool,
+
     /// Short edge removal ratio (heuristic simplification)
     #[arg(long, default_value_t = 0.8f64)]
     pub short_edge_ratio: f64,
+
+    /// Maximum chain length for bi-loop cutting (heuristic simplification, 0 disables)
+    #[arg(long, default_value_t = 4u32)]
+    pub max_biloop_ext: u32,
+
+    /// Maximum chain length for internal sequence cutting (heuristic simplification, 0 disables)
+    #[arg(long, default_value_t = 1u32)]
+    pub max_internal_ext: u32,
+
+    /// Remove weak outgoing edges of branching nodes, see --weak-edge-ratio and --weak-edge-identity-tolerance (heuristic simplification)
+    #[arg(long)]
+    pub remove_weak_edges: bool,
+
+    /// An edge is weak if its matching bases (overlap length * identity) are below this ratio of the best edge of the node
+    #[arg(long, default_value_t = 0.8f64)]
+    pub weak_edge_ratio: f64,
+
+    /// Edges within this many percentage points of the best identity of the node are never weak
+    #[arg(long, default_value_t = 2.0f64)]
+    pub weak_edge_identity_tolerance: f64,
+
+    /// Remove likely collapsed repeat reads: all their edges are cut and the isolated reads are dropped, breaking the graph at repeats (heuristic simplification)
+    #[arg(long)]
+    pub mask_repeats: bool,
+
+    /// Minimum indegree of a repeat node for --mask-repeats
+    #[arg(long, default_value_t = 3usize)]
+    pub repeat_min_indegree: usize,
+
+    /// Minimum outdegree of a repeat node for --mask-repeats
+    #[arg(long, default_value_t = 3usize)]
+    pub repeat_min_outdegree: usize,
+
+    /// Record self-alignments and annotate reads with internal repeats
+    #[arg(long)]
+    pub paf_self_overlaps_as_repeats: bool,
+
+    /// Maximum number of alignments kept per read pair (>1 keeps alternative overlaps as multi-edges)
+    #[arg(long, default_value_t = 1usize)]
+    pub max_alignments_per_pair: usize,
+
+    /// Record a container read for every contained read, written alongside the overlaps
+    #[arg(long)]
+    pub keep_contained_reads: bool,
+
+    /// Merge colinear alignments of a read pair (split alignments) whose gaps in both reads are at most this many bases (disabled if not set)
+    #[arg(long)]
+    pub merge_split_alignments: Option<u32>,
+
+    /// Drop reads with an internal coverage valley as chimeric, minimum coverage of the flanks around the valley (disabled if not set)
+    #[arg(long)]
+    pub chimera_min_flank_coverage: Option<u32>,
+
+    /// Flank size in bases for chimera detection
+    #[arg(long, default_value_t = 100u32)]
+    pub chimera_window: u32,
+
+    /// Count the read coverage in bins of this many bases instead of per base, bounds the memory of long reads at the cost of coarser coverage trimming and chimera detection
+    #[arg(long, default_value_t = 1u32, value_parser = clap::value_parser!(u32).range(1..))]
+    pub coverage_bin_size: u32,
+
+    /// Clip this many bases from both ends of every read before classifying its alignments, for adapters or low quality ends (at most half of a read)
+    #[arg(long, default_value_t = 0u32)]
+    pub trim_overhangs: u32,
+
+    /// Coverage below which a position is part of a valley for chimera detection
+    #[arg(long, default_value_t = 1u32)]
+    pub chimera_max_valley_coverage: u32,
+
+    /// Minimum percent identity of an overlap to become a graph edge (0 keeps all overlaps)
+    #[arg(long, default_value_t = 0.0f64)]
+    pub min_edge_identity: f64,
+
+    /// Drop graph edges whose overlap is longer than one of its reads in the FASTQ file, before the cleanup
+    #[arg(long)]
+    pub validate_overlaps: bool,
+
+    /// Remove tips whose mean edge identity (percent) is below this value, regardless of their length (0 disables)
+    #[arg(long, default_value_t = 0.0f64)]
+    pub min_tip_identity: f64,
+
+    /// Maximum number of nodes of a tip removed by the low identity tip trimming
+    #[arg(long, default_value_t = 20u32)]
+    pub max_low_identity_tip_len: u32,
+
+    /// Remove tips that spell fewer than this many bases, regardless of their number of nodes (0 disables)
+    #[arg(long, default_value_t = 0u32)]
+    pub max_tip_bp: u32,
+
+    /// Maximum number of nodes of a tip removed by the base pair tip trimming
+    #[arg(long, default_value_t = 50u32)]
+    pub max_tip_bp_nodes: u32,
+
+    /// Cut tangled components at articulation points (reads that alone join two parts of the graph) when at least two of the parts have this many nodes (0 disables)
+    #[arg(long, default_value_t = 0u32)]
+    pub split_articulation_min_nodes: u32,
+
+    /// Write a GFA snapshot of the graph to the output directory after every cleanup step (iter<i>_step<j>_<step>.gfa)
+    #[arg(long)]
+    pub keep_intermediate: bool,
+
+    /// Log the nodes and edges every cleanup iteration removed or added, and the change in components
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Write the overlap graph before any cleanup to this GFA file, one segment per oriented read
+    #[arg(long)]
+    pub dump_raw_graph: Option<String>,
+
+    /// Collapse reads with an identical sequence into one representative before building the graph (PAF input only)
+    #[arg(long)]
+    pub dedup_reads: bool,
+
+    /// Minimum length in bases of an output unitig (0 keeps all unitigs)
+    #[arg(long, default_value_t = 0u32)]
+    pub min_unitig_length: u32,
+
+    /// Trim the first and last read of a unitig if its mean coverage is below this value (0 disables)
+    #[arg(long, default_value_t = 0.0f64)]
+    pub min_end_coverage: f64,
+
+    /// Drop unitigs that contain both orientations of a read instead of splitting them
+    #[arg(long)]
+    pub drop_palindromic_unitigs: bool,
+
+    /// Emit junction reads as single-read unitigs instead of absorbing them into the unitigs leaving them
+    #[arg(long)]
+    pub standalone_junctions: bool,
+
+    /// Polish unitigs by majority vote over their backbone and contained reads, positions covered by fewer reads keep the backbone base (0 disables)
+    #[arg(long, default_value_t = 0u32)]
+    pub consensus_min_depth: u32,
+
+    /// Write the reads that are in no unitig and not contained in another read to <prefix>.singletons.fasta
+    #[arg(long)]
+    pub write_singletons: bool,
+
+    /// Number of threads used by the parallel steps (0 uses all cores)
+    #[arg(short = 't', long, default_value_t = 0usize)]
+    pub threads: usize,
+
+    /// Write the statistics of the run (filtering, cleanup iterations, final graph and unitigs) to this JSON file
+    #[arg(long, alias = "emit-stats-per-iteration-json")]
+    pub stats_json: Option<String>,
+
+    /// Expected genome size in bases, adds NG50 and LG50 to the assembly statistics
+    #[arg(long)]
+    pub genome_size: Option<usize>,
 }
 
 impl From<&AssembleArgs> for crate::configs::AssembleConfig {
     fn from(args: &AssembleArgs) -> Self {
+        let defaults = PresetDefaults::for_preset(args.preset);
         Self {
             input_paf: args.input_paf.clone(),
             min_overlap_length: args.min_overlap_length,
             min_overlap_count: args.min_overlap_count,
-            min_percent_identity: args.min_percent_identity,
-            overhang_ratio: args.overhang_ratio,
+            min_percent_identity: args
+                .min_percent_identity
+                .unwrap_or(defaults.min_percent_identity),
+            min_mapq: args.min_mapq,
+            overhang_ratio: args.overhang_ratio.unwrap_or(defaults.overhang_ratio),
+            max_overhang: args.max_overhang.unwrap_or(defaults.max_overhang),
+            containment_fuzz: args.containment_fuzz,
             overlaps: args.overlaps.clone(),
+            input_gfa: args.input_gfa.clone(),
+            resume_from: args.resume_from,
             reads_fq: args.reads_fq.clone(),
             output_prefix: args.output_prefix.clone(),
             output_dir: args.output_dir.clone(),
             max_bubble_length: args.max_bubble_length,
             min_support_ratio: args.min_support_ratio,
+            bubble_overlap_weight: args
+                .bubble_overlap_weight
+                .unwrap_or(defaults.bubble_overlap_weight),
+            bubble_identity_weight: args
+                .bubble_identity_weight
+                .unwrap_or(defaults.bubble_identity_weight),
+            bubble_read_count_weight: args
+                .bubble_read_count_weight
+                .unwrap_or(defaults.bubble_read_count_weight),
             max_tip_len: args.max_tip_len,
             fuzz: args.fuzz,
+            no_smallest_edge_rule: args.no_smallest_edge_rule,
             cleanup_iterations: args.cleanup_iterations,
+            no_transitive_reduction: args.no_transitive_reduction,
+            no_bubble_removal: args.no_bubble_removal,
+            no_tip_trimming: args.no_tip_trimming,
+            no_short_edge_removal: args.no_short_edge_removal,
+            dry_run: args.dry_run,
             short_edge_ratio: args.short_edge_ratio,
+            max_biloop_ext: args.max_biloop_ext,
+            max_internal_ext: args.max_internal_ext,
+            remove_weak_edges: args.remove_weak_edges,
+            weak_edge_ratio: args.weak_edge_ratio,
+            weak_edge_identity_tolerance: args.weak_edge_identity_tolerance,
+            mask_repeats: args.mask_repeats,
+            repeat_min_indegree: args.repeat_min_indegree,
+            repeat_min_outdegree: args.repeat_min_outdegree,
+            paf_self_overlaps_as_repeats: args.paf_self_overlaps_as_repeats,
+            max_alignments_per_pair: args.max_alignments_per_pair,
+            keep_contained_reads: args.keep_contained_reads,
+            merge_split_alignments: args.merge_split_alignments,
+            chimera_min_flank_coverage: args.chimera_min_flank_coverage,
+            chimera_window: args.chimera_window,
+            coverage_bin_size: args.coverage_bin_size,
+            trim_overhangs: args.trim_overhangs,
+            chimera_max_valley_coverage: args.chimera_max_valley_coverage,
+            min_edge_identity: args.min_edge_identity,
+            validate_overlaps: args.validate_overlaps,
+            min_unitig_length: args.min_unitig_length,
+            min_end_coverage: args.min_end_coverage,
+            drop_palindromic_unitigs: args.drop_palindromic_unitigs,
+            standalone_junctions: args.standalone_junctions,
+            consensus_min_depth: args.consensus_min_depth,
+            write_singletons: args.write_singletons,
+            threads: args.threads,
+            dedup_reads: args.dedup_reads,
+            min_tip_identity: args.min_tip_identity,
+            max_low_identity_tip_len: args.max_low_identity_tip_len,
+            max_tip_bp: args.max_tip_bp,
+            max_tip_bp_nodes: args.max_tip_bp_nodes,
+            split_articulation_min_nodes: args.split_articulation_min_nodes,
+            keep_intermediate: args.keep_intermediate,
+            diff: args.diff,
+            dump_raw_graph: args.dump_raw_graph.clone(),
+            stats_json: args.stats_json.clone(),
+            genome_size: args.genome_size,
         }
     }
 }
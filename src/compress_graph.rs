@@ -1,11 +1,16 @@
+use crate::create_overlap_graph::{NodeIndex, OverlapGraph};
 use crate::utils;
+use log::warn;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 /// graph compression module
 /// creates a compressed graph of unitigs from an overlap graph
 /// 1. get the indegree and outdegree of each node
-/// 2. get non-circular unitigs (start at nodes with indegree != 1 or outdegree != 1)
+/// 2. get non-circular unitigs (start at nodes with indegree != 1 or outdegree != 1),
+///    junction nodes are either absorbed as the first node of each outgoing unitig or emitted as standalone unitigs
 /// 3. get circular unitigs (remaining unvisited nodes)
 use std::collections::{HashMap, HashSet};
-use std::io::BufRead;
+use std::io::Write;
 
 pub struct UnitigMember {
     pub node_id: String,
@@ -17,6 +22,10 @@ pub struct Unitig {
     pub id: usize,
     pub members: Vec<UnitigMember>,
     pub fasta_seq: Option<String>,
+    // the unitig is a cycle: its last member links back to its first member
+    pub is_circular: bool,
+    // base-weighted mean read coverage, None if the reads carry no coverage
+    pub coverage: Option<f64>,
 }
 
 pub struct CompressedGraph {
@@ -32,160 +41,229 @@ pub struct UnitigEdge {
     pub identity: f64,
 }
 
-/// Main function: compress maximal non-branching paths into unitigs.
-/// Preserves member lists and the overlap lengths between them.
-pub fn compress_unitigs(
-    graph: &crate::create_overlap_graph::OverlapGraph,
-    fastq_path: &str,
-    fasta_path: &str,
-) -> CompressedGraph {
-    // 1) create a map of indegrees
-    let mut indegree: HashMap<String, usize> = HashMap::new();
-    for id in graph.nodes.keys() {
-        indegree.insert(id.clone(), 0);
+/// Unitig member for node `idx`, with the target and edge length of the edge to the next member (None for the last member)
+fn member(graph: &OverlapGraph, idx: NodeIndex, edge: Option<(NodeIndex, u32)>) -> UnitigMember {
+    UnitigMember {
+        node_id: graph.node_id(idx),
+        edge: edge.map_or((String::new(), 0), |(target, edge_len)| {
+            (graph.node_id(target), edge_len)
+        }),
     }
-    for (_source_id, node) in &graph.nodes {
-        for e in &node.edges {
-            *indegree.entry(e.target_id.clone()).or_default() += 1;
+}
+
+/// Position of the first member whose reverse complement is an earlier member of the same unitig, if any.
+/// Such a unitig runs through both orientations of a read (a palindrome) and spells a nonsense sequence.
+fn self_rc_position(members: &[UnitigMember]) -> Option<usize> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    for (i, member) in members.iter().enumerate() {
+        if utils::rc_node(&member.node_id).is_some_and(|rc| seen.contains(rc.as_str())) {
+            return Some(i);
+        }
+        seen.insert(member.node_id.as_str());
+    }
+    None
+}
+
+/// A node is a junction if it has more than one incoming or more than one outgoing edge.
+fn is_junction(graph: &OverlapGraph, idx: NodeIndex) -> bool {
+    graph.indegree(idx) > 1 || graph.node(idx).is_some_and(|n| n.edges.len() > 1)
+}
+
+/// Non-circular unitigs with every junction node as a single-member unitig.
+/// Chains start at nodes without a predecessor or right after a junction, and stop before the next junction.
+fn standalone_junction_unitigs(
+    graph: &OverlapGraph,
+    node_ids: &[NodeIndex],
+    visited: &mut HashSet<NodeIndex>,
+    unitigs: &mut Vec<Unitig>,
+) {
+    let mut push = |members: Vec<UnitigMember>| {
+        let uid = unitigs.len();
+        unitigs.push(Unitig {
+            id: uid,
+            members,
+            fasta_seq: None,
+            is_circular: false,
+            coverage: None,
+        });
+    };
+
+    for &id in node_ids {
+        if visited.contains(&id) {
+            continue;
+        }
+        if is_junction(graph, id) {
+            visited.insert(id);
+            push(vec![member(graph, id, None)]);
+            continue;
+        }
+        // a non-junction node has at most one predecessor
+        let starts_chain = match graph.node(id).map_or(&[][..], |n| n.in_edges()) {
+            [] => true,
+            [pred] => is_junction(graph, *pred),
+            _ => false,
+        };
+        if !starts_chain {
+            continue;
+        }
+
+        let mut members: Vec<UnitigMember> = Vec::new();
+        let mut cur = id;
+        visited.insert(cur);
+        loop {
+            let next = match graph.node(cur).map_or(&[][..], |n| n.edges.as_slice()) {
+                [e] if !is_junction(graph, e.target) && !visited.contains(&e.target) => {
+                    Some((e.target, e.edge_len))
+                }
+                _ => None,
+            };
+            let Some((next, edge_len)) = next else {
+                break;
+            };
+            members.push(member(graph, cur, Some((next, edge_len))));
+            visited.insert(next);
+            cur = next;
         }
+        members.push(member(graph, cur, None));
+        push(members);
     }
+}
+
+/// Main function: compress maximal non-branching paths into unitigs.
+/// Preserves member lists and the overlap lengths between them.
+/// With `standalone_junctions` every junction node becomes its own unitig, so the unitig graph keeps the branching
+/// structure of the read graph; otherwise a junction is the first node of each unitig leaving it.
+pub fn compress_unitigs(graph: &OverlapGraph, standalone_junctions: bool) -> CompressedGraph {
+    // 1) indegrees are tracked by the graph itself
 
-    let mut visited: HashSet<String> = HashSet::new();
+    let mut visited: HashSet<NodeIndex> = HashSet::new();
     let mut unitigs: Vec<Unitig> = Vec::new();
 
     // Helper to extract the single outgoing neighbor if outdeg == 1
-    let out_single =
-        |g: &crate::create_overlap_graph::OverlapGraph, cur: &str| -> Option<(String, u32)> {
-            g.nodes.get(cur).and_then(|n| {
-                if n.edges.len() == 1 {
-                    let e = &n.edges[0];
-                    Some((e.target_id.clone(), e.edge_len))
-                } else {
-                    None
-                }
-            })
-        };
+    let out_single = |g: &OverlapGraph, cur: NodeIndex| -> Option<(NodeIndex, u32)> {
+        g.node(cur).and_then(|n| {
+            if n.edges.len() == 1 {
+                let e = &n.edges[0];
+                Some((e.target, e.edge_len))
+            } else {
+                None
+            }
+        })
+    };
 
-    // 2) non-circular unitigs, start unitigs at nodes where indegree != 1 || outdeg != 1
-    for (id, node) in &graph.nodes {
-        let indegree_i = *indegree.get(id).unwrap_or(&0);
-        let outdeg_i = node.edges.len();
+    // nodes are visited in sorted order, so unitig ids are reproducible
+    let node_ids = graph.sorted_node_indices();
 
-        // skip if already visited
-        if visited.contains(id) {
-            continue;
-        }
+    // 2) non-circular unitigs, start unitigs at nodes where indegree != 1 || outdeg != 1
+    if standalone_junctions {
+        standalone_junction_unitigs(graph, &node_ids, &mut visited, &mut unitigs);
+    } else {
+        for &id in &node_ids {
+            let Some(node) = graph.node(id) else {
+                continue;
+            };
+            let indegree_i = graph.indegree(id);
+            let outdeg_i = node.edges.len();
 
-        // start a unitig if indegree != 1 or outdegree != 1 (i.e., not a simple internal node)
-        if indegree_i != 1 || outdeg_i != 1 {
-            // create a unitig for each outgoing edge
-            // if outdeg == 0, there will be no unitig, each outgoing edge will be zero-indexed
-            for out_edge_i in 0..outdeg_i {
-                // start a new unitig from id
-                let mut members: Vec<UnitigMember> = Vec::new();
-                let mut cur = id.clone();
-                //members.push(UnitigMember { node_id: cur.clone(), overlap_from_prev: 0 });
-                visited.insert(cur.clone());
-
-                // check the next outgoing edge
-                let (second, edge_len) = {
-                    let e = &node.edges[out_edge_i];
-                    (e.target_id.clone(), e.edge_len)
-                };
-                // push the first node into the unitig members
-                members.push(UnitigMember {
-                    node_id: cur.clone(),
-                    edge: (second.clone(), edge_len),
-                });
+            // skip if already visited
+            if visited.contains(&id) {
+                continue;
+            }
 
-                let second_indegree = *indegree.get(&second).unwrap_or(&0);
-                // check if the node breaks the chain
-                if second_indegree != 1 {
-                    continue;
-                }
-                // stop if second is already visited
-                if visited.contains(&second) {
-                    continue;
-                }
+            // start a unitig if indegree != 1 or outdegree != 1 (i.e., not a simple internal node)
+            if indegree_i != 1 || outdeg_i != 1 {
+                // create a unitig for each outgoing edge
+                // if outdeg == 0, there will be no unitig, each outgoing edge will be zero-indexed
+                for out_edge_i in 0..outdeg_i {
+                    // start a new unitig from id
+                    let mut members: Vec<UnitigMember> = Vec::new();
+                    let mut cur = id;
+                    //members.push(UnitigMember { node_id: cur.clone(), overlap_from_prev: 0 });
+                    visited.insert(cur);
 
-                visited.insert(second.clone());
-                cur = second.clone();
+                    // check the next outgoing edge
+                    let (second, edge_len) = {
+                        let e = &node.edges[out_edge_i];
+                        (e.target, e.edge_len)
+                    };
+                    // push the first node into the unitig members
+                    members.push(member(graph, cur, Some((second, edge_len))));
 
-                // extend forward from second untill the end
-                while let Some((next, edge_len)) = out_single(graph, &cur) {
-                    let next_indegree = *indegree.get(&next).unwrap_or(&0);
-                    // don't add the node that breaks the chain
-                    if next_indegree != 1 {
-                        break;
+                    let second_indegree = graph.indegree(second);
+                    // check if the node breaks the chain
+                    if second_indegree != 1 {
+                        continue;
                     }
-                    // stop if next is already visited
-                    if visited.contains(&next) {
-                        break;
+                    // stop if second is already visited
+                    if visited.contains(&second) {
+                        continue;
                     }
-                    // push cur to the unitig members
-                    members.push(UnitigMember {
-                        node_id: cur.clone(),
-                        edge: (next.clone(), edge_len),
-                    });
-                    visited.insert(next.clone());
-                    cur = next;
-                }
 
-                // add the final node
-                members.push(UnitigMember {
-                    node_id: cur.clone(),
-                    edge: (String::new(), 0),
-                });
+                    visited.insert(second);
+                    cur = second;
 
-                // create the unitig
-                let uid = unitigs.len();
-                unitigs.push(Unitig {
-                    id: uid,
-                    members,
-                    fasta_seq: None,
-                });
+                    // extend forward from second untill the end
+                    while let Some((next, edge_len)) = out_single(graph, cur) {
+                        let next_indegree = graph.indegree(next);
+                        // don't add the node that breaks the chain
+                        if next_indegree != 1 {
+                            break;
+                        }
+                        // stop if next is already visited
+                        if visited.contains(&next) {
+                            break;
+                        }
+                        // push cur to the unitig members
+                        members.push(member(graph, cur, Some((next, edge_len))));
+                        visited.insert(next);
+                        cur = next;
+                    }
+
+                    // add the final node
+                    members.push(member(graph, cur, None));
+
+                    // create the unitig
+                    let uid = unitigs.len();
+                    unitigs.push(Unitig {
+                        id: uid,
+                        members,
+                        fasta_seq: None,
+                        is_circular: false,
+                        coverage: None,
+                    });
+                }
             }
         }
     }
 
     // 3) circular unitigs, handle remaining nodes that are still unvisited
-    for id in graph.nodes.keys() {
-        if visited.contains(id) {
+    for &id in &node_ids {
+        if visited.contains(&id) {
             continue;
         }
 
-        // start a circular unitig
-        let mut cur = id.clone();
+        // walk the single outgoing edges until the walk returns to its start (a cycle) or the chain ends.
+        // every node is pushed once, a cycle's last member keeps the closing edge back to the start
+        let mut cur = id;
         let mut members: Vec<UnitigMember> = Vec::new();
-
+        let mut is_circular = false;
         loop {
-            visited.insert(cur.clone());
-            // to follow, get the unique outgoing edge
-            let next_edge = out_single(graph, &cur);
-            if next_edge.is_none() {
-                // shouldn't happen in pure cycle, break defensively
-                break;
-            }
-            let (next, edge_len) = next_edge.unwrap();
-
-            // push cur to the unitig members
-            members.push(UnitigMember {
-                node_id: cur.clone(),
-                edge: (next.clone(), edge_len),
-            });
-
-            let next_indegree = *indegree.get(&next).unwrap_or(&0);
-
-            // stop if the next node breaks the cycle structure (indegree != 1)
-            if next_indegree != 1 {
-                break;
-            }
-            // stop if next is already visited
-            if visited.contains(&next) {
-                break;
-            }
-
-            cur = next;
+            visited.insert(cur);
+            let edge = match out_single(graph, cur) {
+                Some((next, edge_len)) if next == id && graph.indegree(id) == 1 => {
+                    is_circular = true;
+                    Some((next, edge_len))
+                }
+                Some((next, edge_len)) if graph.indegree(next) == 1 && !visited.contains(&next) => {
+                    members.push(member(graph, cur, Some((next, edge_len))));
+                    cur = next;
+                    continue;
+                }
+                // the chain ends, the last member is spelled in full
+                _ => None,
+            };
+            members.push(member(graph, cur, edge));
+            break;
         }
 
         // register unitig
@@ -194,158 +272,712 @@ pub fn compress_unitigs(
             id: uid,
             members,
             fasta_seq: None,
+            is_circular,
+            coverage: None,
         });
     }
 
-    // Build edges between unitigs based on original overlap graph
-    let mut node_to_unitig: HashMap<String, usize> = HashMap::new();
+    // Build edges between unitigs based on original overlap graph.
+    // A junction can be the first node of several unitigs, so edges only run from the last member of a unitig
+    // to the first member of another one.
+    let mut unitigs_by_first: HashMap<NodeIndex, Vec<usize>> = HashMap::new();
     for u in &unitigs {
-        for m in &u.members {
-            node_to_unitig.insert(m.node_id.clone(), u.id);
+        if let Some(first) = u.members.first().and_then(|m| graph.index(&m.node_id)) {
+            unitigs_by_first.entry(first).or_default().push(u.id);
         }
     }
 
     let mut unitig_edge_map: HashMap<(usize, usize), UnitigEdge> = HashMap::new();
-    for (source_id, node) in &graph.nodes {
-        if let Some(&from_uid) = node_to_unitig.get(source_id) {
-            for e in &node.edges {
-                if let Some(&to_uid) = node_to_unitig.get(&e.target_id) {
-                    if from_uid == to_uid {
-                        continue;
-                    }
-                    let key = (from_uid, to_uid);
-                    let entry = unitig_edge_map.entry(key).or_insert(UnitigEdge {
-                        from: from_uid,
-                        to: to_uid,
-                        edge_len: e.edge_len,
-                        overlap_len: e.overlap_len,
-                        identity: e.identity,
-                    });
-                    // choose smallest edge_len and best identity
-                    if e.edge_len < entry.edge_len {
-                        entry.edge_len = e.edge_len;
-                    }
-                    if e.identity > entry.identity {
-                        entry.identity = e.identity;
-                    }
-                    if e.overlap_len > entry.overlap_len {
-                        entry.overlap_len = e.overlap_len;
+    for u in &unitigs {
+        let Some(node) = u
+            .members
+            .last()
+            .and_then(|m| graph.node(graph.index(&m.node_id)?))
+        else {
+            continue;
+        };
+        for e in &node.edges {
+            for &to_uid in unitigs_by_first.get(&e.target).into_iter().flatten() {
+                if u.id == to_uid {
+                    continue;
+                }
+                let entry = unitig_edge_map.entry((u.id, to_uid)).or_insert(UnitigEdge {
+                    from: u.id,
+                    to: to_uid,
+                    edge_len: e.edge_len,
+                    overlap_len: e.overlap_len,
+                    identity: e.identity,
+                });
+                // choose smallest edge_len and best identity
+                if e.edge_len < entry.edge_len {
+                    entry.edge_len = e.edge_len;
+                }
+                if e.identity > entry.identity {
+                    entry.identity = e.identity;
+                }
+                if e.overlap_len > entry.overlap_len {
+                    entry.overlap_len = e.overlap_len;
+                }
+            }
+        }
+    }
+
+    let mut edges: Vec<UnitigEdge> = unitig_edge_map.into_values().collect();
+    edges.sort_unstable_by_key(|e| (e.from, e.to));
+
+    CompressedGraph { unitigs, edges }
+}
+
+impl CompressedGraph {
+    /// Generate the sequence of every unitig from the read sequences.
+    /// Unitigs that already have a sequence are kept, unless `force_recompute` is set.
+    /// Unitigs whose sequence can't be built are skipped with a warning, returns the ids of the failed unitigs.
+    /// Every unitig only writes its own sequence, so with the `parallel` feature the unitigs are built in parallel.
+    pub fn build_sequences(
+        &mut self,
+        graph: &OverlapGraph,
+        fastq_seqs: &HashMap<String, String>,
+        force_recompute: bool,
+    ) -> Vec<usize> {
+        let build = |unitig: &mut Unitig| -> Option<usize> {
+            if unitig.fasta_seq.is_some() && !force_recompute {
+                return None;
+            }
+            match unitig_sequence(unitig, graph, fastq_seqs) {
+                Ok(seq) => {
+                    unitig.fasta_seq = Some(seq);
+                    None
+                }
+                Err(e) => {
+                    warn!("skipping unitig_{}: {}", unitig.id, e);
+                    unitig.fasta_seq = None;
+                    Some(unitig.id)
+                }
+            }
+        };
+
+        // results are collected in unitig order, also in parallel
+        #[cfg(feature = "parallel")]
+        let failed = self.unitigs.par_iter_mut().filter_map(build).collect();
+        #[cfg(not(feature = "parallel"))]
+        let failed = self.unitigs.iter_mut().filter_map(build).collect();
+        failed
+    }
+
+    /// Estimate the coverage of every unitig from the mean coverage of its member reads.
+    /// Members are weighted by the bases they contribute to the unitig sequence: their edge length, or the
+    /// full read length for the last member. A long read that is mostly overlapped by its successor so adds
+    /// little, and the estimate isn't pulled towards the coverage of short reads at a repeat boundary.
+    pub fn compute_coverage(
+        &mut self,
+        graph: &crate::create_overlap_graph::OverlapGraph,
+        fastq_seqs: &HashMap<String, String>,
+    ) {
+        for unitig in self.unitigs.iter_mut() {
+            let mut weighted = 0.0;
+            let mut bases = 0usize;
+            for member in &unitig.members {
+                let Some(node) = graph.index(&member.node_id).and_then(|idx| graph.node(idx))
+                else {
+                    continue;
+                };
+                let contributed = if member.edge.0.is_empty() {
+                    utils::split_node_id(&member.node_id)
+                        .and_then(|(read_id, _)| fastq_seqs.get(read_id))
+                        .map_or(0, |s| s.len())
+                } else {
+                    member.edge.1 as usize
+                };
+                weighted += node.coverage * contributed as f64;
+                bases += contributed;
+            }
+            unitig.coverage = (bases > 0 && weighted > 0.0).then(|| weighted / bases as f64);
+        }
+    }
+
+    /// Write a TSV with the coverage estimate of every unitig: unitig_id, length, reads, coverage.
+    /// Unitigs without a coverage estimate are written with coverage NA.
+    pub fn write_coverage(
+        &self,
+        path: &str,
+        fastq_seqs: &HashMap<String, String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        let mut w = std::io::BufWriter::new(file);
+        writeln!(w, "unitig_id\tlength\treads\tcoverage")?;
+        for u in &self.unitigs {
+            let length =
+                unitig_bp_length(u, fastq_seqs).map_or_else(|| "NA".to_string(), |l| l.to_string());
+            let coverage = u
+                .coverage
+                .map_or_else(|| "NA".to_string(), |c| format!("{:.2}", c));
+            writeln!(
+                w,
+                "unitig_{}\t{}\t{}\t{}",
+                u.id,
+                length,
+                u.members.len(),
+                coverage
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Find unitigs that contain both orientations of a read and log them.
+    /// With `drop`, such unitigs are removed together with their unitig edges. Otherwise they are split
+    /// before the member whose reverse complement was already seen, repeatedly, into unitigs that
+    /// are free of both orientations; the new parts get fresh ids.
+    /// Returns the number of palindromic unitigs found.
+    pub fn resolve_palindromic_unitigs(&mut self, drop: bool) -> usize {
+        let palindromic: Vec<usize> = self
+            .unitigs
+            .iter()
+            .filter(|u| self_rc_position(&u.members).is_some())
+            .map(|u| u.id)
+            .collect();
+        for id in &palindromic {
+            warn!(
+                "unitig_{} contains both orientations of a read, {}",
+                id,
+                if drop { "dropping it" } else { "splitting it" }
+            );
+        }
+
+        if drop {
+            let palindromic: HashSet<usize> = palindromic.iter().copied().collect();
+            self.unitigs.retain(|u| !palindromic.contains(&u.id));
+            self.edges
+                .retain(|e| !palindromic.contains(&e.from) && !palindromic.contains(&e.to));
+            return palindromic.len();
+        }
+
+        let mut next_id = self.unitigs.iter().map(|u| u.id + 1).max().unwrap_or(0);
+        let mut parts: Vec<Unitig> = Vec::new();
+        for unitig in self.unitigs.iter_mut() {
+            let mut current_id = unitig.id;
+            let mut rest: Option<Vec<UnitigMember>> = None;
+            if let Some(pos) = self_rc_position(&unitig.members) {
+                rest = Some(unitig.members.split_off(pos));
+                if let Some(last) = unitig.members.last_mut() {
+                    last.edge = (String::new(), 0);
+                }
+                unitig.fasta_seq = None;
+                unitig.coverage = None;
+                unitig.is_circular = false;
+            }
+            while let Some(mut members) = rest.take() {
+                if let Some(pos) = self_rc_position(&members) {
+                    rest = Some(members.split_off(pos));
+                    if let Some(last) = members.last_mut() {
+                        last.edge = (String::new(), 0);
                     }
                 }
+                // the edges leaving the unitig now leave its last part
+                for e in self.edges.iter_mut().filter(|e| e.from == current_id) {
+                    e.from = next_id;
+                }
+                current_id = next_id;
+                parts.push(Unitig {
+                    id: next_id,
+                    members,
+                    fasta_seq: None,
+                    is_circular: false,
+                    coverage: None,
+                });
+                next_id += 1;
+            }
+        }
+        self.unitigs.extend(parts);
+        self.edges.sort_unstable_by_key(|e| (e.from, e.to));
+
+        palindromic.len()
+    }
+
+    /// Trim the first and last member of every linear unitig if the read's mean coverage is below `min_coverage`.
+    /// At most one member is trimmed per end and at least one member is kept. The new last member loses its
+    /// member edge, unitig edges at a trimmed end are dropped since their overlap belonged to the trimmed read.
+    /// Reads without coverage information (0) are never trimmed. Returns the number of trimmed unitigs and members.
+    pub fn trim_low_coverage_ends(
+        &mut self,
+        graph: &crate::create_overlap_graph::OverlapGraph,
+        min_coverage: f64,
+    ) -> (usize, usize) {
+        let low_coverage = |member: Option<&UnitigMember>| {
+            member
+                .and_then(|m| graph.node(graph.index(&m.node_id)?))
+                .is_some_and(|n| n.coverage > 0.0 && n.coverage < min_coverage)
+        };
+
+        let mut trimmed_starts: HashSet<usize> = HashSet::new();
+        let mut trimmed_ends: HashSet<usize> = HashSet::new();
+        let mut trimmed_members = 0usize;
+        for unitig in self.unitigs.iter_mut().filter(|u| !u.is_circular) {
+            if unitig.members.len() > 1 && low_coverage(unitig.members.first()) {
+                unitig.members.remove(0);
+                trimmed_starts.insert(unitig.id);
+                trimmed_members += 1;
+            }
+            if unitig.members.len() > 1 && low_coverage(unitig.members.last()) {
+                unitig.members.pop();
+                if let Some(last) = unitig.members.last_mut() {
+                    last.edge = (String::new(), 0);
+                }
+                trimmed_ends.insert(unitig.id);
+                trimmed_members += 1;
+            }
+            // the sequence and coverage no longer match the members
+            if trimmed_starts.contains(&unitig.id) || trimmed_ends.contains(&unitig.id) {
+                unitig.fasta_seq = None;
+                unitig.coverage = None;
             }
         }
+        self.edges
+            .retain(|e| !trimmed_ends.contains(&e.from) && !trimmed_starts.contains(&e.to));
+
+        let trimmed_unitigs = trimmed_starts.union(&trimmed_ends).count();
+        (trimmed_unitigs, trimmed_members)
     }
 
-    let edges: Vec<UnitigEdge> = unitig_edge_map.into_values().collect();
+    /// Write the read tiling of every unitig with a sequence: unitig_id, read_name, orientation, start, end.
+    /// Coordinates are 0-based and end-exclusive, reads of a circular unitig may end past the unitig length.
+    pub fn write_layout(
+        &self,
+        path: &str,
+        fastq_seqs: &HashMap<String, String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        let mut w = std::io::BufWriter::new(file);
+        writeln!(w, "unitig_id\tread_name\torientation\tstart\tend")?;
+        for u in self.unitigs.iter().filter(|u| u.fasta_seq.is_some()) {
+            let Some(layout) = unitig_layout(u, fastq_seqs) else {
+                continue;
+            };
+            for (read_name, orientation, start, end) in layout {
+                writeln!(
+                    w,
+                    "unitig_{}\t{}\t{}\t{}\t{}",
+                    u.id, read_name, orientation, start, end
+                )?;
+            }
+        }
+        Ok(())
+    }
 
-    // load fastq sequences
-    println!("Loading FASTQ sequences from {}...", fastq_path);
-    let fastq_seqs = load_fastq_sequences(fastq_path).unwrap();
+    /// Drop unitigs shorter than `min_len` bases, together with the unitig edges that reference them.
+    /// Unitigs whose length can't be determined are kept. Returns the number of removed unitigs and bases.
+    pub fn remove_short_unitigs(
+        &mut self,
+        min_len: usize,
+        fastq_seqs: &HashMap<String, String>,
+    ) -> (usize, usize) {
+        let mut removed: HashSet<usize> = HashSet::new();
+        let mut removed_bases = 0usize;
+        self.unitigs
+            .retain(|u| match unitig_bp_length(u, fastq_seqs) {
+                Some(len) if len < min_len => {
+                    removed.insert(u.id);
+                    removed_bases += len;
+                    false
+                }
+                _ => true,
+            });
+        self.edges
+            .retain(|e| !removed.contains(&e.from) && !removed.contains(&e.to));
 
-    // generate fasta sequences for unitigs and write to fasta_path
-    println!("Generating unitig sequences and writing to FASTA...");
-    for unitig in unitigs.iter_mut() {
-        let seq = unitig_sequence(unitig, graph, fastq_seqs.clone()).unwrap();
-        unitig.fasta_seq = Some(seq);
+        (removed.len(), removed_bases)
     }
-    // write to fasta file
-    {
-        let mut fasta_file = std::fs::File::create(fasta_path).unwrap();
-        for unitig in unitigs.iter() {
-            let seq = unitig.fasta_seq.as_ref().unwrap();
-            let header = format!(">unitig_{} len={}bp\n", unitig.id, seq.len());
-            use std::io::Write;
-            fasta_file.write_all(header.as_bytes()).unwrap();
-            fasta_file.write_all(seq.as_bytes()).unwrap();
-            fasta_file.write_all(b"\n").unwrap();
+
+    /// Write the unitigs with a sequence to a FASTA file, the header carries the unitig id and length
+    pub fn write_fasta(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        let mut w = std::io::BufWriter::new(file);
+        for unitig in &self.unitigs {
+            if let Some(seq) = &unitig.fasta_seq {
+                let circular = if unitig.is_circular {
+                    " circular=true"
+                } else {
+                    ""
+                };
+                writeln!(w, ">unitig_{} len={}bp{}", unitig.id, seq.len(), circular)?;
+                writeln!(w, "{}", seq)?;
+            }
         }
+        Ok(())
     }
 
-    CompressedGraph { unitigs, edges }
-}
+    /// Pair every unitig with its reverse-complement twin.
+    /// Each read graph path is present in both orientations, so a unitig [a+, b+, c+] has a twin [c-, b-, a-].
+    /// Returns, for every unitig, the id of the representative unitig of the pair (lowest id) and the
+    /// orientation of the unitig relative to that representative. Unitigs without a twin represent themselves.
+    /// The twin of a circular unitig runs through the same cycle backwards and may start at any of its nodes.
+    pub fn segment_orientations(&self) -> HashMap<usize, (usize, char)> {
+        // index unitigs by their first member, circular unitigs by every member
+        let mut by_first: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut circular_by_member: HashMap<&str, usize> = HashMap::new();
+        for (idx, u) in self.unitigs.iter().enumerate() {
+            if let Some(first) = u.members.first() {
+                by_first
+                    .entry(first.node_id.as_str())
+                    .or_default()
+                    .push(idx);
+            }
+            if u.is_circular {
+                for member in &u.members {
+                    circular_by_member.insert(member.node_id.as_str(), idx);
+                }
+            }
+        }
 
-impl CompressedGraph {
+        let mut orientations: HashMap<usize, (usize, char)> = HashMap::new();
+        for (idx, u) in self.unitigs.iter().enumerate() {
+            if orientations.contains_key(&u.id) {
+                continue;
+            }
+            // the twin starts with rc(last member), ends with rc(first member) and has the same length
+            let twin = match (
+                u.members.first().and_then(|m| utils::rc_node(&m.node_id)),
+                u.members.last().and_then(|m| utils::rc_node(&m.node_id)),
+            ) {
+                (Some(rc_first), _) if u.is_circular => circular_by_member
+                    .get(rc_first.as_str())
+                    .copied()
+                    .filter(|&t| {
+                        let tu = &self.unitigs[t];
+                        t != idx
+                            && !orientations.contains_key(&tu.id)
+                            && tu.members.len() == u.members.len()
+                    }),
+                (Some(rc_first), Some(rc_last)) => by_first
+                    .get(rc_last.as_str())
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .find(|&t| {
+                        let tu = &self.unitigs[t];
+                        t != idx
+                            && !orientations.contains_key(&tu.id)
+                            && tu.members.len() == u.members.len()
+                            && tu.members.last().map(|m| m.node_id.as_str())
+                                == Some(rc_first.as_str())
+                    }),
+                _ => None,
+            };
+
+            orientations.insert(u.id, (u.id, '+'));
+            if let Some(t) = twin {
+                orientations.insert(self.unitigs[t].id, (u.id, '-'));
+            }
+        }
+        orientations
+    }
+
+    /// Write a TSV mapping every read to its unitig: read_name, orientation, unitig_id, position in the unitig.
+    /// Reads are listed once, using the representative unitig of each reverse-complement pair first.
+    /// Duplicate reads collapsed into a representative (`read_aliases`: duplicate -> representative) are
+    /// listed with the placement of their representative.
+    pub fn write_read_mapping(
+        &self,
+        path: &str,
+        read_aliases: &HashMap<String, String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        let mut w = std::io::BufWriter::new(file);
+        let orientations = self.segment_orientations();
+
+        writeln!(w, "read_name\torientation\tunitig_id\tposition")?;
+
+        // duplicates per representative
+        let mut duplicates: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (duplicate, representative) in read_aliases {
+            duplicates
+                .entry(representative.as_str())
+                .or_default()
+                .push(duplicate.as_str());
+        }
+        for names in duplicates.values_mut() {
+            names.sort_unstable();
+        }
+
+        // representative unitigs first, the others only add reads that were not seen yet
+        let (representatives, others): (Vec<&Unitig>, Vec<&Unitig>) = self
+            .unitigs
+            .iter()
+            .partition(|u| orientations.get(&u.id) == Some(&(u.id, '+')));
+
+        let mut seen: HashSet<&str> = HashSet::new();
+        for u in representatives.into_iter().chain(others) {
+            for (position, member) in u.members.iter().enumerate() {
+                let Some((read_name, orientation)) = utils::split_node_id(&member.node_id) else {
+                    continue;
+                };
+                if !seen.insert(read_name) {
+                    continue;
+                }
+                let copies = duplicates.get(read_name).into_iter().flatten();
+                for name in std::iter::once(&read_name).chain(copies) {
+                    writeln!(
+                        w,
+                        "{}\t{}\tunitig_{}\t{}",
+                        name, orientation, u.id, position
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Input reads that ended up in no unitig, sorted by name.
+    /// Duplicates of a read in a unitig and the reads in `contained` are represented by another read and not listed.
+    pub fn singleton_reads(
+        &self,
+        fastq_seqs: &HashMap<String, String>,
+        read_aliases: &HashMap<String, String>,
+        contained: &HashSet<&str>,
+    ) -> Vec<String> {
+        let placed: HashSet<&str> = self
+            .unitigs
+            .iter()
+            .flat_map(|u| &u.members)
+            .filter_map(|m| utils::split_node_id(&m.node_id).map(|(name, _)| name))
+            .collect();
+        let mut singletons: Vec<String> = fastq_seqs
+            .keys()
+            .filter(|name| {
+                let representative = read_aliases.get(*name).unwrap_or(*name);
+                !placed.contains(representative.as_str()) && !contained.contains(name.as_str())
+            })
+            .cloned()
+            .collect();
+        singletons.sort_unstable();
+        singletons
+    }
+
+    /// Write the unitig graph in GFA1 format.
+    /// Only one unitig of every reverse-complement pair is written as a segment, links refer to the
+    /// other one through the orientation, and the reverse-complement duplicate of each link is skipped.
     pub fn write_gfa(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut file = std::fs::File::create(path)?;
-        use std::io::Write;
+        let file = std::fs::File::create(path)?;
+        let mut w = std::io::BufWriter::new(file);
+        let orientations = self.segment_orientations();
+        let flip = |o: char| if o == '+' { '-' } else { '+' };
+
         // header
-        writeln!(file, "H\tVN:Z:1.0")?;
+        writeln!(w, "H\tVN:Z:1.0")?;
 
         // segments
         for u in &self.unitigs {
+            if orientations.get(&u.id) != Some(&(u.id, '+')) {
+                continue;
+            }
             let sid = format!("unitig_{}", u.id);
-            let seq = u.fasta_seq.as_ref().map(|s| s.as_str()).unwrap_or("*");
-            writeln!(file, "S\t{}\t{}", sid, seq)?;
+            let seq = u.fasta_seq.as_deref().unwrap_or("*");
+            write!(w, "S\t{}\t{}", sid, seq)?;
+            if let Some(coverage) = u.coverage {
+                write!(w, "\tDP:f:{:.2}", coverage)?;
+            }
+            if u.is_circular {
+                write!(w, "\tTP:Z:circular")?;
+            }
+            writeln!(w)?;
         }
 
         // links
+        let mut written: HashSet<(usize, char, usize, char)> = HashSet::new();
         for e in &self.edges {
-            let from = format!("unitig_{}", e.from);
-            let to = format!("unitig_{}", e.to);
+            let (from, from_orient) = orientations[&e.from];
+            let (to, to_orient) = orientations[&e.to];
+            // a link and its reverse complement describe the same overlap
+            let link = (from, from_orient, to, to_orient);
+            let rc_link = (to, flip(to_orient), from, flip(from_orient));
+            if !written.insert(std::cmp::min(link, rc_link)) {
+                continue;
+            }
             // represent overlap as CIGAR M operation
             let cigar = format!("{}M", e.overlap_len);
-            writeln!(file, "L\t{}\t+\t{}\t+\t{}", from, to, cigar)?;
+            writeln!(
+                w,
+                "L\tunitig_{}\t{}\tunitig_{}\t{}\t{}",
+                from, from_orient, to, to_orient, cigar
+            )?;
         }
 
         Ok(())
     }
 }
 
-fn load_fastq_sequences(fastq_path: &str) -> Result<HashMap<String, String>, String> {
-    let mut seq_map: HashMap<String, String> = HashMap::new();
-
-    let reader = match std::fs::File::open(fastq_path) {
-        Ok(f) => f,
-        Err(e) => return Err(format!("failed to open FASTQ file '{}': {}", fastq_path, e)),
+/// Check the compressed graph for internal consistency:
+/// every unitig has members, unitig ids are unique, every node is a member of at most one unitig,
+/// consecutive members are linked by their member edge, every unitig edge references existing unitigs
+/// and no member edge length exceeds the length of its read.
+/// Returns the first few violations as an error.
+pub fn validate_compressed(
+    compressed: &CompressedGraph,
+    fastq_seqs: &HashMap<String, String>,
+) -> Result<(), String> {
+    const MAX_REPORTED: usize = 5;
+    let mut violations: Vec<String> = Vec::new();
+    let mut total = 0usize;
+    let mut report = |msg: String| {
+        total += 1;
+        if violations.len() < MAX_REPORTED {
+            violations.push(msg);
+        }
     };
-    let buf_reader = std::io::BufReader::new(reader);
-    let mut lines = buf_reader.lines();
-
-    while let Some(Ok(header)) = lines.next() {
-        if !header.starts_with('@') {
-            return Err(format!(
-                "invalid FASTQ format: expected header line starting with '@', got '{}'",
-                header
+
+    let mut unitig_ids: HashSet<usize> = HashSet::new();
+    let mut node_to_unitig: HashMap<&str, usize> = HashMap::new();
+    for u in &compressed.unitigs {
+        if !unitig_ids.insert(u.id) {
+            report(format!("unitig id {} is used more than once", u.id));
+        }
+        if u.members.is_empty() {
+            report(format!("unitig_{} has no members", u.id));
+        }
+
+        for (i, m) in u.members.iter().enumerate() {
+            // membership
+            if let Some(other) = node_to_unitig.insert(m.node_id.as_str(), u.id) {
+                report(format!(
+                    "node {} is a member of unitig_{} and unitig_{}",
+                    m.node_id, other, u.id
+                ));
+            }
+
+            // the member edge points to the next member
+            if let Some(next) = u.members.get(i + 1) {
+                if m.edge.0 != next.node_id {
+                    report(format!(
+                        "unitig_{}: member {} links to {} but is followed by {}",
+                        u.id, m.node_id, m.edge.0, next.node_id
+                    ));
+                }
+            }
+
+            // the edge length can't be longer than the read itself
+            let read_len = utils::split_node_id(&m.node_id)
+                .and_then(|(read_id, _)| fastq_seqs.get(read_id))
+                .map(|seq| seq.len());
+            if let Some(read_len) = read_len {
+                if m.edge.1 as usize > read_len {
+                    report(format!(
+                        "unitig_{}: edge length {} of member {} exceeds its read length {}",
+                        u.id, m.edge.1, m.node_id, read_len
+                    ));
+                }
+            }
+        }
+    }
+
+    for e in &compressed.edges {
+        if !unitig_ids.contains(&e.from) || !unitig_ids.contains(&e.to) {
+            report(format!(
+                "unitig edge {} -> {} references a missing unitig",
+                e.from, e.to
             ));
         }
-        let seq = match lines.next() {
-            Some(Ok(s)) => s,
-            _ => return Err("invalid FASTQ format: missing sequence line".to_string()),
-        };
-        // skip plus line
-        lines.next();
-        // skip quality line
-        lines.next();
+    }
+
+    if total == 0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} violations, first {}: {}",
+            total,
+            violations.len(),
+            violations.join("; ")
+        ))
+    }
+}
 
-        let id = header[1..].split_whitespace().next().unwrap().to_string();
-        seq_map.insert(id, seq);
+/// Length of a unitig in bases.
+/// Uses the assembled sequence if it was built, otherwise the sum of the member edge lengths plus the
+/// length of the last read. A circular unitig is the sum of all member edge lengths, the edge of its last
+/// member closes the cycle. Returns None if a needed read sequence is missing.
+pub fn unitig_bp_length(unitig: &Unitig, fastq_seqs: &HashMap<String, String>) -> Option<usize> {
+    if let Some(seq) = &unitig.fasta_seq {
+        return Some(seq.len());
     }
+    if unitig.is_circular {
+        return Some(unitig.members.iter().map(|m| m.edge.1 as usize).sum());
+    }
+    let last = unitig.members.last()?;
+    let (read_id, _) = utils::split_node_id(&last.node_id)?;
+    let last_len = fastq_seqs.get(read_id)?.len();
+    let prefix_len: usize = unitig.members[..unitig.members.len() - 1]
+        .iter()
+        .map(|m| m.edge.1 as usize)
+        .sum();
+    Some(prefix_len + last_len)
+}
 
-    Ok(seq_map)
+/// Read tiling of a unitig: (read name, orientation, start, end) per member, in unitig coordinates.
+/// A member starts where the previous one starts plus the previous edge length and spans its full read.
+/// Returns None if a member id has no orientation suffix or a read sequence is missing.
+pub fn unitig_layout(
+    unitig: &Unitig,
+    fastq_seqs: &HashMap<String, String>,
+) -> Option<Vec<(String, char, usize, usize)>> {
+    let mut layout = Vec::with_capacity(unitig.members.len());
+    let mut start = 0usize;
+    for member in &unitig.members {
+        let (read_name, orientation) = utils::split_node_id(&member.node_id)?;
+        let read_len = fastq_seqs.get(read_name)?.len();
+        layout.push((read_name.to_string(), orientation, start, start + read_len));
+        start += member.edge.1 as usize;
+    }
+    Some(layout)
+}
+
+/// Write the named reads from `fastq_seqs` as FASTA records, names without a sequence are skipped
+pub fn write_reads_fasta(
+    path: &std::path::Path,
+    names: &[String],
+    fastq_seqs: &HashMap<String, String>,
+) -> std::io::Result<()> {
+    let mut w = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for name in names {
+        if let Some(seq) = fastq_seqs.get(name) {
+            writeln!(w, ">{} len={}bp", name, seq.len())?;
+            writeln!(w, "{}", seq)?;
+        }
+    }
+    Ok(())
+}
+
+/// N50 of a set of lengths: the length L such that items of length >= L cover half of the total
+pub fn n50(lengths: &[usize]) -> usize {
+    let mut sorted = lengths.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let total: usize = sorted.iter().sum();
+    let mut cumulative = 0usize;
+    for len in sorted {
+        cumulative += len;
+        if cumulative * 2 >= total {
+            return len;
+        }
+    }
+    0
 }
 
 /// Build nucleotide sequence for `unitig` by concatenating node sequences and
 /// removing overlaps recorded in UnitigMember.edge.(target, edge_len).
 pub fn unitig_sequence(
     unitig: &Unitig,
-    graph: &crate::create_overlap_graph::OverlapGraph,
-    fastq_seqs: HashMap<String, String>,
+    graph: &OverlapGraph,
+    fastq_seqs: &HashMap<String, String>,
 ) -> Result<String, String> {
     if unitig.members.is_empty() {
-        println!("unitig has no members; cannot infer sequence");
+        return Err("unitig has no members; cannot infer sequence".to_string());
     }
 
     // Helper to get sequence for a node id
     let get_seq = |node_id: &str| -> Result<String, String> {
         // Node ID must end with '+' or '-'
-        let orientation = node_id
-            .chars()
-            .last()
+        let (read_id, orientation) = utils::split_node_id(node_id)
             .ok_or_else(|| format!("node id '{}' has no orientation suffix (+/-)", node_id))?;
-        let read_id = &node_id[..node_id.len() - 1];
         let _node = graph
-            .nodes
-            .get(node_id)
+            .index(node_id)
             .ok_or_else(|| format!("node_id '{}' not found in overlap graph", node_id))?;
         let seq = fastq_seqs.get(read_id).ok_or_else(|| {
             format!(
@@ -353,13 +985,10 @@ pub fn unitig_sequence(
                 read_id
             )
         })?;
-        match orientation {
-            '+' => Ok(seq.clone()),
-            '-' => Ok(utils::rev_comp(seq)),
-            _ => Err(format!(
-                "invalid orientation '{}' in node id '{}'",
-                orientation, node_id
-            )),
+        if orientation == '+' {
+            Ok(seq.clone())
+        } else {
+            Ok(utils::rev_comp(seq))
         }
     };
 
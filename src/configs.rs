@@ -1,10 +1,25 @@
+use crate::alignment_filtering::{ChimeraParams, FilteringOptions};
+
 pub struct AlignmentFilteringConfig {
     pub input_paf: String,
     pub output_overlaps: String,
+    pub overlaps_per_read: Option<String>,
     pub min_overlap_length: u32,
     pub min_overlap_count: u32,
     pub min_percent_identity: f32,
+    pub min_mapq: u8,
     pub overhang_ratio: f32,
+    pub max_overhang: u32,
+    pub containment_fuzz: u32,
+    pub paf_self_overlaps_as_repeats: bool,
+    pub max_alignments_per_pair: usize,
+    pub keep_contained_reads: bool,
+    pub merge_split_alignments: Option<u32>,
+    pub chimera_min_flank_coverage: Option<u32>,
+    pub chimera_window: u32,
+    pub coverage_bin_size: u32,
+    pub trim_overhangs: u32,
+    pub chimera_max_valley_coverage: u32,
 }
 
 pub struct AssembleConfig {
@@ -12,15 +27,167 @@ pub struct AssembleConfig {
     pub min_overlap_length: u32,
     pub min_overlap_count: u32,
     pub min_percent_identity: f32,
+    pub min_mapq: u8,
     pub overhang_ratio: f32,
+    pub max_overhang: u32,
+    pub containment_fuzz: u32,
     pub overlaps: Option<String>,
+    pub input_gfa: Option<String>,
+    pub resume_from: Option<crate::cli::ResumeStage>,
     pub reads_fq: String,
     pub output_prefix: String,
     pub output_dir: String,
     pub max_bubble_length: u32,
     pub min_support_ratio: f64,
+    pub bubble_overlap_weight: f64,
+    pub bubble_identity_weight: f64,
+    pub bubble_read_count_weight: f64,
     pub max_tip_len: u32,
     pub fuzz: u32,
+    pub no_smallest_edge_rule: bool,
     pub cleanup_iterations: u32,
+    pub no_transitive_reduction: bool,
+    pub no_bubble_removal: bool,
+    pub no_tip_trimming: bool,
+    pub no_short_edge_removal: bool,
+    pub dry_run: bool,
     pub short_edge_ratio: f64,
+    pub max_biloop_ext: u32,
+    pub max_internal_ext: u32,
+    pub remove_weak_edges: bool,
+    pub weak_edge_ratio: f64,
+    pub weak_edge_identity_tolerance: f64,
+    pub mask_repeats: bool,
+    pub repeat_min_indegree: usize,
+    pub repeat_min_outdegree: usize,
+    pub paf_self_overlaps_as_repeats: bool,
+    pub max_alignments_per_pair: usize,
+    pub keep_contained_reads: bool,
+    pub merge_split_alignments: Option<u32>,
+    pub chimera_min_flank_coverage: Option<u32>,
+    pub chimera_window: u32,
+    pub coverage_bin_size: u32,
+    pub trim_overhangs: u32,
+    pub chimera_max_valley_coverage: u32,
+    pub min_edge_identity: f64,
+    pub validate_overlaps: bool,
+    pub min_unitig_length: u32,
+    pub min_end_coverage: f64,
+    pub drop_palindromic_unitigs: bool,
+    pub standalone_junctions: bool,
+    pub consensus_min_depth: u32,
+    pub write_singletons: bool,
+    pub threads: usize,
+    pub dedup_reads: bool,
+    pub min_tip_identity: f64,
+    pub max_low_identity_tip_len: u32,
+    pub max_tip_bp: u32,
+    pub max_tip_bp_nodes: u32,
+    pub split_articulation_min_nodes: u32,
+    pub keep_intermediate: bool,
+    pub diff: bool,
+    pub dump_raw_graph: Option<String>,
+    pub stats_json: Option<String>,
+    pub genome_size: Option<usize>,
+}
+
+pub struct GraphStatsConfig {
+    pub input_paf: Option<String>,
+    pub overlaps: Option<String>,
+    pub min_overlap_length: u32,
+    pub min_overlap_count: u32,
+    pub min_percent_identity: f32,
+    pub min_mapq: u8,
+    pub overhang_ratio: f32,
+    pub max_overhang: u32,
+    pub containment_fuzz: u32,
+    pub top: usize,
+}
+
+pub struct OverlapsToPafConfig {
+    pub overlaps: String,
+    pub output: String,
+}
+
+impl From<&AlignmentFilteringConfig> for FilteringOptions {
+    fn from(config: &AlignmentFilteringConfig) -> Self {
+        FilteringOptions {
+            min_overlap_length: config.min_overlap_length,
+            min_overlap_count: config.min_overlap_count,
+            min_percent_identity: config.min_percent_identity,
+            min_mapq: config.min_mapq,
+            overhang_ratio: config.overhang_ratio,
+            max_overhang: config.max_overhang,
+            containment_fuzz: config.containment_fuzz,
+            self_overlaps_as_repeats: config.paf_self_overlaps_as_repeats,
+            max_alignments_per_pair: config.max_alignments_per_pair,
+            chimera: chimera_params(
+                config.chimera_min_flank_coverage,
+                config.chimera_window,
+                config.chimera_max_valley_coverage,
+            ),
+            record_contained: config.keep_contained_reads,
+            merge_split_gap: config.merge_split_alignments,
+            coverage_bin_size: config.coverage_bin_size as usize,
+            trim_overhangs: config.trim_overhangs,
+        }
+    }
+}
+
+impl From<&AssembleConfig> for FilteringOptions {
+    fn from(config: &AssembleConfig) -> Self {
+        FilteringOptions {
+            min_overlap_length: config.min_overlap_length,
+            min_overlap_count: config.min_overlap_count,
+            min_percent_identity: config.min_percent_identity,
+            min_mapq: config.min_mapq,
+            overhang_ratio: config.overhang_ratio,
+            max_overhang: config.max_overhang,
+            containment_fuzz: config.containment_fuzz,
+            self_overlaps_as_repeats: config.paf_self_overlaps_as_repeats,
+            max_alignments_per_pair: config.max_alignments_per_pair,
+            chimera: chimera_params(
+                config.chimera_min_flank_coverage,
+                config.chimera_window,
+                config.chimera_max_valley_coverage,
+            ),
+            // the consensus places contained reads on their containers,
+            // the singleton output needs them to tell contained reads apart
+            record_contained: config.keep_contained_reads
+                || config.consensus_min_depth > 0
+                || config.write_singletons,
+            merge_split_gap: config.merge_split_alignments,
+            coverage_bin_size: config.coverage_bin_size as usize,
+            trim_overhangs: config.trim_overhangs,
+        }
+    }
+}
+
+/// The graph-stats command filters with the thresholds it is given and the defaults for everything else
+impl From<&GraphStatsConfig> for FilteringOptions {
+    fn from(config: &GraphStatsConfig) -> Self {
+        FilteringOptions {
+            min_overlap_length: config.min_overlap_length,
+            min_overlap_count: config.min_overlap_count,
+            min_percent_identity: config.min_percent_identity,
+            min_mapq: config.min_mapq,
+            overhang_ratio: config.overhang_ratio,
+            max_overhang: config.max_overhang,
+            containment_fuzz: config.containment_fuzz,
+            ..FilteringOptions::default()
+        }
+    }
+}
+
+/// Chimera detection parameters, None if no minimum flank coverage is set
+fn chimera_params(
+    min_flank_coverage: Option<u32>,
+    window: u32,
+    max_valley_coverage: u32,
+) -> Option<ChimeraParams> {
+    min_flank_coverage.map(|min_flank_cov| ChimeraParams {
+        min_flank_cov,
+        window: window as usize,
+        max_valley_cov: max_valley_coverage,
+    })
 }
@@ -0,0 +1,225 @@
+use crate::alignment_filtering::ContainedPlacement;
+use crate::compress_graph::{CompressedGraph, unitig_layout};
+use crate::utils;
+/// consensus module
+/// polishes unitig sequences with a per-position majority vote over the reads stacked on them
+/// 1. stack the backbone reads of a unitig at their layout position
+/// 2. stack the contained reads on top of their container, using the offset of the containment alignment
+/// 3. count the bases per column and replace the backbone base if another base has a strict majority
+///
+/// The vote is ungapped: reads are placed by their start only, so the columns are only meaningful for
+/// substitution errors. Indels shift the rest of a read out of phase, those columns rarely reach a majority.
+/// A column covered by fewer than `min_depth` reads keeps the backbone base.
+use std::collections::HashMap;
+
+// column index per base, everything else (N, IUPAC codes) is counted as 'other'
+fn base_index(base: u8) -> usize {
+    match base.to_ascii_uppercase() {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => 4,
+    }
+}
+
+/// Majority vote consensus of one unitig sequence.
+/// `stack` holds (start in unitig coordinates, oriented read sequence), reads are clipped to the backbone.
+/// Returns the polished sequence and the number of changed positions.
+pub fn majority_consensus(
+    backbone: &str,
+    stack: &[(i64, String)],
+    min_depth: usize,
+) -> (String, usize) {
+    let backbone = backbone.as_bytes();
+    let mut counts: Vec<[u32; 5]> = vec![[0; 5]; backbone.len()];
+    for (start, seq) in stack {
+        for (i, base) in seq.bytes().enumerate() {
+            let pos = start + i as i64;
+            if pos < 0 {
+                continue;
+            }
+            let Some(column) = counts.get_mut(pos as usize) else {
+                break;
+            };
+            column[base_index(base)] += 1;
+        }
+    }
+
+    let mut changed = 0usize;
+    let polished: Vec<u8> = backbone
+        .iter()
+        .zip(&counts)
+        .map(|(&base, column)| {
+            let depth: u32 = column.iter().sum();
+            if (depth as usize) < min_depth {
+                return base;
+            }
+            // strict majority among the four bases, 'other' never wins
+            let Some((best, &count)) = column[..4].iter().enumerate().max_by_key(|(_, c)| **c)
+            else {
+                return base;
+            };
+            if count * 2 <= depth || best == base_index(base) {
+                return base;
+            }
+            changed += 1;
+            b"ACGT"[best]
+        })
+        .collect();
+    (String::from_utf8_lossy(&polished).into_owned(), changed)
+}
+
+/// Polish every unitig with a sequence by majority vote over its backbone and contained reads.
+/// `contained_in` maps contained read names to their placement on a graph read, it may be empty.
+/// Returns the number of changed positions over all unitigs.
+pub fn polish_unitigs(
+    compressed: &mut CompressedGraph,
+    fastq_seqs: &HashMap<String, String>,
+    contained_in: &HashMap<String, ContainedPlacement>,
+    min_depth: usize,
+) -> usize {
+    // container name -> contained reads placed on it
+    let mut by_container: HashMap<&str, Vec<(&str, &ContainedPlacement)>> = HashMap::new();
+    for (read, placement) in contained_in {
+        by_container
+            .entry(placement.container.as_str())
+            .or_default()
+            .push((read.as_str(), placement));
+    }
+
+    let mut total_changed = 0usize;
+    for unitig in &mut compressed.unitigs {
+        let Some(backbone) = unitig.fasta_seq.as_ref() else {
+            continue;
+        };
+        let Some(layout) = unitig_layout(unitig, fastq_seqs) else {
+            continue;
+        };
+
+        let oriented = |seq: &str, strand: char| -> String {
+            if strand == '+' {
+                seq.to_string()
+            } else {
+                utils::rev_comp(seq)
+            }
+        };
+        let mut stack: Vec<(i64, String)> = Vec::new();
+        for (read, orientation, start, end) in &layout {
+            let Some(seq) = fastq_seqs.get(read) else {
+                continue;
+            };
+            stack.push((*start as i64, oriented(seq, *orientation)));
+
+            // contained reads, their offset is on the + strand of the container
+            let container_len = (end - start) as i64;
+            for (contained, placement) in by_container.get(read.as_str()).into_iter().flatten() {
+                let Some(contained_seq) = fastq_seqs.get(*contained) else {
+                    continue;
+                };
+                let contained_len = contained_seq.len() as i64;
+                let (offset, strand) = if *orientation == '+' {
+                    (placement.offset, placement.strand)
+                } else {
+                    // container is reverse complemented in the unitig, mirror the placement
+                    let flipped = if placement.strand == '+' { '-' } else { '+' };
+                    (container_len - placement.offset - contained_len, flipped)
+                };
+                stack.push((*start as i64 + offset, oriented(contained_seq, strand)));
+            }
+        }
+
+        let (polished, changed) = majority_consensus(backbone, &stack, min_depth);
+        total_changed += changed;
+        unitig.fasta_seq = Some(polished);
+    }
+    total_changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress_graph::{Unitig, UnitigMember};
+
+    /// Reads of the stack, all starting at the first backbone position
+    fn stack(reads: &[&str]) -> Vec<(i64, String)> {
+        reads.iter().map(|r| (0, r.to_string())).collect()
+    }
+
+    #[test]
+    fn majority_substitution_replaces_the_backbone_base() {
+        // two of the three reads have a T at position 2
+        let (polished, changed) =
+            majority_consensus("ACGTA", &stack(&["ACGTA", "ACTTA", "ACTTA"]), 1);
+        assert_eq!((polished.as_str(), changed), ("ACTTA", 1));
+    }
+
+    #[test]
+    fn tie_keeps_the_backbone_base() {
+        // G and T both have one vote at position 2, neither is a strict majority
+        let (polished, changed) = majority_consensus("ACGTA", &stack(&["ACGTA", "ACTTA"]), 1);
+        assert_eq!((polished.as_str(), changed), ("ACGTA", 0));
+    }
+
+    #[test]
+    fn columns_below_min_depth_keep_the_backbone_base() {
+        // three reads agree on the T, but a depth of 4 is required
+        let reads = stack(&["ACTTA", "ACTTA", "ACTTA"]);
+        assert_eq!(
+            majority_consensus("ACGTA", &reads, 4),
+            ("ACGTA".to_string(), 0)
+        );
+        assert_eq!(
+            majority_consensus("ACGTA", &reads, 3),
+            ("ACTTA".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn contained_reads_are_mirrored_on_a_reverse_complemented_container() {
+        // container c is the only unitig member, on its - strand. Two copies of a 4 bp read are contained at
+        // offset 2 on the + strand of c, with a T -> A substitution at position 3 of c
+        let container = "ACGTACGGTC";
+        let fastq_seqs: HashMap<String, String> =
+            [("c", container), ("r1", "GAAC"), ("r2", "GAAC")]
+                .into_iter()
+                .map(|(name, seq)| (name.to_string(), seq.to_string()))
+                .collect();
+        let contained_in: HashMap<String, ContainedPlacement> = ["r1", "r2"]
+            .into_iter()
+            .map(|read| {
+                let placement = ContainedPlacement {
+                    container: "c".to_string(),
+                    strand: '+',
+                    offset: 2,
+                };
+                (read.to_string(), placement)
+            })
+            .collect();
+        let backbone = utils::rev_comp(container);
+        assert_eq!(backbone, "GACCGTACGT");
+        let mut compressed = CompressedGraph {
+            unitigs: vec![Unitig {
+                id: 0,
+                members: vec![UnitigMember {
+                    node_id: "c-".to_string(),
+                    edge: (String::new(), 0),
+                }],
+                fasta_seq: Some(backbone),
+                is_circular: false,
+                coverage: None,
+            }],
+            edges: Vec::new(),
+        };
+
+        // the reads start at 10 - 2 - 4 = 4 in the unitig, reverse complemented, the substitution lands on 6
+        assert_eq!(
+            polish_unitigs(&mut compressed, &fastq_seqs, &contained_in, 1),
+            1
+        );
+        assert_eq!(
+            compressed.unitigs[0].fasta_seq.as_deref(),
+            Some("GACCGTTCGT")
+        );
+    }
+}
@@ -1,109 +1,405 @@
-use crate::alignment_filtering::Overlap;
+use crate::alignment_filtering::{AlignmentFilteringOutput, Overlap, verify_overlap_orientations};
+use crate::utils::split_node_id;
+use log::{info, trace, warn};
+use serde::{Deserialize, Serialize};
 /// Overlap graph creation module
 /// read overlaps from alignment filtering module and build the overlap graph
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io;
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Index of a node in the overlap graph. Read i is represented by the nodes 2 * i ("<read_name>+") and
+/// 2 * i + 1 ("<read_name>-"), so the orientation is the low bit and the reverse complement of a node
+/// is `rc_index(idx)` = idx ^ 1.
+pub type NodeIndex = u32;
+
+/// Reverse complement of a node index
+pub fn rc_index(idx: NodeIndex) -> NodeIndex {
+    idx ^ 1
+}
 
 /// Edge info containing all the metrics we track
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct EdgeInfo {
-    pub target_id: String,
+    pub target: NodeIndex,
     pub edge_len: u32,
     pub overlap_len: u32,
+    // percent identity (0-100) of the overlap
     pub identity: f64,
 }
 
 /// A node in the overlap graph. Earch read is represented by two nodes: "<read_name>+" and "<read_name>-"
 /// One for the origininal orientation and one for the reverse complement
 /// Each node has directed edges to other nodes with associated edge lengths
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Node {
-    node_id: String,
     pub edges: Vec<EdgeInfo>,
+    // source nodes of the incoming edges, one entry per edge
+    // kept in sync by the OverlapGraph edge and node methods
+    in_edges: Vec<NodeIndex>,
+    // the read has self-alignments off the main diagonal (internal repeat)
+    pub is_repeat: bool,
+    // mean per-base coverage of the read, 0 if unknown (e.g. graph loaded from GFA)
+    pub coverage: f64,
 }
 
 impl Node {
-    /// Create a new node with given id and no edges
-    fn new(node_id: String) -> Self {
+    /// Create a new node with no edges
+    fn new() -> Self {
         Self {
-            node_id,
             edges: Vec::new(),
+            in_edges: Vec::new(),
+            is_repeat: false,
+            coverage: 0.0,
         }
     }
 
-    /// Add a directed edge to a node, if an edge to the target already exists, we ignore (avoid duplicates)
-    fn add_edge(&mut self, target_node: &str, edge_len: u32, overlap_len: u32, identity: f64) {
-        if self.edges.iter().any(|e| e.target_id == target_node) {
-            // multiple edges to the same target
-            // currently impossible due to the overlap filtering
-            // handling might change in the future to handle this case
-            // silently ignore duplicate edges but log for debugging
-            eprintln!(
-                "Warning: duplicate edge {} -> {} ignored",
-                self.node_id, target_node
-            );
-            return;
-        }
+    /// Add a directed edge to a node.
+    /// The edge is always appended, duplicate edges are removed by `drop_duplicate_edges` once the graph is built,
+    /// unless multi-edges are allowed.
+    fn add_edge(&mut self, target: NodeIndex, edge_len: u32, overlap_len: u32, identity: f64) {
         self.edges.push(EdgeInfo {
-            target_id: target_node.to_owned(),
+            target,
             edge_len,
             overlap_len,
             identity,
         });
     }
 
-    /// Remove a directed edge to the node with target_node id
-    pub fn remove_edge(&mut self, target_node: &str) {
-        if let Some(pos) = self.edges.iter().position(|e| e.target_id == target_node) {
+    /// Keep the first edge to every target and drop the later ones, returns the dropped targets.
+    /// A set of the targets seen so far keeps this linear in the degree.
+    fn drop_duplicate_edges(&mut self) -> Vec<NodeIndex> {
+        let keep = first_occurrences(self.edges.iter().map(|e| e.target));
+        let mut dropped: Vec<NodeIndex> = Vec::new();
+        let mut keep = keep.into_iter();
+        self.edges.retain(|e| {
+            let kept = keep.next().unwrap_or(true);
+            if !kept {
+                dropped.push(e.target);
+            }
+            kept
+        });
+        dropped
+    }
+
+    /// Keep the first incoming edge entry of every source, the counterpart of drop_duplicate_edges
+    fn drop_duplicate_in_edges(&mut self) {
+        let mut keep = first_occurrences(self.in_edges.iter().copied()).into_iter();
+        self.in_edges.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    /// Remove a directed edge to the target node, returns true if an edge was removed
+    fn remove_edge(&mut self, target: NodeIndex) -> bool {
+        if let Some(pos) = self.edges.iter().position(|e| e.target == target) {
             self.edges.swap_remove(pos);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove one incoming edge entry from source
+    fn remove_in_edge(&mut self, source: NodeIndex) {
+        if let Some(pos) = self.in_edges.iter().position(|&s| s == source) {
+            self.in_edges.swap_remove(pos);
         }
     }
 
+    /// Source nodes of the incoming edges, one entry per edge
+    pub fn in_edges(&self) -> &[NodeIndex] {
+        &self.in_edges
+    }
+
     /// Sort edges by length (ascending).
     pub fn sort_edges(&mut self) {
         self.edges.sort_unstable_by_key(|e| e.edge_len);
     }
 }
 
-/// Overlap graph containing nodes keyed by their node id
+/// For every item, true if it is the first occurrence of its value
+fn first_occurrences(items: impl Iterator<Item = NodeIndex>) -> Vec<bool> {
+    let mut seen: HashSet<NodeIndex> = HashSet::new();
+    items.map(|item| seen.insert(item)).collect()
+}
+
+/// Overlap graph with the nodes stored in a Vec indexed by NodeIndex.
+/// Node ids like "read123+" are only used for input, output and logging, `index` and `node_id` translate
+/// between the two. Reads are sorted by name, so ascending indices visit the nodes sorted by read name and
+/// then orientation ('+' first).
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OverlapGraph {
-    pub nodes: HashMap<String, Node>,
+    // sorted read names, read i has the node indices 2 * i and 2 * i + 1
+    read_names: Vec<String>,
+    // read name to read index
+    read_index: HashMap<String, u32>,
+    // node per index, None if the node is not in the graph (removed, or that orientation was never added)
+    nodes: Vec<Option<Node>>,
+    // number of nodes in the graph
+    node_count: usize,
+    // store multiple edges between the same pair of nodes instead of ignoring duplicates
+    pub allow_multi_edges: bool,
 }
 
 impl OverlapGraph {
-    /// Create a new empty overlap graph
-    fn new() -> Self {
+    /// Create a graph without nodes for the given reads, duplicate names are ignored
+    fn with_reads<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut read_names: Vec<String> = names.into_iter().map(str::to_string).collect();
+        read_names.sort_unstable();
+        read_names.dedup();
+        let read_index: HashMap<String, u32> = read_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i as u32))
+            .collect();
         Self {
-            nodes: HashMap::new(),
+            nodes: (0..2 * read_names.len()).map(|_| None).collect(),
+            read_names,
+            read_index,
+            node_count: 0,
+            allow_multi_edges: false,
         }
     }
 
-    /// Add a node to the graph if it does not already exist, if it already exists do nothing
-    fn add_node(&mut self, node_id: String) {
+    /// Serialize the graph to a binary file, to resume the pipeline from it later on
+    pub fn serialize_graph<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    /// Deserialize a graph written by serialize_graph
+    pub fn deserialize_graph<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(bincode::deserialize_from(reader)?)
+    }
+
+    /// Index of a node id like "read123+", whether or not the node is in the graph.
+    /// None for unknown reads and ids without an orientation suffix.
+    fn slot(&self, node_id: &str) -> Option<NodeIndex> {
+        let (name, orientation) = split_node_id(node_id)?;
+        let read = self.read_index.get(name)?;
+        Some(2 * read + NodeIndex::from(orientation == '-'))
+    }
+
+    /// Index of the node with id `node_id` ("read123+"), None if the node is not in the graph
+    pub fn index(&self, node_id: &str) -> Option<NodeIndex> {
+        self.slot(node_id).filter(|&idx| self.contains(idx))
+    }
+
+    /// Node id of an index, e.g. "read123+", the inverse of `index`
+    pub fn node_id(&self, idx: NodeIndex) -> String {
+        let orientation = if idx & 1 == 0 { '+' } else { '-' };
+        format!("{}{}", self.read_name(idx), orientation)
+    }
+
+    /// Name of the read of a node, without the orientation
+    pub fn read_name(&self, idx: NodeIndex) -> &str {
+        &self.read_names[(idx >> 1) as usize]
+    }
+
+    /// True if the node is in the graph
+    pub fn contains(&self, idx: NodeIndex) -> bool {
+        self.node(idx).is_some()
+    }
+
+    /// The node with index `idx`, None if it is not in the graph
+    pub fn node(&self, idx: NodeIndex) -> Option<&Node> {
+        self.nodes.get(idx as usize)?.as_ref()
+    }
+
+    /// Mutable access to the node with index `idx`.
+    /// Edges must be changed through the graph methods, they keep the incoming edges up to date.
+    pub fn node_mut(&mut self, idx: NodeIndex) -> Option<&mut Node> {
+        self.nodes.get_mut(idx as usize)?.as_mut()
+    }
+
+    /// Number of nodes in the graph
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Number of edges in the graph
+    pub fn edge_count(&self) -> usize {
+        self.nodes().map(|(_, n)| n.edges.len()).sum()
+    }
+
+    /// Upper bound of the node indices, for tables indexed by NodeIndex.
+    /// Both orientations of every read are counted, whether or not they are in the graph.
+    pub fn index_bound(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Indices of the nodes in the graph, in sorted node id order
+    pub fn node_indices(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.nodes().map(|(idx, _)| idx)
+    }
+
+    /// Nodes in the graph with their index, in sorted node id order
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeIndex, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, n)| Some((idx as NodeIndex, n.as_ref()?)))
+    }
+
+    /// Mutable nodes with their index, in sorted node id order
+    pub fn nodes_mut(&mut self) -> impl Iterator<Item = (NodeIndex, &mut Node)> {
         self.nodes
-            .entry(node_id.clone())
-            .or_insert_with(|| Node::new(node_id));
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, n)| Some((idx as NodeIndex, n.as_mut()?)))
     }
 
-    /// Add a directed edge from from_id to to_id with given edge length and metrics
+    /// Add a node to the graph if it does not already exist, if it already exists do nothing
+    fn add_node(&mut self, idx: NodeIndex) {
+        let slot = &mut self.nodes[idx as usize];
+        if slot.is_none() {
+            *slot = Some(Node::new());
+            self.node_count += 1;
+        }
+    }
+
+    /// Add a directed edge from `from` to `to` with given edge length and metrics
     fn add_edge(
         &mut self,
-        from_id: &str,
-        to_id: &str,
+        from: NodeIndex,
+        to: NodeIndex,
         edge_len: u32,
         overlap_len: u32,
         identity: f64,
     ) {
+        debug_assert!(
+            (0.0..=100.0).contains(&identity),
+            "edge {} -> {} has identity {}, expected a percentage",
+            self.node_id(from),
+            self.node_id(to),
+            identity
+        );
         // ensure nodes exist
-        if !self.nodes.contains_key(from_id) || !self.nodes.contains_key(to_id) {
+        if !self.contains(from) || !self.contains(to) {
             panic!("add_edge: nodes must exist before adding edge");
         }
-        if let Some(node) = self.nodes.get_mut(from_id) {
-            node.add_edge(to_id, edge_len, overlap_len, identity);
+        if let Some(node) = self.node_mut(from) {
+            node.add_edge(to, edge_len, overlap_len, identity);
+        }
+        if let Some(target) = self.node_mut(to) {
+            target.in_edges.push(from);
         }
     }
 
+    /// Drop parallel edges, only the first edge between two nodes is kept. Returns the number of dropped edges.
+    /// Parallel edges come from several overlaps between the same reads, they are only kept in multi-edge mode.
+    /// The incoming edges are deduplicated per node, removing them one by one is quadratic for high degree nodes.
+    fn drop_duplicate_edges(&mut self) -> usize {
+        let mut dropped = 0usize;
+        for idx in self.sorted_node_indices() {
+            let Some(targets) = self.node_mut(idx).map(Node::drop_duplicate_edges) else {
+                continue;
+            };
+            for target in targets {
+                trace!(
+                    "duplicate edge {} -> {} ignored",
+                    self.node_id(idx),
+                    self.node_id(target)
+                );
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            for (_, node) in self.nodes_mut() {
+                node.drop_duplicate_in_edges();
+            }
+        }
+        dropped
+    }
+
+    /// Remove one directed edge from `from` to `to`, returns true if an edge was removed
+    pub fn remove_edge(&mut self, from: NodeIndex, to: NodeIndex) -> bool {
+        let removed = self.node_mut(from).is_some_and(|node| node.remove_edge(to));
+        if removed {
+            if let Some(target) = self.node_mut(to) {
+                target.remove_in_edge(from);
+            }
+        }
+        removed
+    }
+
+    /// Remove the edge from -> to together with its reverse complement counterpart rc(to) -> rc(from),
+    /// so the bigraph stays synchronized. Returns true if the from -> to edge was removed.
+    pub fn remove_edge_symmetric(&mut self, from: NodeIndex, to: NodeIndex) -> bool {
+        let removed = self.remove_edge(from, to);
+        // an edge from a node to its own reverse complement is its own counterpart
+        if rc_index(to) != from {
+            self.remove_edge(rc_index(to), rc_index(from));
+        }
+        removed
+    }
+
+    /// Replace the outgoing edges of a node, incoming edge bookkeeping of the targets is updated
+    pub fn set_edges(&mut self, idx: NodeIndex, edges: Vec<EdgeInfo>) {
+        let Some(node) = self.node_mut(idx) else {
+            return;
+        };
+        let old_edges = std::mem::replace(&mut node.edges, edges);
+        let new_targets: Vec<NodeIndex> = node.edges.iter().map(|e| e.target).collect();
+        for e in old_edges {
+            if let Some(target) = self.node_mut(e.target) {
+                target.remove_in_edge(idx);
+            }
+        }
+        for t in new_targets {
+            if let Some(target) = self.node_mut(t) {
+                target.in_edges.push(idx);
+            }
+        }
+    }
+
+    /// Remove a node together with its outgoing and incoming edges
+    pub fn remove_node(&mut self, idx: NodeIndex) -> Option<Node> {
+        let node = self.nodes.get_mut(idx as usize)?.take()?;
+        self.node_count -= 1;
+        for e in &node.edges {
+            if let Some(target) = self.node_mut(e.target) {
+                target.remove_in_edge(idx);
+            }
+        }
+        for &source in &node.in_edges {
+            if let Some(source_node) = self.node_mut(source) {
+                source_node.edges.retain(|e| e.target != idx);
+            }
+        }
+        Some(node)
+    }
+
+    /// Snapshot of the node indices in sorted node id order.
+    /// Passes that mutate the graph iterate over it, so runs on the same input give the same result.
+    pub fn sorted_node_indices(&self) -> Vec<NodeIndex> {
+        self.node_indices().collect()
+    }
+
+    /// Node ids in sorted order: by read name, then orientation
+    pub fn sorted_node_ids(&self) -> Vec<String> {
+        self.node_indices().map(|idx| self.node_id(idx)).collect()
+    }
+
+    /// Remove a node and its reverse complement, each together with its outgoing and incoming edges,
+    /// so no edge is left pointing to either orientation. Returns the number of removed nodes.
+    pub fn remove_node_symmetric(&mut self, idx: NodeIndex) -> usize {
+        usize::from(self.remove_node(idx).is_some())
+            + usize::from(self.remove_node(rc_index(idx)).is_some())
+    }
+
+    /// Number of incoming edges of a node, 0 if the node does not exist
+    pub fn indegree(&self, idx: NodeIndex) -> usize {
+        self.node(idx).map_or(0, |n| n.in_edges.len())
+    }
+
     /// Write the overlap graph to a DOT file for visualization
     pub fn write_dot<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
         // helper functions
@@ -124,39 +420,29 @@ impl OverlapGraph {
         let file = File::create(path)?;
         let mut w = BufWriter::new(file);
 
-        // gather stats
-        let mut outdegrees: HashMap<String, usize> = HashMap::new();
-        let mut indegrees: HashMap<String, usize> = HashMap::new();
-        for node in self.nodes.values() {
-            *outdegrees.entry(node.node_id.clone()).or_insert(0) += node.edges.len();
-            for e in &node.edges {
-                *indegrees.entry(e.target_id.clone()).or_insert(0) += 1;
-            }
-        }
-
         writeln!(w, "digraph OverlapGraph {{")?;
         writeln!(w, "  rankdir=LR;")?;
         writeln!(w, "  node [shape=box fontname=\"Helvetica\"];")?;
         writeln!(w, "  edge [fontname=\"Helvetica\"];")?;
         writeln!(w)?;
 
-        // Emit nodes explicitly (optional but useful for styling later)
-        for node_id in self.nodes.keys() {
+        // Emit nodes explicitly (optional but useful for styling later), in sorted order so the output is reproducible
+        for (idx, node) in self.nodes() {
             writeln!(
                 w,
                 "  \"{}\" [style=filled fillcolor={} ];",
-                escape_dot(node_id),
-                degree_color(*outdegrees.get(node_id).unwrap_or(&0))
+                escape_dot(&self.node_id(idx)),
+                degree_color(node.edges.len())
             )?;
         }
 
         writeln!(w)?;
 
         // Emit edges
-        for node in self.nodes.values() {
-            let from = escape_dot(&node.node_id);
+        for (idx, node) in self.nodes() {
+            let from = escape_dot(&self.node_id(idx));
             for e in &node.edges {
-                let to = escape_dot(&e.target_id);
+                let to = escape_dot(&self.node_id(e.target));
                 writeln!(
                     w,
                     "  \"{}\" -> \"{}\";",
@@ -170,63 +456,398 @@ impl OverlapGraph {
         writeln!(w, "}}")?;
         Ok(())
     }
-}
 
-pub struct AlignmentFilteringOutput {
-    pub overlaps: HashMap<(usize, usize), Overlap>,
-}
+    /// Write the graph at read level in GFA1 format, with one segment per oriented node ("read+" and "read-").
+    /// All links join segments in '+' orientation and carry the edge length (el:i), overlap length (ol:i)
+    /// and identity (id:f). Segment lengths (LN:i) come from the read sequences, if available.
+    pub fn write_raw_gfa<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        fastq_seqs: &HashMap<String, String>,
+    ) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        let mut w = BufWriter::new(file);
 
-impl AlignmentFilteringOutput {
-    // serialize the overlaps
-    pub fn serialize_overlaps(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(w, "H\tVN:Z:1.0")?;
+        for idx in self.node_indices() {
+            match fastq_seqs.get(self.read_name(idx)) {
+                Some(seq) => writeln!(w, "S\t{}\t*\tLN:i:{}", self.node_id(idx), seq.len())?,
+                None => writeln!(w, "S\t{}\t*", self.node_id(idx))?,
+            }
+        }
+        for (idx, node) in self.nodes() {
+            let from = self.node_id(idx);
+            for e in &node.edges {
+                writeln!(
+                    w,
+                    "L\t{}\t+\t{}\t+\t{}M\tel:i:{}\tol:i:{}\tid:f:{:.3}",
+                    from,
+                    self.node_id(e.target),
+                    e.overlap_len,
+                    e.edge_len,
+                    e.overlap_len,
+                    e.identity
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the graph in GFA1 format, readable by read_gfa.
+    /// Every read becomes a segment without sequence, its length (LN:i) is taken from an edge that leaves it.
+    /// An edge and its reverse complement counterpart describe the same link, only one of them is written.
+    pub fn write_gfa<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
         let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, &self.overlaps)?;
+        let mut w = BufWriter::new(file);
 
+        writeln!(w, "H\tVN:Z:1.0")?;
+        // read lengths, edge length plus overlap length of any edge leaving either orientation
+        for read in 0..self.read_names.len() as NodeIndex {
+            let orientations = [self.node(2 * read), self.node(2 * read + 1)];
+            if orientations.iter().all(Option::is_none) {
+                continue;
+            }
+            let len = orientations
+                .iter()
+                .flatten()
+                .find_map(|n| n.edges.first())
+                .map(|e| e.edge_len + e.overlap_len);
+            let name = self.read_name(2 * read);
+            match len {
+                Some(len) => writeln!(w, "S\t{}\t*\tLN:i:{}", name, len)?,
+                None => writeln!(w, "S\t{}\t*", name)?,
+            }
+        }
+        let orientation = |idx: NodeIndex| if idx & 1 == 0 { '+' } else { '-' };
+        for (idx, node) in self.nodes() {
+            for e in &node.edges {
+                // keep the edge if it sorts before its reverse complement counterpart
+                if (rc_index(e.target), rc_index(idx)) < (idx, e.target) {
+                    continue;
+                }
+                writeln!(
+                    w,
+                    "L\t{}\t{}\t{}\t{}\t{}M",
+                    self.read_name(idx),
+                    orientation(idx),
+                    self.read_name(e.target),
+                    orientation(e.target),
+                    e.overlap_len
+                )?;
+            }
+        }
         Ok(())
     }
 }
 
-/// Build overlap graph from overlaps
-pub fn run_create_overlap_graph(
-    overlaps: HashMap<(usize, usize), Overlap>,
-) -> Result<OverlapGraph, io::Error> {
-    println!("=== OVERLAP GRAPH CREATION ===");
-    let mut g = OverlapGraph::new();
-
-    for ((_query_id, _target_id), o) in overlaps.iter() {
-        // add overlap to the graph
+/// Build an overlap graph from a list of overlaps.
+/// Every overlap adds its edge and the reverse complement counterpart, so the graph stays synchronized.
+/// Usable for overlaps that did not come from a PAF file, e.g. constructed by a library user.
+/// With `allow_multi_edges`, several overlaps between the same reads become parallel edges.
+/// Overlaps with a node id without orientation suffix are skipped.
+///
+/// ```
+/// use ilesta::Overlap;
+/// use ilesta::create_overlap_graph::build_overlap_graph_from;
+///
+/// // a+ -> b+ -> c+, every read is 1000 bp and overlaps the next one by 600 bp
+/// let overlap = |source: &str, sink: &str| Overlap {
+///     source_name: format!("{}+", source),
+///     sink_name: format!("{}+", sink),
+///     rc_source_name: format!("{}-", sink),
+///     rc_sink_name: format!("{}-", source),
+///     edge_len: 400,
+///     rc_edge_len: 400,
+///     overlap_len: 600,
+///     identity: 99.0,
+/// };
+/// let graph = build_overlap_graph_from(&[overlap("a", "b"), overlap("b", "c")], false);
+///
+/// // both orientations of the three reads, every overlap adds its reverse complement edge
+/// assert_eq!(graph.node_count(), 6);
+/// assert_eq!(graph.edge_count(), 4);
+/// assert_eq!(graph.node_id(graph.node(graph.index("b-").unwrap()).unwrap().edges[0].target), "a-");
+/// ```
+pub fn build_overlap_graph_from(overlaps: &[Overlap], allow_multi_edges: bool) -> OverlapGraph {
+    build_graph(overlaps, allow_multi_edges, &[])
+}
 
-        // original orientation
-        g.add_node(o.source_name.clone());
-        g.add_node(o.sink_name.clone());
-        g.add_edge(
+/// Build the graph of `overlaps`, the `extra_reads` get both orientations as nodes even without any overlap
+fn build_graph(
+    overlaps: &[Overlap],
+    allow_multi_edges: bool,
+    extra_reads: &[&str],
+) -> OverlapGraph {
+    // all reads are known up front, so they get their indices in sorted order
+    let overlap_reads = overlaps.iter().flat_map(|o| {
+        [
             &o.source_name,
             &o.sink_name,
-            o.edge_len,
-            o.overlap_len,
-            o.identity,
+            &o.rc_source_name,
+            &o.rc_sink_name,
+        ]
+        .into_iter()
+        .filter_map(|id| split_node_id(id).map(|(name, _)| name))
+    });
+    let mut g = OverlapGraph::with_reads(overlap_reads.chain(extra_reads.iter().copied()));
+    g.allow_multi_edges = allow_multi_edges;
+
+    for o in overlaps {
+        // add overlap to the graph
+        let nodes = (
+            g.slot(&o.source_name),
+            g.slot(&o.sink_name),
+            g.slot(&o.rc_source_name),
+            g.slot(&o.rc_sink_name),
         );
+        let (Some(source), Some(sink), Some(rc_source), Some(rc_sink)) = nodes else {
+            warn!(
+                "overlap {} -> {} has a node id without orientation, skipped",
+                o.source_name, o.sink_name
+            );
+            continue;
+        };
+
+        // original orientation
+        g.add_node(source);
+        g.add_node(sink);
+        g.add_edge(source, sink, o.edge_len, o.overlap_len, o.identity);
 
         // reverse complement counterpart:
-        g.add_node(o.rc_source_name.clone());
-        g.add_node(o.rc_sink_name.clone());
-        g.add_edge(
-            &o.rc_source_name,
-            &o.rc_sink_name,
-            o.rc_edge_len,
-            o.overlap_len,
-            o.identity,
+        g.add_node(rc_source);
+        g.add_node(rc_sink);
+        g.add_edge(rc_source, rc_sink, o.rc_edge_len, o.overlap_len, o.identity);
+    }
+    for name in extra_reads {
+        if let Some(&read) = g.read_index.get(*name) {
+            g.add_node(2 * read);
+            g.add_node(2 * read + 1);
+        }
+    }
+    // several overlaps between the same reads are only possible when multiple alignments per pair are kept
+    if !allow_multi_edges {
+        g.drop_duplicate_edges();
+    }
+
+    g
+}
+
+/// Shorthand for an invalid GFA error
+fn invalid_gfa(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Length of an overlap CIGAR on the first segment of a link (M, =, X and D operations), "*" means unknown (0)
+fn cigar_overlap_len(cigar: &str) -> Option<u32> {
+    if cigar == "*" {
+        return Some(0);
+    }
+    let mut len = 0u32;
+    let mut num = 0u32;
+    for c in cigar.chars() {
+        if let Some(d) = c.to_digit(10) {
+            num = num.checked_mul(10)?.checked_add(d)?;
+        } else {
+            if matches!(c, 'M' | '=' | 'X' | 'D') {
+                len = len.checked_add(num)?;
+            } else if !matches!(c, 'I' | 'S' | 'H' | 'N' | 'P') {
+                return None;
+            }
+            num = 0;
+        }
+    }
+    Some(len)
+}
+
+/// Read an assembly graph in GFA1 format, e.g. written by miniasm or gfatools.
+/// Segments become reads, both orientations of every segment are added as nodes.
+/// A link "L a + b - 500M" becomes the edge a+ -> b- and its reverse complement counterpart b+ -> a-,
+/// the edge length is the segment length minus the overlap. Segment lengths come from the sequence or the LN:i tag.
+/// GFA has no identity, so every edge gets an identity of 100. Links with an overlap that is not shorter than
+/// the segment are skipped.
+pub fn read_gfa(path: &str) -> io::Result<OverlapGraph> {
+    let reader = crate::utils::open_reader(path)?;
+    let mut segment_lengths: HashMap<String, u32> = HashMap::new();
+    let mut links: Vec<(String, char, String, char, u32)> = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.trim_end().split('\t').collect();
+        match fields[0] {
+            "S" => {
+                if fields.len() < 3 {
+                    return Err(invalid_gfa(format!(
+                        "truncated S line in '{}': {}",
+                        path, line
+                    )));
+                }
+                let len = if fields[2] != "*" {
+                    Some(fields[2].len() as u32)
+                } else {
+                    fields[3..]
+                        .iter()
+                        .find_map(|t| t.strip_prefix("LN:i:"))
+                        .and_then(|l| l.parse().ok())
+                };
+                let len = len.ok_or_else(|| {
+                    invalid_gfa(format!(
+                        "segment '{}' has no sequence or LN:i tag",
+                        fields[1]
+                    ))
+                })?;
+                segment_lengths.insert(fields[1].to_string(), len);
+            }
+            "L" => {
+                if fields.len() < 6 {
+                    return Err(invalid_gfa(format!(
+                        "truncated L line in '{}': {}",
+                        path, line
+                    )));
+                }
+                let orientation = |o: &str| match o {
+                    "+" => Ok('+'),
+                    "-" => Ok('-'),
+                    _ => Err(invalid_gfa(format!("invalid link orientation '{}'", o))),
+                };
+                let overlap_len = cigar_overlap_len(fields[5])
+                    .ok_or_else(|| invalid_gfa(format!("invalid overlap CIGAR '{}'", fields[5])))?;
+                links.push((
+                    fields[1].to_string(),
+                    orientation(fields[2])?,
+                    fields[3].to_string(),
+                    orientation(fields[4])?,
+                    overlap_len,
+                ));
+            }
+            // header, paths and other records are not needed
+            _ => {}
+        }
+    }
+
+    let flip = |o: char| if o == '+' { '-' } else { '+' };
+    let mut overlaps: Vec<Overlap> = Vec::with_capacity(links.len());
+    let mut skipped = 0usize;
+    for (from, from_orient, to, to_orient, overlap_len) in links {
+        let (Some(&from_len), Some(&to_len)) =
+            (segment_lengths.get(&from), segment_lengths.get(&to))
+        else {
+            return Err(invalid_gfa(format!(
+                "link {} -> {} references an unknown segment",
+                from, to
+            )));
+        };
+        if overlap_len >= from_len || overlap_len >= to_len {
+            skipped += 1;
+            continue;
+        }
+        overlaps.push(Overlap {
+            source_name: format!("{}{}", from, from_orient),
+            sink_name: format!("{}{}", to, to_orient),
+            rc_source_name: format!("{}{}", to, flip(to_orient)),
+            rc_sink_name: format!("{}{}", from, flip(from_orient)),
+            edge_len: from_len - overlap_len,
+            rc_edge_len: to_len - overlap_len,
+            overlap_len,
+            identity: 100.0,
+        });
+    }
+    if skipped > 0 {
+        warn!(
+            "Skipped {} GFA links with an overlap not shorter than their segments",
+            skipped
+        );
+    }
+
+    // every segment gets both orientations, also without links
+    let segments: Vec<&str> = segment_lengths.keys().map(String::as_str).collect();
+    Ok(build_graph(&overlaps, false, &segments))
+}
+
+/// Build overlap graph from the output of the alignment filtering
+/// Overlaps with an identity below `min_edge_identity` (percent) are skipped before any node is added,
+/// so the edge and its reverse complement are dropped together and no isolated nodes are created.
+/// Fails if no overlap is left, an empty graph has nothing to assemble.
+pub fn run_create_overlap_graph(
+    filtering: AlignmentFilteringOutput,
+    min_edge_identity: f64,
+) -> Result<OverlapGraph, Box<dyn std::error::Error>> {
+    info!("=== OVERLAP GRAPH CREATION ===");
+    let repeat_reads = filtering.repeat_reads();
+    // sort by read pair, so the edge order of every node doesn't depend on hash map iteration order
+    let mut pairs: Vec<((usize, usize), Vec<Overlap>)> = filtering.overlaps.into_iter().collect();
+    pairs.sort_unstable_by_key(|(ids, _)| *ids);
+    let mut overlaps: Vec<Overlap> = pairs.into_iter().flat_map(|(_, ovs)| ovs).collect();
+
+    // minimum identity filter
+    let overlap_count = overlaps.len();
+    overlaps.retain(|o| o.identity >= min_edge_identity);
+    let low_identity_overlaps = overlap_count - overlaps.len();
+    if low_identity_overlaps > 0 {
+        info!(
+            "Overlaps skipped due to minimum edge identity ({}%): {} ({} edges)",
+            min_edge_identity,
+            low_identity_overlaps,
+            2 * low_identity_overlaps
+        );
+    }
+
+    if overlaps.is_empty() {
+        return Err(
+            "no overlaps survived filtering; try lowering --min-overlap-length or --min-percent-identity"
+                .into(),
         );
     }
 
+    // the orientation bookkeeping of the overlaps is fiddly, report mistakes before they reach the graph
+    let problems = verify_overlap_orientations(&overlaps);
+    if !problems.is_empty() {
+        warn!(
+            "{} overlaps have inconsistent orientations, first: {}",
+            problems.len(),
+            problems
+                .iter()
+                .take(5)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+    }
+
+    let mut g = build_overlap_graph_from(&overlaps, filtering.multi_alignments);
+
+    // annotate both orientations of repeat-containing reads
+    for read_name in repeat_reads {
+        for orientation in ['+', '-'] {
+            if let Some(idx) = g.index(&format!("{}{}", read_name, orientation)) {
+                if let Some(node) = g.node_mut(idx) {
+                    node.is_repeat = true;
+                }
+            }
+        }
+    }
+
+    // both orientations of a read share its coverage
+    let coverages: Vec<(NodeIndex, f64)> = g
+        .node_indices()
+        .filter_map(|idx| Some((idx, *filtering.read_coverage.get(g.read_name(idx))?)))
+        .collect();
+    for (idx, coverage) in coverages {
+        if let Some(node) = g.node_mut(idx) {
+            node.coverage = coverage;
+        }
+    }
+
     // graph stats
-    let edge_count: usize = g.nodes.values().map(|n| n.edges.len()).sum();
-    let node_count = g.nodes.len();
+    let edge_count = g.edge_count();
+    let node_count = g.node_count();
     let node_to_edge_ratio = node_count as f64 / edge_count as f64;
-    println!("Graph nodes: {}", node_count);
-    println!("Graph edges: {}", edge_count);
-    println!("Node to edge ratio: {:.4}", node_to_edge_ratio);
-    println!("=== OVERLAP GRAPH CREATION FINISHED ===");
-    return Ok(g);
+    info!("Graph nodes: {}", node_count);
+    info!("Graph edges: {}", edge_count);
+    info!("Node to edge ratio: {:.4}", node_to_edge_ratio);
+    info!(
+        "Repeat-containing nodes: {}",
+        g.nodes().filter(|(_, n)| n.is_repeat).count()
+    );
+    info!("=== OVERLAP GRAPH CREATION FINISHED ===");
+    Ok(g)
 }
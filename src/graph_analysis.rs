@@ -1,97 +1,186 @@
-use crate::create_overlap_graph::OverlapGraph;
-use crate::utils;
+use crate::create_overlap_graph::{EdgeInfo, NodeIndex, OverlapGraph, rc_index};
+use crate::heuristic_simplification;
+use log::{info, warn};
 use std::collections::{HashMap, HashSet};
+use std::io::Write;
 
-/// Check if the bigraph is synchronized:
-/// 1. Every node has a reverse complement.
-/// 2. Ingoing edges of every node correspond to outgoing edges of its reverse complement.
-pub fn check_synchronization(g: &OverlapGraph) {
-    for n in g.nodes.keys() {
+/// Collect all synchronization violations of the bigraph, in node id order
+fn synchronization_violations(g: &OverlapGraph) -> Vec<String> {
+    let mut violations: Vec<String> = Vec::new();
+
+    for (n, node) in g.nodes() {
         // compute reverse complement node
-        let n_rc = utils::rc_node(n);
+        let n_rc = rc_index(n);
 
         // check that the reverse complement exists
-        assert!(
-            g.nodes.contains_key(&n_rc),
-            "Reverse complement not found for {}. The bigraph is not synchronized.",
-            n
-        );
+        if !g.contains(n_rc) {
+            violations.push(format!("reverse complement not found for {}", g.node_id(n)));
+        }
 
         // check that every outgoing edge has a counterpart in the reverse complement node
-        if let Some(node) = g.nodes.get(n) {
-            for e in &node.edges {
-                let t = &e.target_id;
-                let t_rc = utils::rc_node(t);
-
-                // get the reverse complement node for t_rc
-                if let Some(t_rc_node) = g.nodes.get(&t_rc) {
-                    // Check if there's a matching edge from t_rc to n_rc
-                    assert!(
-                        t_rc_node.edges.iter().any(|e| e.target_id == n_rc),
-                        "Corresponding edge not found for {}. The bigraph is not synchronized.",
-                        n
-                    );
-                } else {
-                    panic!(
-                        "Reverse complement node {} missing for target {}. The bigraph is not synchronized.",
-                        t_rc, t
-                    );
+        for e in &node.edges {
+            let t_rc = rc_index(e.target);
+
+            // get the reverse complement node for t_rc
+            match g.node(t_rc) {
+                // Check if there's a matching edge from t_rc to n_rc
+                Some(t_rc_node) => {
+                    if !t_rc_node.edges.iter().any(|e| e.target == n_rc) {
+                        violations.push(format!(
+                            "corresponding edge {} -> {} not found for {} -> {}",
+                            g.node_id(t_rc),
+                            g.node_id(n_rc),
+                            g.node_id(n),
+                            g.node_id(e.target)
+                        ));
+                    }
                 }
+                None => violations.push(format!(
+                    "reverse complement node {} missing for target {}",
+                    g.node_id(t_rc),
+                    g.node_id(e.target)
+                )),
             }
         }
     }
+
+    violations
 }
 
-/// Find weakly connected components ("clustered reads") of the graph.
-pub fn weakly_connected_components(graph: &OverlapGraph) -> Vec<Vec<String>> {
-    // Build an undirected adjacency list
-    // Because it is undirected, we can move through both incoming and outgoing edges, meaning we can reach all nodes in a component.
-    let mut adjacency_list: HashMap<String, Vec<String>> = HashMap::new();
+/// Check if the bigraph is synchronized:
+/// 1. Every node has a reverse complement.
+/// 2. Ingoing edges of every node correspond to outgoing edges of its reverse complement.
+///
+/// Returns an error listing the first few violations.
+pub fn check_synchronization(g: &OverlapGraph) -> Result<(), String> {
+    const MAX_REPORTED: usize = 5;
+    let violations = synchronization_violations(g);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "The bigraph is not synchronized, {} violations: {}",
+        violations.len(),
+        violations
+            .iter()
+            .take(MAX_REPORTED)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("; ")
+    ))
+}
 
-    // ensure every node in graph.nodes has an entry
-    for node_id in graph.nodes.keys() {
-        adjacency_list.entry(node_id.clone()).or_default();
+/// Check the bigraph synchronization and repair it instead of failing.
+/// Violations are logged, edges without a reverse complement counterpart are dropped
+/// by `heuristic_simplification::symmetrize_graph`. Returns the number of repaired edges.
+pub fn ensure_synchronized(graph: &mut OverlapGraph) -> usize {
+    if let Err(e) = check_synchronization(graph) {
+        warn!("{}", e);
+        let repaired = heuristic_simplification::symmetrize_graph(graph);
+        info!("Repaired synchronization by removing {} edges", repaired);
+        repaired
+    } else {
+        0
     }
+}
+
+/// Drop the edges whose overlap is longer than one of its reads, such an overlap can only come from bad input.
+/// Read lengths come from `fastq_seqs`, edges of reads without a sequence are kept. An edge and its reverse
+/// complement counterpart share the read pair and overlap, so both are dropped. Offending edges are logged.
+/// Returns the number of removed edges.
+pub fn remove_impossible_overlaps(
+    graph: &mut OverlapGraph,
+    fastq_seqs: &HashMap<String, String>,
+) -> usize {
+    let read_len = |idx: NodeIndex| fastq_seqs.get(graph.read_name(idx)).map(|seq| seq.len());
+    let mut edits: Vec<(NodeIndex, Vec<EdgeInfo>)> = Vec::new();
+    let mut removed = 0usize;
+    for (idx, node) in graph.nodes() {
+        let source_len = read_len(idx);
+        let (kept, dropped): (Vec<EdgeInfo>, Vec<EdgeInfo>) =
+            node.edges.iter().cloned().partition(|e| {
+                let shorter = match (source_len, read_len(e.target)) {
+                    (Some(a), Some(b)) => a.min(b),
+                    (Some(a), None) | (None, Some(a)) => a,
+                    (None, None) => return true,
+                };
+                e.overlap_len as usize <= shorter
+            });
+        if dropped.is_empty() {
+            continue;
+        }
+        for e in &dropped {
+            warn!(
+                "edge {} -> {} has an overlap of {} bases, longer than one of its reads",
+                graph.node_id(idx),
+                graph.node_id(e.target),
+                e.overlap_len
+            );
+        }
+        removed += dropped.len();
+        edits.push((idx, kept));
+    }
+    for (idx, kept) in edits {
+        graph.set_edges(idx, kept);
+    }
+    removed
+}
+
+/// Undirected view of the graph: the neighbors of every node through both incoming and outgoing edges.
+/// Building it is the expensive part of the component queries, so callers that run several queries on an
+/// unchanged graph build it once with `build_undirected_adjacency` and pass it to the `*_in` functions.
+pub struct UndirectedAdjacency {
+    // true if the node index is in the graph (both orientations get an index, only one might exist)
+    present: Vec<bool>,
+    neighbors: Vec<Vec<NodeIndex>>,
+}
+
+/// Build the undirected adjacency list of the graph
+pub fn build_undirected_adjacency(graph: &OverlapGraph) -> UndirectedAdjacency {
+    // Because it is undirected, we can move through both incoming and outgoing edges, meaning we can reach all nodes in a component.
+    let mut present: Vec<bool> = vec![false; graph.index_bound()];
+    let mut neighbors: Vec<Vec<NodeIndex>> = vec![Vec::new(); graph.index_bound()];
 
     // Populate adjacency using outgoing edges (and add reverse edges to make undirected)
-    for (source_id, node) in &graph.nodes {
-        // Each edge is stored as EdgeInfo
+    for (source, node) in graph.nodes() {
+        present[source as usize] = true;
         for e in &node.edges {
-            let target_id = &e.target_id;
-            adjacency_list
-                .entry(source_id.clone())
-                .or_default()
-                .push(target_id.clone());
-            adjacency_list
-                .entry(target_id.clone())
-                .or_default()
-                .push(source_id.clone());
+            neighbors[source as usize].push(e.target);
+            neighbors[e.target as usize].push(source);
         }
     }
 
+    UndirectedAdjacency { present, neighbors }
+}
+
+/// Find weakly connected components ("clustered reads") of the graph.
+pub fn weakly_connected_components(graph: &OverlapGraph) -> Vec<Vec<NodeIndex>> {
+    weakly_connected_components_in(&build_undirected_adjacency(graph))
+}
+
+/// Weakly connected components from a prebuilt undirected adjacency
+pub fn weakly_connected_components_in(adjacency: &UndirectedAdjacency) -> Vec<Vec<NodeIndex>> {
     // Find components with DFS/stack
-    let mut visited: HashSet<String> = HashSet::new();
-    let mut components: Vec<Vec<String>> = Vec::new();
+    let mut visited: Vec<bool> = vec![false; adjacency.present.len()];
+    let mut components: Vec<Vec<NodeIndex>> = Vec::new();
 
-    for start in adjacency_list.keys() {
-        // check if already visited
-        if visited.contains(start) {
+    for start in 0..adjacency.present.len() {
+        // check if already visited, indices without a node are skipped
+        if visited[start] || !adjacency.present[start] {
             continue;
         }
 
         // new component
-        let mut component: Vec<String> = Vec::new();
-        let mut stack: Vec<String> = vec![start.clone()];
-        visited.insert(start.clone());
+        let mut component: Vec<NodeIndex> = Vec::new();
+        let mut stack: Vec<NodeIndex> = vec![start as NodeIndex];
+        visited[start] = true;
 
         while let Some(current) = stack.pop() {
-            component.push(current.clone());
-            if let Some(neighbors) = adjacency_list.get(&current) {
-                for neighbor in neighbors {
-                    if !visited.contains(neighbor) {
-                        visited.insert(neighbor.clone());
-                        stack.push(neighbor.clone());
-                    }
+            component.push(current);
+            for &neighbor in &adjacency.neighbors[current as usize] {
+                if !visited[neighbor as usize] {
+                    visited[neighbor as usize] = true;
+                    stack.push(neighbor);
                 }
             }
         }
@@ -102,9 +191,293 @@ pub fn weakly_connected_components(graph: &OverlapGraph) -> Vec<Vec<String>> {
     components
 }
 
+/// Difference between two versions of a graph, e.g. before and after a cleanup iteration.
+/// Nodes are compared by node id, the two graphs do not need to share node indices.
+/// Edges are compared as (from, to) pairs, parallel edges count once.
+pub struct GraphDiff {
+    pub removed_nodes: Vec<String>,
+    pub added_nodes: Vec<String>,
+    pub removed_edges: Vec<(String, String)>,
+    pub added_edges: Vec<(String, String)>,
+    pub components_before: usize,
+    pub components_after: usize,
+}
+
+impl GraphDiff {
+    /// Net change in the number of weakly connected components
+    pub fn component_change(&self) -> i64 {
+        self.components_after as i64 - self.components_before as i64
+    }
+
+    /// True if both graphs have the same nodes, edges and number of components
+    pub fn is_empty(&self) -> bool {
+        self.removed_nodes.is_empty()
+            && self.added_nodes.is_empty()
+            && self.removed_edges.is_empty()
+            && self.added_edges.is_empty()
+            && self.component_change() == 0
+    }
+}
+
+/// Set differences of the node ids and edges of `before` and `after`, sorted by node id
+pub fn graph_diff(before: &OverlapGraph, after: &OverlapGraph) -> GraphDiff {
+    fn edge_set(graph: &OverlapGraph) -> HashSet<(String, String)> {
+        graph
+            .nodes()
+            .flat_map(|(idx, node)| {
+                node.edges
+                    .iter()
+                    .map(move |e| (graph.node_id(idx), graph.node_id(e.target)))
+            })
+            .collect()
+    }
+    fn sorted_difference<T: Ord + Clone + std::hash::Hash>(
+        a: &HashSet<T>,
+        b: &HashSet<T>,
+    ) -> Vec<T> {
+        let mut diff: Vec<T> = a.difference(b).cloned().collect();
+        diff.sort_unstable();
+        diff
+    }
+
+    let nodes_before: HashSet<String> = before.sorted_node_ids().into_iter().collect();
+    let nodes_after: HashSet<String> = after.sorted_node_ids().into_iter().collect();
+    let edges_before = edge_set(before);
+    let edges_after = edge_set(after);
+
+    GraphDiff {
+        removed_nodes: sorted_difference(&nodes_before, &nodes_after),
+        added_nodes: sorted_difference(&nodes_after, &nodes_before),
+        removed_edges: sorted_difference(&edges_before, &edges_after),
+        added_edges: sorted_difference(&edges_after, &edges_before),
+        components_before: weakly_connected_components(before).len(),
+        components_after: weakly_connected_components(after).len(),
+    }
+}
+
+/// Find strongly connected components of the directed graph (iterative Tarjan).
+/// Unlike the weakly connected components, edge direction is respected, so components with more
+/// than one node contain a cycle. An explicit call stack is used because recursion depth on real
+/// graphs can overflow the stack.
+pub fn strongly_connected_components(graph: &OverlapGraph) -> Vec<Vec<String>> {
+    // adjacency list indexed by node index, indices without a node are never visited
+    let n = graph.index_bound();
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (idx, node) in graph.nodes() {
+        adjacency[idx as usize] = node.edges.iter().map(|e| e.target as usize).collect();
+    }
+
+    let mut index = vec![usize::MAX; n]; // discovery index, MAX = unvisited
+    let mut lowlink = vec![0usize; n];
+    let mut on_stack = vec![false; n];
+    let mut stack: Vec<usize> = Vec::new();
+    let mut next_index = 0usize;
+    let mut components: Vec<Vec<String>> = Vec::new();
+
+    // explicit call stack of (node, position of the next neighbor to visit)
+    let mut call_stack: Vec<(usize, usize)> = Vec::new();
+    for root in graph.node_indices().map(|idx| idx as usize) {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        call_stack.push((root, 0));
+
+        while let Some(&(v, pos)) = call_stack.last() {
+            // first visit of v
+            if pos == 0 && index[v] == usize::MAX {
+                index[v] = next_index;
+                lowlink[v] = next_index;
+                next_index += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+
+            if pos < adjacency[v].len() {
+                // visit the next neighbor
+                if let Some(top) = call_stack.last_mut() {
+                    top.1 += 1;
+                }
+                let w = adjacency[v][pos];
+                if index[w] == usize::MAX {
+                    call_stack.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+            } else {
+                // all neighbors done, return to the parent
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[v]);
+                }
+                // v is the root of a component
+                if lowlink[v] == index[v] {
+                    let mut component: Vec<String> = Vec::new();
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component.push(graph.node_id(w as NodeIndex));
+                        if w == v {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+    }
+
+    components
+}
+
+/// Render the subgraph induced by `nodes` as a DOT digraph (for debugging small regions).
+/// Only edges between requested nodes are emitted, labeled with their edge length and identity.
+pub fn to_dot(graph: &OverlapGraph, nodes: &HashSet<String>) -> String {
+    fn escape_dot(s: &str) -> String {
+        s.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    // sort for a stable output
+    let mut node_ids: Vec<NodeIndex> = nodes.iter().filter_map(|n| graph.index(n)).collect();
+    node_ids.sort_unstable();
+
+    let mut out = String::new();
+    out.push_str("digraph Subgraph {\n");
+    out.push_str("  rankdir=LR;\n");
+    out.push_str("  node [shape=box fontname=\"Helvetica\"];\n");
+    for &idx in &node_ids {
+        out.push_str(&format!("  \"{}\";\n", escape_dot(&graph.node_id(idx))));
+    }
+    for &idx in &node_ids {
+        for e in graph.node(idx).into_iter().flat_map(|n| &n.edges) {
+            let target_id = graph.node_id(e.target);
+            if !nodes.contains(&target_id) {
+                continue;
+            }
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\" [label=\"len={} id={:.3}\"];\n",
+                escape_dot(&graph.node_id(idx)),
+                escape_dot(&target_id),
+                e.edge_len,
+                e.identity
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Shape of a weakly connected component
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentKind {
+    // a simple path (in this orientation)
+    Linear,
+    // a simple cycle, every node has one incoming and one outgoing edge
+    Cyclic,
+    // at least one node with more than one incoming or outgoing edge
+    Branching,
+}
+
+/// Statistics of a single weakly connected component
+pub struct ComponentStat {
+    pub id: usize,
+    pub nodes: usize,
+    pub edges: usize,
+    pub branching_nodes: usize,
+    pub kind: ComponentKind,
+}
+
+/// Weakly connected components, largest (by node count) first.
+/// Component ids (in `component_report` and the backbone output) follow this order.
+pub fn components_largest_first(adjacency: &UndirectedAdjacency) -> Vec<Vec<NodeIndex>> {
+    let mut components = weakly_connected_components_in(adjacency);
+    components.sort_by_key(|c| std::cmp::Reverse(c.len()));
+    components
+}
+
+/// Ordered path of a component that is an unambiguous line: one start node without incoming edges,
+/// every other node with exactly one incoming edge and every node but the last with exactly one outgoing edge.
+/// Returns None for branching and cyclic components.
+pub fn linear_backbone(graph: &OverlapGraph, component: &[NodeIndex]) -> Option<Vec<String>> {
+    let mut start: Option<NodeIndex> = None;
+    for &idx in component {
+        let out_deg = graph.node(idx)?.edges.len();
+        let in_deg = graph.indegree(idx);
+        if in_deg > 1 || out_deg > 1 {
+            return None;
+        }
+        if in_deg == 0 {
+            // a second start means the component is not a single line
+            if start.is_some() {
+                return None;
+            }
+            start = Some(idx);
+        }
+    }
+
+    // walk from the start, the walk has to visit the whole component
+    let mut path: Vec<NodeIndex> = vec![start?];
+    while let Some(edge) = graph.node(*path.last()?)?.edges.first() {
+        if path.len() >= component.len() {
+            return None;
+        }
+        path.push(edge.target);
+    }
+    (path.len() == component.len()).then(|| path.iter().map(|&idx| graph.node_id(idx)).collect())
+}
+
+/// Write the linear backbones as a chain file: component id, number of nodes and the comma separated path
+pub fn write_backbones(
+    path: &std::path::Path,
+    backbones: &[(usize, Vec<String>)],
+) -> std::io::Result<()> {
+    let mut w = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(w, "component_id	nodes	path")?;
+    for (id, path) in backbones {
+        writeln!(w, "component_{}	{}	{}", id, path.len(), path.join(","))?;
+    }
+    Ok(())
+}
+
+/// Statistics of every weakly connected component of `graph`, the component id is the index in `components`.
+pub fn component_report(graph: &OverlapGraph, components: &[Vec<NodeIndex>]) -> Vec<ComponentStat> {
+    components
+        .iter()
+        .enumerate()
+        .map(|(id, component)| {
+            let mut edges = 0usize;
+            let mut branching_nodes = 0usize;
+            let mut all_simple = true;
+            for &idx in component {
+                let out_deg = graph.node(idx).map_or(0, |n| n.edges.len());
+                let in_deg = graph.indegree(idx);
+                edges += out_deg;
+                if in_deg > 1 || out_deg > 1 {
+                    branching_nodes += 1;
+                }
+                if in_deg != 1 || out_deg != 1 {
+                    all_simple = false;
+                }
+            }
+            let kind = if branching_nodes > 0 {
+                ComponentKind::Branching
+            } else if all_simple {
+                ComponentKind::Cyclic
+            } else {
+                ComponentKind::Linear
+            };
+            ComponentStat {
+                id,
+                nodes: component.len(),
+                edges,
+                branching_nodes,
+                kind,
+            }
+        })
+        .collect()
+}
+
 /// Convenience: return component sizes sorted descending
-pub fn component_sizes_sorted(graph: &OverlapGraph) -> Vec<usize> {
-    let mut sizes: Vec<usize> = weakly_connected_components(graph)
+pub fn component_sizes_sorted(adjacency: &UndirectedAdjacency) -> Vec<usize> {
+    let mut sizes: Vec<usize> = weakly_connected_components_in(adjacency)
         .into_iter()
         .map(|c| c.len())
         .collect();
@@ -117,18 +490,10 @@ pub fn analyze_degrees(graph: &OverlapGraph) -> (HashMap<usize, usize>, HashMap<
     let mut indegree_dist: HashMap<usize, usize> = HashMap::new();
     let mut outdegree_dist: HashMap<usize, usize> = HashMap::new();
 
-    // Count indegrees first
-    let mut indegrees: HashMap<String, usize> = HashMap::new();
-    for node in graph.nodes.values() {
-        for e in &node.edges {
-            *indegrees.entry(e.target_id.clone()).or_default() += 1;
-        }
-    }
-
-    // Now collect distributions
-    for node_id in graph.nodes.keys() {
-        let in_deg = indegrees.get(node_id).copied().unwrap_or(0);
-        let out_deg = graph.nodes.get(node_id).map(|n| n.edges.len()).unwrap_or(0);
+    // collect distributions
+    for (idx, node) in graph.nodes() {
+        let in_deg = graph.indegree(idx);
+        let out_deg = node.edges.len();
 
         *indegree_dist.entry(in_deg).or_default() += 1;
         *outdegree_dist.entry(out_deg).or_default() += 1;
@@ -139,20 +504,10 @@ pub fn analyze_degrees(graph: &OverlapGraph) -> (HashMap<usize, usize>, HashMap<
 
 /// Fraction of nodes that are compressible (in==1 && out==1) at the oriented-node level.
 pub fn compressible_node_stats(graph: &OverlapGraph) -> (usize, usize, f64) {
-    // compute indegrees
-    let mut indegrees: HashMap<String, usize> = HashMap::new();
-    for (src, node) in &graph.nodes {
-        for e in &node.edges {
-            *indegrees.entry(e.target_id.clone()).or_default() += 1;
-        }
-        // ensure src exists in map
-        indegrees.entry(src.clone()).or_default();
-    }
-
     let mut compressible = 0usize;
     let mut total = 0usize;
-    for (node_id, node) in &graph.nodes {
-        let in_deg = *indegrees.get(node_id).unwrap_or(&0);
+    for (idx, node) in graph.nodes() {
+        let in_deg = graph.indegree(idx);
         let out_deg = node.edges.len();
         total += 1;
         if in_deg == 1 && out_deg == 1 {
@@ -170,34 +525,25 @@ pub fn compressible_node_stats(graph: &OverlapGraph) -> (usize, usize, f64) {
 /// Find tips and measure tip-lengths (walk forward from nodes with indeg==0)
 /// max_walk limits how far we follow a chain (safety).
 pub fn tip_length_distribution(graph: &OverlapGraph, max_walk: usize) -> Vec<usize> {
-    // build indegrees first
-    let mut indegrees: HashMap<String, usize> = HashMap::new();
-    for (src, node) in &graph.nodes {
-        for e in &node.edges {
-            *indegrees.entry(e.target_id.clone()).or_default() += 1;
-        }
-        indegrees.entry(src.clone()).or_default();
-    }
-
     let mut lengths: Vec<usize> = Vec::new();
-    for (start, _) in &graph.nodes {
-        let in_deg = *indegrees.get(start).unwrap_or(&0);
+    for start in graph.node_indices() {
+        let in_deg = graph.indegree(start);
         if in_deg != 0 {
             continue; // not a tip start
         }
 
         // follow forward while nodes are linear (in==1 && out==1)
-        let mut cur = start.clone();
+        let mut cur = start;
         let mut len = 0usize;
         let mut steps = 0usize;
-        let mut visited_local: HashSet<String> = HashSet::new();
+        let mut visited_local: HashSet<NodeIndex> = HashSet::new();
         while steps < max_walk {
             if visited_local.contains(&cur) {
                 break;
             } // cycle safety
-            visited_local.insert(cur.clone());
+            visited_local.insert(cur);
 
-            let node = match graph.nodes.get(&cur) {
+            let node = match graph.node(cur) {
                 Some(n) => n,
                 None => break,
             };
@@ -210,11 +556,11 @@ pub fn tip_length_distribution(graph: &OverlapGraph, max_walk: usize) -> Vec<usi
             }
 
             // move to next node
-            let next = node.edges[0].target_id.clone();
+            let next = node.edges[0].target;
             len += 1;
             steps += 1;
 
-            let next_in = *indegrees.get(&next).unwrap_or(&0);
+            let next_in = graph.indegree(next);
             if next_in != 1 {
                 break;
             }
@@ -226,30 +572,270 @@ pub fn tip_length_distribution(graph: &OverlapGraph, max_walk: usize) -> Vec<usi
     lengths
 }
 
-/// Simple branching summary: return top-k nodes by (in_deg + out_deg)
-pub fn branching_summary(graph: &OverlapGraph, top_k: usize) -> Vec<(String, usize, usize)> {
-    // compute indegrees
-    let mut indegrees: HashMap<String, usize> = HashMap::new();
-    for (src, node) in &graph.nodes {
-        for e in &node.edges {
-            *indegrees.entry(e.target_id.clone()).or_default() += 1;
+/// Find tips and measure their lengths in base pairs (walk forward from nodes with indeg==0).
+/// The tip spells the non-overlapping prefix (edge length) of every tip node plus the full last read,
+/// so a single node tip has the length of its read. Tips whose last read is missing from `fastq_seqs` are skipped.
+pub fn tip_length_distribution_bp(
+    graph: &OverlapGraph,
+    fastq_seqs: &HashMap<String, String>,
+    max_walk: usize,
+) -> Vec<usize> {
+    let mut lengths: Vec<usize> = Vec::new();
+    for start in graph.node_indices() {
+        if graph.indegree(start) != 0 {
+            continue; // not a tip start
+        }
+
+        // follow forward while the next node is only reachable from the tip
+        let mut cur = start;
+        let mut prefix_len = 0usize;
+        let mut visited_local: HashSet<NodeIndex> = HashSet::new();
+        visited_local.insert(cur);
+        while visited_local.len() <= max_walk {
+            let Some(node) = graph.node(cur) else {
+                break;
+            };
+            if node.edges.len() != 1 {
+                break;
+            }
+            let edge = &node.edges[0];
+            // the next node is a junction (or cycles back), the tip ends at cur
+            if graph.indegree(edge.target) != 1 || visited_local.contains(&edge.target) {
+                break;
+            }
+            prefix_len += edge.edge_len as usize;
+            cur = edge.target;
+            visited_local.insert(cur);
+        }
+
+        if let Some(seq) = fastq_seqs.get(graph.read_name(cur)) {
+            lengths.push(prefix_len + seq.len());
+        }
+    }
+
+    lengths
+}
+
+/// Count lengths per bin, `bounds` are the ascending lower bounds of the bins and the last bin is open ended.
+/// Lengths below the first bound are not counted.
+pub fn length_histogram(lengths: &[usize], bounds: &[usize]) -> Vec<usize> {
+    let mut counts = vec![0usize; bounds.len()];
+    for &len in lengths {
+        if let Some(bin) = bounds.iter().rposition(|&b| len >= b) {
+            counts[bin] += 1;
+        }
+    }
+    counts
+}
+
+/// Flag likely collapsed repeats: nodes with indegree >= in_thresh and outdegree >= out_thresh.
+/// The reverse complement of a flagged node is flagged too, so masking them keeps the bigraph synchronized.
+pub fn classify_repeats(
+    graph: &OverlapGraph,
+    in_thresh: usize,
+    out_thresh: usize,
+) -> HashSet<String> {
+    let mut repeats: HashSet<String> = HashSet::new();
+    for (idx, node) in graph.nodes() {
+        if graph.indegree(idx) >= in_thresh && node.edges.len() >= out_thresh {
+            repeats.insert(graph.node_id(idx));
+            repeats.insert(graph.node_id(rc_index(idx)));
+        }
+    }
+    repeats
+}
+
+// neighbors of a node through incoming and outgoing edges, without the node itself
+fn undirected_neighbors(graph: &OverlapGraph, idx: NodeIndex) -> Vec<NodeIndex> {
+    let Some(node) = graph.node(idx) else {
+        return Vec::new();
+    };
+    let mut neighbors: Vec<NodeIndex> = node
+        .edges
+        .iter()
+        .map(|e| e.target)
+        .chain(node.in_edges().iter().copied())
+        .filter(|&n| n != idx)
+        .collect();
+    neighbors.sort_unstable();
+    neighbors.dedup();
+    neighbors
+}
+
+/// Articulation points (cut vertices) of a component in the undirected projection of the graph: removing one
+/// of them disconnects the component. Every inner node of a plain path is one, see `tangle_cut_points` for
+/// the ones that join separate contigs. Sorted by node id.
+pub fn find_articulation_points(graph: &OverlapGraph, component: &[NodeIndex]) -> Vec<String> {
+    articulation_points(graph, component)
+        .into_iter()
+        .map(|idx| graph.node_id(idx))
+        .collect()
+}
+
+/// Articulation points of a component as node indices, sorted by node index
+fn articulation_points(graph: &OverlapGraph, component: &[NodeIndex]) -> Vec<NodeIndex> {
+    let index: HashMap<NodeIndex, usize> = component
+        .iter()
+        .enumerate()
+        .map(|(i, &idx)| (idx, i))
+        .collect();
+    let neighbors: Vec<Vec<usize>> = component
+        .iter()
+        .map(|&idx| {
+            undirected_neighbors(graph, idx)
+                .into_iter()
+                .filter_map(|n| index.get(&n).copied())
+                .collect()
+        })
+        .collect();
+
+    // Tarjan's low-link values with an iterative DFS, frames are (node, parent, next neighbor)
+    const UNVISITED: usize = usize::MAX;
+    let mut discovery = vec![UNVISITED; component.len()];
+    let mut low = vec![0usize; component.len()];
+    let mut is_cut = vec![false; component.len()];
+    let mut time = 0usize;
+    for root in 0..component.len() {
+        if discovery[root] != UNVISITED {
+            continue;
+        }
+        discovery[root] = time;
+        low[root] = time;
+        time += 1;
+        let mut root_children = 0usize;
+        let mut stack: Vec<(usize, usize, usize)> = vec![(root, UNVISITED, 0)];
+        while let Some(frame) = stack.last_mut() {
+            let (v, parent) = (frame.0, frame.1);
+            if let Some(&u) = neighbors[v].get(frame.2) {
+                frame.2 += 1;
+                if u == parent {
+                    continue;
+                }
+                if discovery[u] == UNVISITED {
+                    discovery[u] = time;
+                    low[u] = time;
+                    time += 1;
+                    stack.push((u, v, 0));
+                } else {
+                    low[v] = low[v].min(discovery[u]);
+                }
+                continue;
+            }
+            stack.pop();
+            if parent == UNVISITED {
+                continue;
+            }
+            low[parent] = low[parent].min(low[v]);
+            if parent == root {
+                root_children += 1;
+            } else if low[v] >= discovery[parent] {
+                // nothing below v reaches above its parent
+                is_cut[parent] = true;
+            }
         }
-        indegrees.entry(src.clone()).or_default();
+        // the root is a cut vertex if the DFS left it more than once
+        is_cut[root] = root_children > 1;
     }
 
-    let mut v: Vec<(String, usize, usize, usize)> = Vec::new(); // id, in, out, sum
-    for (id, node) in &graph.nodes {
-        let in_deg = *indegrees.get(id).unwrap_or(&0);
+    let mut points: Vec<NodeIndex> = component
+        .iter()
+        .zip(is_cut)
+        .filter(|(_, cut)| *cut)
+        .map(|(&idx, _)| idx)
+        .collect();
+    points.sort_unstable();
+    points
+}
+
+// true if removing `idx` leaves at least two pieces of min_piece_nodes or more nodes that are attached to
+// the node on the same side, through its incoming or through its outgoing edges
+fn joins_separate_pieces(graph: &OverlapGraph, idx: NodeIndex, min_piece_nodes: usize) -> bool {
+    let Some(node) = graph.node(idx) else {
+        return false;
+    };
+    // piece of every node reachable without passing idx, and the size of every piece
+    let mut piece: HashMap<NodeIndex, usize> = HashMap::new();
+    let mut piece_sizes: Vec<usize> = Vec::new();
+    for start in undirected_neighbors(graph, idx) {
+        if piece.contains_key(&start) {
+            continue;
+        }
+        let id = piece_sizes.len();
+        piece.insert(start, id);
+        let mut size = 0usize;
+        let mut stack = vec![start];
+        while let Some(current) = stack.pop() {
+            size += 1;
+            for next in undirected_neighbors(graph, current) {
+                if next != idx && !piece.contains_key(&next) {
+                    piece.insert(next, id);
+                    stack.push(next);
+                }
+            }
+        }
+        piece_sizes.push(size);
+    }
+
+    let large_pieces = |side: &mut dyn Iterator<Item = NodeIndex>| -> usize {
+        let mut pieces: Vec<usize> = side
+            .filter_map(|n| piece.get(&n).copied())
+            .filter(|p| piece_sizes[*p] >= min_piece_nodes)
+            .collect();
+        pieces.sort_unstable();
+        pieces.dedup();
+        pieces.len()
+    };
+    large_pieces(&mut node.edges.iter().map(|e| e.target)) >= 2
+        || large_pieces(&mut node.in_edges().iter().copied()) >= 2
+}
+
+/// Articulation points of branching components where the graph is worth cutting: removing the node leaves
+/// at least two pieces of `min_piece_nodes` or more nodes that meet the node on the same side, e.g. two
+/// contigs running into one repeat read. A path through the node or a bubble opening at it meets the node
+/// with one piece per side and is left alone. The reverse complement of a cut point is included.
+/// `components` are the weakly connected components of `graph`.
+pub fn tangle_cut_points(
+    graph: &OverlapGraph,
+    components: &[Vec<NodeIndex>],
+    min_piece_nodes: usize,
+) -> HashSet<NodeIndex> {
+    let report = component_report(graph, components);
+    let mut cut_points: HashSet<NodeIndex> = HashSet::new();
+    for (component, stat) in components.iter().zip(report) {
+        if stat.kind != ComponentKind::Branching {
+            continue;
+        }
+        for idx in articulation_points(graph, component) {
+            let branching =
+                graph.indegree(idx) > 1 || graph.node(idx).is_some_and(|n| n.edges.len() > 1);
+            if cut_points.contains(&idx)
+                || !branching
+                || !joins_separate_pieces(graph, idx, min_piece_nodes)
+            {
+                continue;
+            }
+            cut_points.insert(rc_index(idx));
+            cut_points.insert(idx);
+        }
+    }
+    cut_points
+}
+
+/// Simple branching summary: return top-k nodes by (in_deg + out_deg)
+pub fn branching_summary(graph: &OverlapGraph, top_k: usize) -> Vec<(String, usize, usize)> {
+    let mut v: Vec<(NodeIndex, usize, usize, usize)> = Vec::new(); // node, in, out, sum
+    for (idx, node) in graph.nodes() {
+        let in_deg = graph.indegree(idx);
         let out_deg = node.edges.len();
         let sum = in_deg + out_deg;
         if in_deg > 1 || out_deg > 1 {
-            v.push((id.clone(), in_deg, out_deg, sum));
+            v.push((idx, in_deg, out_deg, sum));
         }
     }
-    // sort by sum desc
-    v.sort_unstable_by(|a, b| b.3.cmp(&a.3));
+    // sort by sum desc, ties by node id
+    v.sort_unstable_by(|a, b| b.3.cmp(&a.3).then_with(|| a.0.cmp(&b.0)));
     v.into_iter()
         .take(top_k)
-        .map(|(id, in_deg, out_deg, _)| (id, in_deg, out_deg))
+        .map(|(idx, in_deg, out_deg, _)| (graph.node_id(idx), in_deg, out_deg))
         .collect()
 }
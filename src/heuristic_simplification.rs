@@ -1,63 +1,65 @@
-use crate::create_overlap_graph::OverlapGraph;
-
-// helper: reverse-complement node id (swap '+' and '-')
-fn rc_node(id: &str) -> String {
-    if let Some(last) = id.chars().last() {
-        let base = &id[..id.len() - 1];
-        match last {
-            '+' => format!("{}-", base),
-            '-' => format!("{}+", base),
-            _ => id.to_string(),
-        }
-    } else {
-        id.to_string()
-    }
-}
+use crate::create_overlap_graph::{EdgeInfo, NodeIndex, OverlapGraph, rc_index};
+use log::debug;
+use std::collections::HashSet;
 
 /// Ensure graph symmetry: for every edge `u -> v`, require an edge `rc(v) -> rc(u)`.
 /// If the symmetric counterpart is missing, remove the original edge.
 pub fn symmetrize_graph(graph: &mut OverlapGraph) -> usize {
     let mut removed = 0usize;
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
+    let keys: Vec<NodeIndex> = graph.sorted_node_indices();
     for u in keys {
         // snapshot targets to avoid borrowing while mutating
-        let targets: Vec<String> = match graph.nodes.get(&u) {
-            Some(n) => n.edges.iter().map(|e| e.target_id.clone()).collect(),
+        let targets: Vec<NodeIndex> = match graph.node(u) {
+            Some(n) => n.edges.iter().map(|e| e.target).collect(),
             None => continue,
         };
         for v in targets {
-            let v_rc = rc_node(&v);
-            let u_rc = rc_node(&u);
             // check if rc(v) has edge to rc(u)
             let has_symm = graph
-                .nodes
-                .get(&v_rc)
-                .map_or(false, |vn| vn.edges.iter().any(|e| e.target_id == u_rc));
-            if !has_symm {
-                if let Some(un) = graph.nodes.get_mut(&u) {
-                    un.remove_edge(&v);
-                    removed += 1;
-                }
+                .node(rc_index(v))
+                .is_some_and(|vn| vn.edges.iter().any(|e| e.target == rc_index(u)));
+            if !has_symm && graph.remove_edge(u, v) {
+                removed += 1;
             }
         }
     }
     if removed > 0 {
-        //eprintln!("[heuristic_simplification::symmetrize_graph] removed {} asymmetric edges", removed);
+        debug!("symmetrize_graph: removed {} asymmetric edges", removed);
     }
     removed
 }
 
+/// Remove nodes without incoming and outgoing edges.
+/// A node is only removed if its reverse complement is isolated (or missing) as well, so both orientations go together.
+/// Returns the number of removed nodes. The pipeline runs this once after each heuristic step.
+pub fn prune_isolated_nodes(graph: &mut OverlapGraph) -> usize {
+    let is_isolated = |graph: &OverlapGraph, idx: NodeIndex| {
+        graph
+            .node(idx)
+            .is_none_or(|n| n.edges.is_empty() && n.in_edges().is_empty())
+    };
+    let isolated: Vec<NodeIndex> = graph
+        .node_indices()
+        .filter(|&idx| is_isolated(graph, idx) && is_isolated(graph, rc_index(idx)))
+        .collect();
+
+    isolated
+        .into_iter()
+        .filter(|&idx| graph.remove_node(idx).is_some())
+        .count()
+}
+
 /// Remove short edges (edges with overlap length below threshold)
 /// For each node with multiple outgoing edges, keep only those with overlap length >= drop_ratio * best_overlap_len
 pub fn remove_short_edges(graph: &mut OverlapGraph, drop_ratio: f64) -> usize {
     let mut n_short = 0;
 
-    // iterate over a snapshot of current node keys (no mutation while iterating)
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
+    // iterate over a sorted snapshot of current node keys (no mutation while iterating)
+    let keys: Vec<NodeIndex> = graph.sorted_node_indices();
 
-    for node_id in keys {
+    for idx in keys {
         // Get the outgoing edges for this node
-        let edges_to_remove: Vec<String> = if let Some(node) = graph.nodes.get(&node_id) {
+        let edges_to_remove: Vec<NodeIndex> = if let Some(node) = graph.node(idx) {
             // Skip if less than 2 outgoing edges
             if node.edges.len() < 2 {
                 continue;
@@ -73,40 +75,36 @@ pub fn remove_short_edges(graph: &mut OverlapGraph, drop_ratio: f64) -> usize {
             node.edges
                 .iter()
                 .filter(|e| e.overlap_len < threshold)
-                .map(|e| e.target_id.clone())
+                .map(|e| e.target)
                 .collect()
         } else {
             continue;
         };
 
-        // Remove the short edges
-        for target_id in edges_to_remove {
-            if let Some(node) = graph.nodes.get_mut(&node_id) {
-                node.remove_edge(&target_id);
+        // Remove the short edges and their reverse complement counterparts
+        for target in edges_to_remove {
+            if graph.remove_edge_symmetric(idx, target) {
                 n_short += 1;
-                // Remove the reverse edge as well
-                if let Some(target_node) = graph.nodes.get_mut(&target_id) {
-                    target_node.remove_edge(&node_id);
-                }
             }
         }
     }
+    debug!("remove_short_edges: removed {} short edges", n_short);
     n_short
-    //eprintln!("remove short edges: removed {} short edges", n_short);
 }
 
 /// Cut small bi-loops: patterns where v->...->x and w->v, w->x exist
-/// If overlap(w->v) > overlap(w->x), remove the w->x edge (keep the longer path)
-pub fn cut_biloop(graph: &mut OverlapGraph, max_ext: usize) {
+/// If overlap(w->v) > overlap(w->x), remove the w->x edge (keep the longer path).
+/// Returns the number of cut edges.
+pub fn cut_biloop(graph: &mut OverlapGraph, max_ext: usize) -> usize {
     let mut cnt = 0;
 
-    // iterate over a snapshot of current node keys
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
+    // iterate over a sorted snapshot of current node keys
+    let keys: Vec<NodeIndex> = graph.sorted_node_indices();
 
     for v in keys {
-        // Skip if node doesn't have multiple outgoing edges
-        if let Some(v_node) = graph.nodes.get(&v) {
-            if v_node.edges.len() < 2 {
+        // Skip if node doesn't start a simple chain (a single outgoing edge)
+        if let Some(v_node) = graph.node(v) {
+            if v_node.edges.len() != 1 {
                 continue;
             }
         } else {
@@ -114,42 +112,43 @@ pub fn cut_biloop(graph: &mut OverlapGraph, max_ext: usize) {
         }
 
         // Try to extend from this node up to max_ext steps
-        let extended_path = extend_path(graph, &v, max_ext);
+        let extended_path = extend_path(graph, v, max_ext);
         if extended_path.is_empty() || extended_path.len() < 2 {
             continue;
         }
 
         // Get the last node in the extended path (x)
-        let x = &extended_path[extended_path.len() - 1];
+        let x = extended_path[extended_path.len() - 1];
 
         // Find incoming edges to v (w->v edges)
-        let incoming_to_v: Vec<(String, u32)> = graph
-            .nodes
-            .iter()
-            .filter_map(|(node_id, node)| {
-                node.edges
+        let mut sources: Vec<NodeIndex> = graph
+            .node(v)
+            .map(|n| n.in_edges().to_vec())
+            .unwrap_or_default();
+        sources.sort_unstable();
+        sources.dedup();
+        let incoming_to_v: Vec<(NodeIndex, u32)> = sources
+            .into_iter()
+            .filter_map(|w| {
+                let ov = graph
+                    .node(w)?
+                    .edges
                     .iter()
-                    .find(|e| &e.target_id == &v)
-                    .map(|e| (node_id.clone(), e.overlap_len))
+                    .find(|e| e.target == v)?
+                    .overlap_len;
+                Some((w, ov))
             })
             .collect();
 
         // For each incoming node w, check if it also has an edge to x
         for (w, ov) in incoming_to_v {
-            if let Some(w_node) = graph.nodes.get(&w) {
+            if let Some(w_node) = graph.node(w) {
                 // Find overlap from w to x
-                if let Some(edge_to_x) = w_node.edges.iter().find(|e| &e.target_id == x) {
+                if let Some(edge_to_x) = w_node.edges.iter().find(|e| e.target == x) {
                     let ox = edge_to_x.overlap_len;
 
-                    // If overlap(w->v) > overlap(w->x), remove w->x edge
-                    if ov > ox {
-                        if let Some(w_mut) = graph.nodes.get_mut(&w) {
-                            w_mut.remove_edge(x);
-                        }
-                        // Remove reverse edge
-                        if let Some(x_mut) = graph.nodes.get_mut(x) {
-                            x_mut.remove_edge(&w);
-                        }
+                    // If overlap(w->v) > overlap(w->x), remove w->x edge and its reverse complement counterpart
+                    if ov > ox && graph.remove_edge_symmetric(w, x) {
                         cnt += 1;
                     }
                 }
@@ -157,27 +156,25 @@ pub fn cut_biloop(graph: &mut OverlapGraph, max_ext: usize) {
         }
     }
 
-    if cnt > 0 {
-        //eprintln!("[heuristic_simplification::cut_biloop] cut {} small bi-loops", cnt);
-    }
+    cnt
 }
 
 /// Helper function to extend a path from a starting node up to max_ext edges
 /// Returns the sequence of nodes visited (including start node)
-fn extend_path(graph: &OverlapGraph, start: &str, max_ext: usize) -> Vec<String> {
-    let mut path = vec![start.to_string()];
-    let mut current = start.to_string();
+fn extend_path(graph: &OverlapGraph, start: NodeIndex, max_ext: usize) -> Vec<NodeIndex> {
+    let mut path = vec![start];
+    let mut current = start;
 
     for _ in 0..max_ext {
         // Get the single outgoing edge (if it exists and is unique)
-        if let Some(node) = graph.nodes.get(&current) {
+        if let Some(node) = graph.node(current) {
             // Only extend if there's exactly one outgoing edge
             if node.edges.len() == 1 {
-                let next = &node.edges[0].target_id;
+                let next = node.edges[0].target;
                 // Avoid cycles
-                if !path.contains(next) {
-                    path.push(next.clone());
-                    current = next.clone();
+                if !path.contains(&next) {
+                    path.push(next);
+                    current = next;
                 } else {
                     break;
                 }
@@ -195,28 +192,17 @@ fn extend_path(graph: &OverlapGraph, start: &str, max_ext: usize) -> Vec<String>
 
 /// Cut internal sequences: when there's a simple linear chain between two branching
 /// nodes, remove the internal sequences (reads) found along that chain up to
-/// `max_ext` steps.
-pub fn cut_internal(graph: &mut OverlapGraph, max_ext: usize) {
-    // build indegree map
-    let mut indegree: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-    for id in graph.nodes.keys() {
-        indegree.insert(id.clone(), 0);
-    }
-    for (_id, node) in &graph.nodes {
-        for e in &node.edges {
-            *indegree.entry(e.target_id.clone()).or_default() += 1;
-        }
-    }
-
+/// `max_ext` steps. Returns the number of removed reads.
+pub fn cut_internal(graph: &mut OverlapGraph, max_ext: usize) -> usize {
     let mut removed_reads = 0usize;
 
-    // snapshot of keys to avoid borrowing while mutating
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
+    // sorted snapshot of keys to avoid borrowing while mutating
+    let keys: Vec<NodeIndex> = graph.sorted_node_indices();
 
     for v in keys {
         // snapshot outgoing targets for v to avoid borrowing graph while mutating
-        let outgoing_targets: Vec<String> = match graph.nodes.get(&v) {
-            Some(n) => n.edges.iter().map(|e| e.target_id.clone()).collect(),
+        let outgoing_targets: Vec<NodeIndex> = match graph.node(v) {
+            Some(n) => n.edges.iter().map(|e| e.target).collect(),
             None => continue,
         };
         if outgoing_targets.len() < 2 {
@@ -225,18 +211,18 @@ pub fn cut_internal(graph: &mut OverlapGraph, max_ext: usize) {
 
         // for each outgoing target, try to follow a simple chain
         for target in outgoing_targets {
-            let mut path: Vec<String> = Vec::new();
-            let mut cur = target.clone();
+            let mut path: Vec<NodeIndex> = Vec::new();
+            let mut cur = target;
             let mut steps = 0usize;
 
             // follow while nodes are simple (indegree==1 && outdegree==1)
             while steps < max_ext {
                 // ensure node exists
-                let cur_node = match graph.nodes.get(&cur) {
+                let cur_node = match graph.node(cur) {
                     Some(n) => n,
                     None => break,
                 };
-                let in_deg = *indegree.get(&cur).unwrap_or(&0);
+                let in_deg = graph.indegree(cur);
                 let out_deg = cur_node.edges.len();
 
                 // stop extension if this node is not a simple internal node
@@ -245,8 +231,8 @@ pub fn cut_internal(graph: &mut OverlapGraph, max_ext: usize) {
                 }
 
                 // record this internal node and advance
-                path.push(cur.clone());
-                let next = cur_node.edges[0].target_id.clone();
+                path.push(cur);
+                let next = cur_node.edges[0].target;
                 // avoid cycles
                 if path.contains(&next) {
                     break;
@@ -259,7 +245,7 @@ pub fn cut_internal(graph: &mut OverlapGraph, max_ext: usize) {
             if path.is_empty() {
                 continue;
             }
-            if let Some(end_node) = graph.nodes.get(&cur) {
+            if let Some(end_node) = graph.node(cur) {
                 if end_node.edges.len() < 2 {
                     continue;
                 }
@@ -269,56 +255,56 @@ pub fn cut_internal(graph: &mut OverlapGraph, max_ext: usize) {
 
             // delete the internal reads (both orientations)
             for internal in path {
-                if internal.len() < 1 {
-                    continue;
-                }
-                let read = &internal[..internal.len() - 1];
-                let plus = format!("{}+", read);
-                let minus = format!("{}-", read);
-                let removed_plus = graph.nodes.remove(&plus);
-                let removed_minus = graph.nodes.remove(&minus);
-                if removed_plus.is_some() || removed_minus.is_some() {
+                if graph.remove_node_symmetric(internal) > 0 {
                     removed_reads += 1;
                 }
             }
         }
     }
 
-    // cleanup: remove edges that point to missing nodes
-    let existing: std::collections::HashSet<String> = graph.nodes.keys().cloned().collect();
-    for node in graph.nodes.values_mut() {
-        node.edges.retain(|e| existing.contains(&e.target_id));
-    }
+    removed_reads
+}
+
+/// The best of the parallel edges to `target`: largest overlap_len, then highest identity.
+/// The first of equally good edges wins.
+fn best_parallel_edge(edges: &[EdgeInfo], target: NodeIndex) -> Option<EdgeInfo> {
+    edges
+        .iter()
+        .filter(|e| e.target == target)
+        .fold(None, |best: Option<&EdgeInfo>, e| match best {
+            Some(b)
+                if e.overlap_len < b.overlap_len
+                    || (e.overlap_len == b.overlap_len && e.identity <= b.identity) =>
+            {
+                Some(b)
+            }
+            _ => Some(e),
+        })
+        .cloned()
+}
 
-    if removed_reads > 0 {
-        //eprintln!("[heuristic_simplification::cut_internal] cut {} internal sequences", removed_reads);
+/// Overwrite the metrics of the (single remaining) edge from -> edge.target
+fn replace_edge_info(graph: &mut OverlapGraph, from: NodeIndex, edge: EdgeInfo) {
+    if let Some(slot) = graph
+        .node_mut(from)
+        .and_then(|n| n.edges.iter_mut().find(|e| e.target == edge.target))
+    {
+        *slot = edge;
     }
 }
 
 /// Delete multi-arcs: when a node has multiple arcs to the same target,
 /// keep the best (largest overlap_len, then highest identity) and remove the others.
+/// The extra arcs are removed together with their reverse complement counterparts, so both strands keep one arc.
+/// Returns the number of removed edges, counted on both strands.
 pub fn remove_multi_edges(graph: &mut OverlapGraph) -> usize {
-    use std::collections::HashMap;
-
-    let mut n_multi = 0usize;
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
-
-    // helper to compute reverse-complement node id
-    let rc = |id: &str| -> String {
-        if let Some(last) = id.chars().last() {
-            let base = &id[..id.len() - 1];
-            match last {
-                '+' => format!("{}-", base),
-                '-' => format!("{}+", base),
-                _ => id.to_string(),
-            }
-        } else {
-            id.to_string()
-        }
-    };
+    use std::collections::BTreeMap;
+
+    let edges_before = graph.edge_count();
+    let keys: Vec<NodeIndex> = graph.sorted_node_indices();
 
     for src in keys {
-        let edges_snapshot = match graph.nodes.get(&src) {
+        let edges_snapshot = match graph.node(src) {
             Some(n) => n.edges.clone(),
             None => continue,
         };
@@ -326,84 +312,95 @@ pub fn remove_multi_edges(graph: &mut OverlapGraph) -> usize {
             continue;
         }
 
-        let mut counts: HashMap<String, usize> = HashMap::new();
-        let mut best_idx: HashMap<String, usize> = HashMap::new();
-        for (i, e) in edges_snapshot.iter().enumerate() {
-            *counts.entry(e.target_id.clone()).or_insert(0) += 1;
-            match best_idx.get(&e.target_id) {
-                Some(&bi) => {
-                    let be = &edges_snapshot[bi];
-                    if e.overlap_len > be.overlap_len
-                        || (e.overlap_len == be.overlap_len && e.identity > be.identity)
-                    {
-                        best_idx.insert(e.target_id.clone(), i);
-                    }
-                }
-                None => {
-                    best_idx.insert(e.target_id.clone(), i);
-                }
-            }
-        }
-
-        if edges_snapshot.len() == best_idx.len() {
-            continue;
+        let mut counts: BTreeMap<NodeIndex, usize> = BTreeMap::new();
+        for e in &edges_snapshot {
+            *counts.entry(e.target).or_insert(0) += 1;
         }
 
-        let mut chosen: Vec<(String, crate::create_overlap_graph::EdgeInfo)> = best_idx
-            .into_iter()
-            .map(|(t, i)| (t, edges_snapshot[i].clone()))
-            .collect();
-        chosen.sort_by(|a, b| a.0.cmp(&b.0));
-        let new_edges: Vec<crate::create_overlap_graph::EdgeInfo> =
-            chosen.into_iter().map(|(_t, e)| e).collect();
-
-        let mut removed_targets: Vec<String> = Vec::new();
-        for (t, cnt) in counts.into_iter() {
-            if cnt > 1 {
-                removed_targets.push(t);
-                n_multi += cnt - 1;
+        for (tgt, cnt) in counts {
+            if cnt < 2 {
+                continue;
             }
-        }
-
-        if let Some(node_mut) = graph.nodes.get_mut(&src) {
-            node_mut.edges = new_edges;
-        }
-
-        for tgt in removed_targets {
-            if let Some(tnode) = graph.nodes.get_mut(&tgt) {
-                tnode.remove_edge(&src);
+            let best = best_parallel_edge(&edges_snapshot, tgt);
+            // the counterpart rc(tgt) -> rc(src) keeps its own best metrics, its edge length differs
+            let rc_best = graph
+                .node(rc_index(tgt))
+                .and_then(|n| best_parallel_edge(&n.edges, rc_index(src)));
+
+            for _ in 1..cnt {
+                graph.remove_edge_symmetric(src, tgt);
             }
-            let src_rc = rc(&src);
-            let tgt_rc = rc(&tgt);
-            if let Some(tnode_rc) = graph.nodes.get_mut(&tgt_rc) {
-                tnode_rc.remove_edge(&src_rc);
+
+            if let Some(best) = best {
+                replace_edge_info(graph, src, best);
             }
-            if let Some(src_rc_node) = graph.nodes.get_mut(&src_rc) {
-                src_rc_node.remove_edge(&tgt_rc);
+            if let Some(rc_best) = rc_best {
+                replace_edge_info(graph, rc_index(tgt), rc_best);
             }
         }
     }
 
+    // both strands of a multi-arc are counted
+    let n_multi = edges_before - graph.edge_count();
+
     // ensure symmetry after modifications
     let sym_removed = symmetrize_graph(graph);
     if n_multi > 0 || sym_removed > 0 {
-        //eprintln!("[heuristic_simplification::remove_multi_arcs] removed {} multi-arcs ({} asymmetric edges removed)", n_multi, sym_removed);
+        debug!(
+            "remove_multi_edges: removed {} multi-edges ({} asymmetric edges removed)",
+            n_multi, sym_removed
+        );
     }
 
     n_multi
 }
 
-/// Remove low identity from nodes with multiple outgoing edges
-pub fn remove_weak(graph: &mut OverlapGraph) {
-    // iterate over a snapshot of current node keys (no mutation while iterating)
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
+/// Cut the graph at `nodes`: remove all edges into and out of them, e.g. collapsed repeats or articulation points.
+/// Edges are removed together with their reverse complement. The nodes end up isolated, so the next
+/// `prune_isolated_nodes` removes the reads from the graph and they are left out of every unitig.
+/// Returns the number of removed edges.
+pub fn isolate_nodes(graph: &mut OverlapGraph, nodes: &HashSet<NodeIndex>) -> usize {
+    let mut n_removed = 0usize;
+    let mut sorted: Vec<NodeIndex> = nodes.iter().copied().collect();
+    sorted.sort_unstable();
+    for n in sorted {
+        let Some(node) = graph.node(n) else {
+            continue;
+        };
+        let outgoing: Vec<NodeIndex> = node.edges.iter().map(|e| e.target).collect();
+        let incoming: Vec<NodeIndex> = node.in_edges().to_vec();
+        for target in outgoing {
+            if graph.remove_edge_symmetric(n, target) {
+                n_removed += 1;
+            }
+        }
+        for source in incoming {
+            if graph.remove_edge_symmetric(source, n) {
+                n_removed += 1;
+            }
+        }
+    }
+    n_removed
+}
+
+/// Remove weak edges from nodes with multiple outgoing edges.
+/// An edge is scored by its overlap length weighted by identity (the number of matching bases) and is weak
+/// if its score is below `keep_ratio` * the best score of the node and its identity is more than
+/// `identity_tolerance` (percentage points) below the best identity. A repeat boundary with two similar
+/// branches therefore keeps both edges.
+/// Returns the number of removed edges.
+pub fn remove_weak(graph: &mut OverlapGraph, keep_ratio: f64, identity_tolerance: f64) -> usize {
+    let score = |overlap_len: u32, identity: f64| overlap_len as f64 * identity / 100.0;
+    let mut n_weak = 0usize;
+    // iterate over a sorted snapshot of current node keys (no mutation while iterating)
+    let keys: Vec<NodeIndex> = graph.sorted_node_indices();
     for n in keys.into_iter() {
         // check the amount of outgoing edges
-        let outgoing = match graph.nodes.get(&n) {
+        let outgoing = match graph.node(n) {
             Some(n) => n
                 .edges
                 .iter()
-                .map(|e| (e.target_id.clone(), e.identity))
+                .map(|e| (e.target, score(e.overlap_len, e.identity), e.identity))
                 .collect::<Vec<_>>(),
             None => continue,
         };
@@ -413,27 +410,15 @@ pub fn remove_weak(graph: &mut OverlapGraph) {
             continue;
         }
 
-        // only keep edge with highest identity, remove others
-        let mut max_identity: f64 = -1.0;
-        let mut best_target: Option<String> = None;
-        for (target_id, identity) in outgoing.iter() {
-            if *identity > max_identity {
-                max_identity = *identity;
-                best_target = Some(target_id.clone());
-            }
-        }
-
-        // remove all edges except the best one
-        for (target_id, _) in outgoing.iter() {
-            if Some(target_id) != best_target.as_ref() {
-                if let Some(node) = graph.nodes.get_mut(&n) {
-                    node.remove_edge(target_id);
-                    // remove the reverse edge as well
-                    if let Some(target_node) = graph.nodes.get_mut(target_id) {
-                        target_node.remove_edge(&n);
-                    }
-                }
+        let best_score = outgoing.iter().map(|e| e.1).fold(0.0, f64::max);
+        let best_identity = outgoing.iter().map(|e| e.2).fold(0.0, f64::max);
+        for (target, edge_score, identity) in outgoing.iter() {
+            let weak = *edge_score < keep_ratio * best_score
+                && *identity < best_identity - identity_tolerance;
+            if weak && graph.remove_edge_symmetric(n, *target) {
+                n_weak += 1;
             }
         }
     }
+    n_weak
 }
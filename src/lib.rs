@@ -0,0 +1,34 @@
+//! Ilesta, an overlap-layout-consensus assembler for long reads.
+//! The modules follow the stages of the pipeline, `assemble` runs all of them in memory and returns the unitig
+//! graph. It only reads its inputs: the FASTQ and the PAF, overlaps, GFA or intermediate given in the config.
+//!
+//! The assemble command calls `pipeline::run` instead, which also writes the intermediates an interrupted
+//! run can be resumed from and, through `pipeline::write_outputs`, the unitig FASTA and GFA, the reads2unitig,
+//! coverage, layout and backbones tables, graph.dot and any requested reports to `output_dir`.
+pub mod alignment_filtering;
+pub mod bubble_removal;
+pub mod cli;
+pub mod compress_graph;
+pub mod configs;
+pub mod consensus;
+pub mod create_overlap_graph;
+pub mod graph_analysis;
+pub mod heuristic_simplification;
+pub mod pipeline;
+pub mod read_io;
+pub mod stats;
+pub mod tip_trimming;
+pub mod transitive_edge_reduction;
+pub mod utils;
+
+pub use alignment_filtering::Overlap;
+pub use bubble_removal::remove_bubbles;
+pub use compress_graph::CompressedGraph;
+pub use configs::AssembleConfig;
+pub use create_overlap_graph::OverlapGraph;
+pub use heuristic_simplification::{
+    cut_biloop, cut_internal, isolate_nodes, remove_multi_edges, remove_short_edges, remove_weak,
+};
+pub use pipeline::assemble;
+pub use tip_trimming::{trim_low_identity_tips, trim_short_tips, trim_tips};
+pub use transitive_edge_reduction::reduce_transitive_edges;
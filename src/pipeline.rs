@@ -0,0 +1,870 @@
+use crate::alignment_filtering::{
+    self, AlignmentFilteringOutput, ContainedPlacement, FilteringOptions,
+};
+use crate::cli::ResumeStage;
+use crate::compress_graph::{self, CompressedGraph};
+use crate::configs;
+use crate::create_overlap_graph::{NodeIndex, OverlapGraph};
+use crate::{
+    bubble_removal, consensus, create_overlap_graph, graph_analysis, heuristic_simplification,
+    read_io, stats, tip_trimming, transitive_edge_reduction,
+};
+/// assembly pipeline module
+/// runs the stages of the assemble command, in memory with `assemble` or writing their output with `run`
+/// 1. load the reads and filter the alignments (or load overlaps, a GFA graph or an intermediate to resume from)
+/// 2. build the overlap graph and clean it up
+/// 3. compress the graph into unitigs and build and polish their sequences
+/// 4. `write_outputs` writes the results to the output directory
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+/// Result of the assemble pipeline, with the data the output files are written from
+pub struct Assembly {
+    /// unitigs with their sequences and coverage
+    pub unitigs: CompressedGraph,
+    /// the overlap graph after the cleanup
+    pub graph: OverlapGraph,
+    pub fastq_seqs: HashMap<String, String>,
+    /// duplicate read -> its representative, empty without --dedup-reads
+    pub read_aliases: HashMap<String, String>,
+    /// contained read -> placement on its container
+    pub contained_in: HashMap<String, ContainedPlacement>,
+    /// components of the cleaned graph that are a single line, as (component id, ordered node ids)
+    pub backbones: Vec<(usize, Vec<String>)>,
+    /// reads in no unitig and not contained in another read, only collected with --write-singletons
+    pub singletons: Vec<String>,
+    pub stats: stats::RunStats,
+}
+
+/// The stage functions report their errors as Box<dyn Error>, the pipeline as io::Error
+fn io_error(e: Box<dyn std::error::Error>) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// Run the assemble pipeline in memory and return the unitig graph.
+/// Only the inputs are read (the reads, the PAF, overlaps or GFA file, or the intermediate to resume from),
+/// nothing is written, see `run` for the assemble command. A dry run only reports the cleanup candidates
+/// and returns an empty graph.
+pub fn assemble(config: &configs::AssembleConfig) -> io::Result<CompressedGraph> {
+    Ok(run_stages(config, None)?.map_or_else(
+        || CompressedGraph {
+            unitigs: Vec::new(),
+            edges: Vec::new(),
+        },
+        |assembly| assembly.unitigs,
+    ))
+}
+
+/// Run the assemble command: the pipeline, writing the intermediates to the output directory as the stages
+/// finish so an interrupted run can be resumed, and then the outputs
+pub fn run(config: &configs::AssembleConfig) -> io::Result<()> {
+    let out_dir = Path::new(&config.output_dir);
+    std::fs::create_dir_all(out_dir)?;
+    if let Some(assembly) = run_stages(config, Some(out_dir))? {
+        write_outputs(config, &assembly)?;
+    }
+    info!("=== ASSEMBLY COMPLETE ===");
+    Ok(())
+}
+
+/// Stages of the pipeline, a dry run stops after reporting the cleanup candidates and returns None.
+/// The overlaps, graph and cleaned graph intermediates, the overlaps per read, --dump-raw-graph and
+/// the --keep-intermediate snapshots are written to `out_dir`, none of them without it.
+fn run_stages(
+    config: &configs::AssembleConfig,
+    out_dir: Option<&Path>,
+) -> io::Result<Option<Assembly>> {
+    // load reads, needed for duplicate detection, base pair lengths in the diagnostics and the unitig sequences
+    info!("Loading FASTQ sequences from {}...", config.reads_fq);
+    let fastq_seqs = read_io::read_fastq(&config.reads_fq)?;
+
+    // duplicate reads are collapsed while filtering the alignments, so only when a PAF file is filtered
+    let mut run_stats = stats::RunStats::default();
+    let read_aliases = if !config.dedup_reads {
+        HashMap::new()
+    } else if config.input_paf.is_none() {
+        warn!("--dedup-reads only applies when filtering a PAF file, ignoring it");
+        HashMap::new()
+    } else {
+        let aliases = read_io::duplicate_reads(&fastq_seqs);
+        info!(
+            "Collapsed {} duplicate reads into their representatives",
+            aliases.len()
+        );
+        run_stats.duplicate_reads = aliases.len();
+        aliases
+    };
+
+    // intermediates of the pipeline stages, an earlier run can be resumed from them
+    let intermediate = |name: &str| {
+        Path::new(&config.output_dir).join(format!("{}.{}.bin", config.output_prefix, name))
+    };
+    let overlaps_path = intermediate("overlaps");
+    let overlaps_path_str = overlaps_path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid output path"))?
+        .to_string();
+    let graph_path = intermediate("graph");
+    let cleaned_graph_path = intermediate("cleaned_graph");
+
+    // contained read -> placement on its container, empty for GFA input
+    let mut contained_in = HashMap::new();
+    let mut graph = if let Some(stage @ (ResumeStage::Cleanup | ResumeStage::Compress)) =
+        config.resume_from
+    {
+        let (stage_name, path) = if stage == ResumeStage::Cleanup {
+            ("cleanup", &graph_path)
+        } else {
+            ("compress", &cleaned_graph_path)
+        };
+        info!(
+            "Resuming from the {} stage, loading the graph from {}",
+            stage_name,
+            path.display()
+        );
+        // the consensus and the singleton output still need the contained reads recorded in the overlaps
+        if config.consensus_min_depth > 0 || config.write_singletons {
+            let overlaps_file = config.overlaps.as_ref().unwrap_or(&overlaps_path_str);
+            match AlignmentFilteringOutput::deserialize_overlaps(overlaps_file) {
+                Ok(filtering) => contained_in = filtering.contained_in,
+                Err(e) => warn!(
+                    "could not load contained reads from {}, only the unitig reads are used: {}",
+                    overlaps_file, e
+                ),
+            }
+        }
+        OverlapGraph::deserialize_graph(path).map_err(io_error)?
+    } else if let Some(ref gfa_file) = config.input_gfa {
+        // Use a provided graph, skips filtering and graph creation
+        info!("Using provided graph from {}", gfa_file);
+        let graph = create_overlap_graph::read_gfa(gfa_file)?;
+        info!("Graph nodes: {}", graph.node_count());
+        info!("Graph edges: {}", graph.edge_count());
+        graph
+    } else {
+        // Determine the path to overlaps: either use provided overlaps or run alignment filtering
+        // load overlaps or keep the freshly filtered ones in memory
+        let requested_params = alignment_filtering::FilteringParams {
+            min_overlap_length: config.min_overlap_length,
+            min_percent_identity: config.min_percent_identity,
+            overhang_ratio: config.overhang_ratio,
+        };
+        let mut filtering = if let Some(ref overlaps_file) = config.overlaps {
+            // Use provided overlaps
+            info!("Using provided overlaps from {}", overlaps_file);
+            let filtering =
+                AlignmentFilteringOutput::deserialize_overlaps(overlaps_file).map_err(io_error)?;
+            filtering.warn_on_param_mismatch(overlaps_file, &requested_params);
+            filtering
+        } else if config.resume_from == Some(ResumeStage::Graph) {
+            info!(
+                "Resuming from the graph stage, using overlaps from {}",
+                overlaps_path_str
+            );
+            let filtering = AlignmentFilteringOutput::deserialize_overlaps(&overlaps_path_str)
+                .map_err(io_error)?;
+            filtering.warn_on_param_mismatch(&overlaps_path_str, &requested_params);
+            filtering
+        } else {
+            // Run alignment filtering
+            let input_paf = config.input_paf.as_ref().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Either --input-paf, --overlaps or --input-gfa must be provided",
+                )
+            })?;
+
+            let mut out = alignment_filtering::run_alignment_filtering(
+                input_paf,
+                &FilteringOptions::from(config),
+                &read_aliases,
+            )
+            .map_err(io_error)?;
+            if let Some(out_dir) = out_dir {
+                out.serialize_overlaps(&overlaps_path_str)
+                    .map_err(io_error)?;
+                info!("Wrote overlaps to {}", overlaps_path_str);
+                let degrees_path =
+                    out_dir.join(format!("{}.overlaps_per_read.tsv", config.output_prefix));
+                let degrees_str = degrees_path.to_str().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "invalid output path")
+                })?;
+                out.write_overlap_degrees(degrees_str).map_err(io_error)?;
+                info!("Wrote overlaps per read to {}", degrees_str);
+            }
+            run_stats.filtering = Some(std::mem::take(&mut out.stats));
+            out
+        };
+
+        // build graph
+        contained_in = std::mem::take(&mut filtering.contained_in);
+        create_overlap_graph::run_create_overlap_graph(filtering, config.min_edge_identity)
+            .map_err(io_error)?
+    };
+    if let Some(raw_graph_path) = config.dump_raw_graph.as_ref().filter(|_| out_dir.is_some()) {
+        if config.resume_from == Some(ResumeStage::Compress) {
+            warn!("the loaded graph is already cleaned up, ignoring --dump-raw-graph");
+        } else {
+            graph.write_raw_gfa(raw_graph_path, &fastq_seqs)?;
+            info!("Wrote the raw overlap graph to {}", raw_graph_path);
+        }
+    }
+    if matches!(config.resume_from, None | Some(ResumeStage::Graph)) {
+        // reject impossible overlaps before they reach the cleanup, the saved graph is already validated
+        if config.validate_overlaps {
+            let removed = graph_analysis::remove_impossible_overlaps(&mut graph, &fastq_seqs);
+            info!(
+                "Removed {} edges with an overlap longer than one of their reads",
+                removed
+            );
+        }
+        if out_dir.is_some() {
+            graph.serialize_graph(&graph_path).map_err(io_error)?;
+            info!("Wrote the overlap graph to {}", graph_path.display());
+        }
+    }
+    run_stats.graph_nodes = graph.node_count();
+    run_stats.graph_edges = graph.edge_count();
+
+    if config.dry_run {
+        report_cleanup_candidates(&mut graph, config);
+        return Ok(None);
+    }
+
+    if config.resume_from != Some(ResumeStage::Compress) {
+        let snapshot_dir = out_dir.filter(|_| config.keep_intermediate);
+        run_cleanup(
+            &mut graph,
+            config,
+            snapshot_dir,
+            &fastq_seqs,
+            &mut run_stats,
+        )?;
+        if out_dir.is_some() {
+            graph
+                .serialize_graph(&cleaned_graph_path)
+                .map_err(io_error)?;
+            info!(
+                "Wrote the cleaned graph to {}",
+                cleaned_graph_path.display()
+            );
+        }
+    }
+    run_stats.final_nodes = graph.node_count();
+    run_stats.final_edges = graph.edge_count();
+    info!("Final graph has {} nodes", run_stats.final_nodes);
+    info!("Final graph has {} edges", run_stats.final_edges);
+    let cyclic_components: Vec<usize> = graph_analysis::strongly_connected_components(&graph)
+        .iter()
+        .map(|c| c.len())
+        .filter(|&len| len > 1)
+        .collect();
+    info!(
+        "Final graph has {} strongly connected components with more than one node (largest: {} nodes)",
+        cyclic_components.len(),
+        cyclic_components.iter().max().unwrap_or(&0)
+    );
+
+    let adjacency = graph_analysis::build_undirected_adjacency(&graph);
+    let components = graph_analysis::components_largest_first(&adjacency);
+    let component_stats = graph_analysis::component_report(&graph, &components);
+    run_stats.final_components = component_stats.len();
+    info!(
+        "Final graph has {} weakly connected components, largest:",
+        component_stats.len()
+    );
+    for c in component_stats.iter().take(10) {
+        info!(
+            "  component {}: {} nodes, {} edges, {} branching nodes, {:?}",
+            c.id, c.nodes, c.edges, c.branching_nodes, c.kind
+        );
+    }
+
+    // components that resolved into a single line, as ordered read lists
+    let backbones: Vec<(usize, Vec<String>)> = components
+        .iter()
+        .enumerate()
+        .filter_map(|(id, c)| graph_analysis::linear_backbone(&graph, c).map(|p| (id, p)))
+        .collect();
+    info!(
+        "{} of {} components are unambiguous linear paths",
+        backbones.len(),
+        components.len()
+    );
+
+    info!("=== COMPRESSING UNITIGS ===");
+    let mut compressed = compress_graph::compress_unitigs(&graph, config.standalone_junctions);
+    let palindromic = compressed.resolve_palindromic_unitigs(config.drop_palindromic_unitigs);
+    if palindromic > 0 {
+        info!(
+            "{} {} unitigs containing both orientations of a read",
+            if config.drop_palindromic_unitigs {
+                "Dropped"
+            } else {
+                "Split"
+            },
+            palindromic
+        );
+    }
+
+    // trim low coverage reads at the unitig ends
+    if config.min_end_coverage > 0.0 {
+        let (trimmed_unitigs, trimmed_members) =
+            compressed.trim_low_coverage_ends(&graph, config.min_end_coverage);
+        info!(
+            "Trimmed {} low coverage end reads from {} unitigs (coverage < {})",
+            trimmed_members, trimmed_unitigs, config.min_end_coverage
+        );
+    }
+
+    // drop short unitigs before generating sequences
+    if config.min_unitig_length > 0 {
+        let (removed, removed_bases) =
+            compressed.remove_short_unitigs(config.min_unitig_length as usize, &fastq_seqs);
+        info!(
+            "Removed {} unitigs shorter than {} bp ({} bp in total)",
+            removed, config.min_unitig_length, removed_bases
+        );
+    }
+
+    // generate unitig sequences
+    if let Err(e) = compress_graph::validate_compressed(&compressed, &fastq_seqs) {
+        warn!("compressed graph is inconsistent: {}", e);
+    }
+    let failed_unitigs = compressed.build_sequences(&graph, &fastq_seqs, false);
+    let failed = failed_unitigs.len();
+    if failed > 0 {
+        info!(
+            "Skipped {} unitigs without a valid sequence: {}",
+            failed,
+            failed_unitigs
+                .iter()
+                .map(|id| format!("unitig_{}", id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    if config.consensus_min_depth > 0 {
+        let changed = consensus::polish_unitigs(
+            &mut compressed,
+            &fastq_seqs,
+            &contained_in,
+            config.consensus_min_depth as usize,
+        );
+        info!(
+            "Consensus changed {} positions using {} contained reads (min depth {})",
+            changed,
+            contained_in.len(),
+            config.consensus_min_depth
+        );
+    }
+    compressed.compute_coverage(&graph, &fastq_seqs);
+
+    let singletons = if config.write_singletons {
+        let contained: HashSet<&str> = contained_in.keys().map(String::as_str).collect();
+        compressed.singleton_reads(&fastq_seqs, &read_aliases, &contained)
+    } else {
+        Vec::new()
+    };
+    run_stats.singleton_reads = singletons.len();
+
+    // assembly statistics over one unitig of every reverse-complement pair, the headline N50 is in base pairs
+    let orientations = compressed.segment_orientations();
+    let representatives: Vec<&compress_graph::Unitig> = compressed
+        .unitigs
+        .iter()
+        .filter(|u| orientations.get(&u.id).is_none_or(|(_, o)| *o == '+'))
+        .collect();
+    let bp_lengths: Vec<usize> = representatives
+        .iter()
+        .filter_map(|u| compress_graph::unitig_bp_length(u, &fastq_seqs))
+        .collect();
+    let node_lengths: Vec<usize> = representatives.iter().map(|u| u.members.len()).collect();
+    run_stats.unitigs = compressed.unitigs.len() - failed;
+    run_stats.circular_unitigs = representatives.iter().filter(|u| u.is_circular).count();
+    info!(
+        "Circular unitigs (one per strand pair): {}",
+        run_stats.circular_unitigs
+    );
+    let metrics = stats::assembly_metrics(&bp_lengths, config.genome_size);
+    info!(
+        "Unitigs (one per strand pair): {}, total length {} bp, largest {} bp",
+        metrics.count, metrics.total, metrics.largest
+    );
+    info!("N50 unitig length: {} bp", metrics.n50);
+    info!("L50: {} unitigs", metrics.l50);
+    if let Some(genome_size) = config.genome_size {
+        match (metrics.ng50, metrics.lg50) {
+            (Some(ng50), Some(lg50)) => info!("NG50: {} bp, LG50: {} unitigs", ng50, lg50),
+            _ => info!(
+                "NG50 undefined, the unitigs cover less than half of the {} bp genome",
+                genome_size
+            ),
+        }
+    }
+    run_stats.n50_bp = metrics.n50;
+    run_stats.n50_nodes = compress_graph::n50(&node_lengths);
+    info!("N50 unitig length: {} nodes", run_stats.n50_nodes);
+    run_stats.metrics = metrics;
+
+    Ok(Some(Assembly {
+        unitigs: compressed,
+        graph,
+        fastq_seqs,
+        read_aliases,
+        contained_in,
+        backbones,
+        singletons,
+        stats: run_stats,
+    }))
+}
+
+/// Write the outputs of an assembly to the output directory: the unitig FASTA and GFA, the reads2unitig,
+/// coverage, layout and backbones tables, graph.dot and, when requested, the singletons and the run statistics
+pub fn write_outputs(config: &configs::AssembleConfig, assembly: &Assembly) -> io::Result<()> {
+    let out_dir = Path::new(&config.output_dir);
+    std::fs::create_dir_all(out_dir)?;
+    let output_path = |name: &str| -> io::Result<String> {
+        out_dir
+            .join(format!("{}.{}", config.output_prefix, name))
+            .to_str()
+            .map(str::to_string)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid output path"))
+    };
+    let compressed = &assembly.unitigs;
+
+    info!("=== WRITING OUTPUT ===");
+    let backbones_path = output_path("backbones.tsv")?;
+    graph_analysis::write_backbones(Path::new(&backbones_path), &assembly.backbones)?;
+    info!("Wrote linear backbones to {}", backbones_path);
+
+    // write graph snapshot into output dir
+    let dot_path = out_dir.join("graph.dot");
+    assembly.graph.write_dot(&dot_path)?;
+    info!("Wrote graph visualization to {}", dot_path.display());
+
+    let fasta_path = output_path("fa")?;
+    compressed.write_fasta(&fasta_path).map_err(io_error)?;
+    info!(
+        "Assembly produced {} unitigs (written to {})",
+        assembly.stats.unitigs, fasta_path
+    );
+    let gfa_path = output_path("gfa")?;
+    compressed.write_gfa(&gfa_path).map_err(io_error)?;
+    info!("Wrote GFA to {}", gfa_path);
+    let mapping_path = output_path("reads2unitig.tsv")?;
+    compressed
+        .write_read_mapping(&mapping_path, &assembly.read_aliases)
+        .map_err(io_error)?;
+    info!("Wrote read to unitig mapping to {}", mapping_path);
+    let coverage_path = output_path("coverage.tsv")?;
+    compressed
+        .write_coverage(&coverage_path, &assembly.fastq_seqs)
+        .map_err(io_error)?;
+    info!("Wrote unitig coverage to {}", coverage_path);
+    let layout_path = output_path("layout.tsv")?;
+    compressed
+        .write_layout(&layout_path, &assembly.fastq_seqs)
+        .map_err(io_error)?;
+    info!("Wrote read layout to {}", layout_path);
+    if config.write_singletons {
+        let singletons_path = output_path("singletons.fasta")?;
+        compress_graph::write_reads_fasta(
+            Path::new(&singletons_path),
+            &assembly.singletons,
+            &assembly.fastq_seqs,
+        )?;
+        info!(
+            "{} reads are in no unitig and not contained in another read, written to {}",
+            assembly.singletons.len(),
+            singletons_path
+        );
+    }
+
+    if let Some(ref stats_path) = config.stats_json {
+        assembly.stats.write_json(stats_path).map_err(io_error)?;
+        info!("Wrote run statistics to {}", stats_path);
+    }
+    Ok(())
+}
+
+/// Dry run of the cleanup: report the candidates of the passes that can be evaluated without changing the graph.
+/// Every pass looks at the graph as it is now, during the actual cleanup each pass sees the result of the previous ones.
+fn report_cleanup_candidates(graph: &mut OverlapGraph, config: &configs::AssembleConfig) {
+    graph_analysis::ensure_synchronized(graph);
+    info!("=== DRY RUN, THE GRAPH IS NOT CHANGED ===");
+
+    let transitive = transitive_edge_reduction::find_transitive_edges(
+        graph,
+        config.fuzz,
+        !config.no_smallest_edge_rule,
+    );
+    for (from, to) in &transitive {
+        debug!("Transitive edge {} -> {}", from, to);
+    }
+    info!(
+        "Transitive edge reduction would remove {} edges",
+        transitive.len()
+    );
+
+    let bubble_weights = bubble_removal::BubbleScoringWeights {
+        overlap: config.bubble_overlap_weight,
+        identity: config.bubble_identity_weight,
+        read_count: config.bubble_read_count_weight,
+    };
+    let bubbles = bubble_removal::find_bubbles(
+        graph,
+        config.max_bubble_length as usize,
+        config.min_support_ratio,
+        &bubble_weights,
+    );
+    let mut bubble_nodes: HashSet<NodeIndex> = HashSet::new();
+    for bubble in &bubbles {
+        let mut removed: Vec<NodeIndex> = bubble.removed.iter().copied().collect();
+        removed.sort_unstable();
+        let join = |nodes: &[NodeIndex]| {
+            nodes
+                .iter()
+                .map(|&n| graph.node_id(n))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        debug!(
+            "Bubble at {}: keep {}, remove {}",
+            graph.node_id(bubble.source),
+            join(&bubble.kept),
+            join(&removed)
+        );
+        bubble_nodes.extend(removed);
+    }
+    info!(
+        "Bubble removal would pop {} simple bubbles, removing {} distinct nodes (superbubbles not included)",
+        bubbles.len(),
+        bubble_nodes.len()
+    );
+}
+
+/// Iterative graph cleanup of the assemble pipeline, every iteration runs all simplification steps
+/// in order and the loop stops early once an iteration changes nothing.
+fn run_cleanup(
+    graph: &mut OverlapGraph,
+    config: &configs::AssembleConfig,
+    snapshot_dir: Option<&Path>,
+    fastq_seqs: &HashMap<String, String>,
+    run_stats: &mut stats::RunStats,
+) -> io::Result<()> {
+    graph_analysis::ensure_synchronized(graph);
+
+    // tip length distribution, to help choosing the tip trimming cutoff
+    let tip_lengths =
+        graph_analysis::tip_length_distribution_bp(graph, fastq_seqs, graph.node_count());
+    let tip_bins = [0usize, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000];
+    info!("Tip length distribution ({} tips):", tip_lengths.len());
+    for (i, count) in graph_analysis::length_histogram(&tip_lengths, &tip_bins)
+        .iter()
+        .enumerate()
+    {
+        match tip_bins.get(i + 1) {
+            Some(upper) => info!("  {}-{} bp: {}", tip_bins[i], upper, count),
+            None => info!("  >={} bp: {}", tip_bins[i], count),
+        }
+    }
+    info!("=== STARTING GRAPH CLEANUP ===");
+    let max_bubble_len = config.max_bubble_length as usize;
+    let min_support_ratio = config.min_support_ratio;
+    let bubble_weights = bubble_removal::BubbleScoringWeights {
+        overlap: config.bubble_overlap_weight,
+        identity: config.bubble_identity_weight,
+        read_count: config.bubble_read_count_weight,
+    };
+    let max_tip_len = config.max_tip_len as usize;
+    let fuzz = config.fuzz;
+
+    // numbered graph snapshot after a cleanup step, only written with --keep-intermediate
+    let snapshot =
+        |graph: &OverlapGraph, iteration: u32, step: u32, name: &str| -> io::Result<()> {
+            let Some(snapshot_dir) = snapshot_dir else {
+                return Ok(());
+            };
+            let path = snapshot_dir.join(format!("iter{}_step{}_{}.gfa", iteration, step, name));
+            graph.write_gfa(&path)?;
+            debug!("Wrote graph snapshot to {}", path.display());
+            Ok(())
+        };
+
+    // cleanup_iterations is an upper bound, stop early once an iteration changes nothing
+    let mut iterations_run = 0u32;
+    for iteration in 1..=config.cleanup_iterations {
+        info!("=== Cleanup Iteration {} ===", iteration);
+        iterations_run = iteration;
+        let iteration_nodes_before = graph.node_count();
+        let iteration_edges_before = graph.edge_count();
+        // copy of the graph to diff against, only kept with --diff
+        let graph_before = config.diff.then(|| graph.clone());
+
+        // transitive edge reduction
+        if config.no_transitive_reduction {
+            info!("Skipped transitive edge reduction (--no-transitive-reduction)");
+        } else {
+            let edges_before = graph.edge_count();
+            transitive_edge_reduction::reduce_transitive_edges(
+                graph,
+                fuzz,
+                !config.no_smallest_edge_rule,
+            );
+            let edges_after = graph.edge_count();
+            info!(
+                "Removed {} edges with transitive edge reduction",
+                edges_before.saturating_sub(edges_after)
+            );
+            snapshot(graph, iteration, 1, "transitive")?;
+        }
+
+        // heuristic simplification: remove multi-edges
+        //println!("Applying heuristic simplification: removing multi-edges...");
+        let n_multi = heuristic_simplification::remove_multi_edges(graph);
+        info!("Removed {} multi-edges", n_multi);
+        let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+        info!(
+            "Pruned {} isolated nodes after removing multi-edges",
+            pruned
+        );
+        snapshot(graph, iteration, 2, "multi_edges")?;
+
+        graph_analysis::ensure_synchronized(graph);
+
+        //heuristic simplification: remove short edges
+        //println!("Applying heuristic simplification: removing short edges...");
+        if config.no_short_edge_removal {
+            info!("Skipped short edge removal (--no-short-edge-removal)");
+        } else {
+            let n_short =
+                heuristic_simplification::remove_short_edges(graph, config.short_edge_ratio);
+            info!("Removed {} short edges", n_short);
+            let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+            info!(
+                "Pruned {} isolated nodes after removing short edges",
+                pruned
+            );
+            snapshot(graph, iteration, 3, "short_edges")?;
+            graph_analysis::ensure_synchronized(graph);
+        }
+
+        // heuristic simplification: cut bi-loops and internal sequences
+        if config.max_biloop_ext > 0 {
+            let n_biloop =
+                heuristic_simplification::cut_biloop(graph, config.max_biloop_ext as usize);
+            info!("Cut {} bi-loop edges", n_biloop);
+            let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+            info!("Pruned {} isolated nodes after cutting bi-loops", pruned);
+            snapshot(graph, iteration, 4, "biloops")?;
+        }
+        if config.max_internal_ext > 0 {
+            let n_internal =
+                heuristic_simplification::cut_internal(graph, config.max_internal_ext as usize);
+            info!("Removed {} internal reads", n_internal);
+            let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+            info!(
+                "Pruned {} isolated nodes after cutting internal reads",
+                pruned
+            );
+            snapshot(graph, iteration, 5, "internal")?;
+        }
+        if config.remove_weak_edges {
+            let n_weak = heuristic_simplification::remove_weak(
+                graph,
+                config.weak_edge_ratio,
+                config.weak_edge_identity_tolerance,
+            );
+            info!("Removed {} weak edges", n_weak);
+            let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+            info!("Pruned {} isolated nodes after removing weak edges", pruned);
+            snapshot(graph, iteration, 6, "weak_edges")?;
+        }
+        if config.mask_repeats {
+            let repeats = graph_analysis::classify_repeats(
+                graph,
+                config.repeat_min_indegree,
+                config.repeat_min_outdegree,
+            );
+            if !repeats.is_empty() {
+                let repeat_nodes: HashSet<NodeIndex> =
+                    repeats.iter().filter_map(|id| graph.index(id)).collect();
+                let components_before = graph_analysis::weakly_connected_components(graph).len();
+                let n_masked = heuristic_simplification::isolate_nodes(graph, &repeat_nodes);
+                let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+                info!("Pruned {} isolated nodes after masking repeats", pruned);
+                let components_after = graph_analysis::weakly_connected_components(graph).len();
+                info!(
+                    "Masked {} repeat nodes (including RCs), removed {} edges, components {} -> {}",
+                    repeats.len(),
+                    n_masked,
+                    components_before,
+                    components_after
+                );
+            }
+            snapshot(graph, iteration, 7, "repeats")?;
+        }
+        graph_analysis::ensure_synchronized(graph);
+
+        // bubble removal
+        if config.no_bubble_removal {
+            info!("Skipped bubble removal (--no-bubble-removal)");
+        } else {
+            let node_count_before = graph.node_count();
+            bubble_removal::remove_bubbles(
+                graph,
+                max_bubble_len,
+                min_support_ratio,
+                &bubble_weights,
+            );
+            let node_count_after = graph.node_count();
+            info!(
+                "Removed {} bubble nodes (including RCs)",
+                node_count_before.saturating_sub(node_count_after)
+            );
+            snapshot(graph, iteration, 8, "bubbles")?;
+        }
+
+        // remove small components (<2)
+        let components = graph_analysis::weakly_connected_components(graph);
+        let mut comp_nodes_to_remove: HashSet<NodeIndex> = HashSet::new();
+        for component in components.iter() {
+            if component.len() < 2 {
+                comp_nodes_to_remove.extend(component.iter().copied());
+            }
+        }
+        let small_comp_count = comp_nodes_to_remove.len();
+        for &idx in comp_nodes_to_remove.iter() {
+            graph.remove_node_symmetric(idx);
+        }
+        info!(
+            "Removed {} oriented nodes from small components (<2)",
+            small_comp_count
+        );
+        snapshot(graph, iteration, 9, "small_components")?;
+
+        // tip trimming
+        if config.no_tip_trimming {
+            info!("Skipped tip trimming (--no-tip-trimming)");
+        } else {
+            let before_trim = graph.node_count();
+            tip_trimming::trim_tips(graph, max_tip_len);
+            let after_trim = graph.node_count();
+            info!(
+                "Removed {} nodes by tip trimming",
+                before_trim.saturating_sub(after_trim)
+            );
+            if config.max_tip_bp > 0 {
+                let removed = tip_trimming::trim_short_tips(
+                    graph,
+                    config.max_tip_bp_nodes as usize,
+                    config.max_tip_bp as usize,
+                    fastq_seqs,
+                );
+                info!(
+                    "Removed {} tip reads shorter than {} bp",
+                    removed, config.max_tip_bp
+                );
+            }
+            snapshot(graph, iteration, 10, "tips")?;
+        }
+        if config.min_tip_identity > 0.0 {
+            let removed = tip_trimming::trim_low_identity_tips(
+                graph,
+                config.max_low_identity_tip_len as usize,
+                config.min_tip_identity,
+            );
+            info!("Removed {} low identity tip reads", removed);
+            snapshot(graph, iteration, 11, "low_identity_tips")?;
+        }
+        if config.split_articulation_min_nodes > 0 {
+            let components = graph_analysis::weakly_connected_components(graph);
+            let cut_points = graph_analysis::tangle_cut_points(
+                graph,
+                &components,
+                config.split_articulation_min_nodes as usize,
+            );
+            if !cut_points.is_empty() {
+                let components_before = components.len();
+                let n_cut = heuristic_simplification::isolate_nodes(graph, &cut_points);
+                let pruned = heuristic_simplification::prune_isolated_nodes(graph);
+                info!(
+                    "Pruned {} isolated nodes after cutting at articulation points",
+                    pruned
+                );
+                let components_after = graph_analysis::weakly_connected_components(graph).len();
+                info!(
+                    "Cut the graph at {} articulation points (including RCs), removed {} edges, components {} -> {}",
+                    cut_points.len(),
+                    n_cut,
+                    components_before,
+                    components_after
+                );
+            }
+            snapshot(graph, iteration, 12, "articulation_points")?;
+        }
+
+        // graph statistics at the end of the iteration
+        let iteration_stats = stats::IterationStats::from_graph(iteration, graph);
+        info!(
+            "Iteration {}: {} nodes, {} edges, node to edge ratio {:.4}, {} nodes with out-degree > 1, {} nodes with out-degree >= 3",
+            iteration,
+            iteration_stats.nodes,
+            iteration_stats.edges,
+            iteration_stats.node_to_edge_ratio,
+            iteration_stats.out_degree_gt1,
+            iteration_stats.out_degree_ge3
+        );
+        let diff = graph_before
+            .as_ref()
+            .map(|graph_before| graph_analysis::graph_diff(graph_before, graph));
+        if let Some(ref diff) = diff {
+            info!(
+                "Iteration {} diff: {} nodes removed, {} added, {} edges removed, {} added, components {} -> {} ({:+})",
+                iteration,
+                diff.removed_nodes.len(),
+                diff.added_nodes.len(),
+                diff.removed_edges.len(),
+                diff.added_edges.len(),
+                diff.components_before,
+                diff.components_after,
+                diff.component_change()
+            );
+            for node in &diff.removed_nodes {
+                debug!("  removed node {}", node);
+            }
+            for node in &diff.added_nodes {
+                debug!("  added node {}", node);
+            }
+            for (from, to) in &diff.removed_edges {
+                debug!("  removed edge {} -> {}", from, to);
+            }
+            for (from, to) in &diff.added_edges {
+                debug!("  added edge {} -> {}", from, to);
+            }
+        }
+        // with --diff, an iteration that swaps edges without changing the counts is not converged
+        let converged = match diff {
+            Some(diff) => diff.is_empty(),
+            None => {
+                iteration_stats.nodes == iteration_nodes_before
+                    && iteration_stats.edges == iteration_edges_before
+            }
+        };
+        run_stats.iterations.push(iteration_stats);
+        if converged {
+            info!("Graph unchanged, stopping cleanup early");
+            break;
+        }
+    }
+
+    info!("=== GRAPH CLEANUP COMPLETE ===");
+    info!(
+        "Ran {} of at most {} cleanup iterations",
+        iterations_run, config.cleanup_iterations
+    );
+    Ok(())
+}
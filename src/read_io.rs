@@ -0,0 +1,94 @@
+/// Read input module
+/// load read sequences used to reconstruct unitig sequences
+use crate::utils;
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+/// Shorthand for an invalid FASTQ error
+fn invalid_fastq(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read a (possibly gzipped) FASTQ file with 4-line records.
+/// Returns a map from the bare read name (header up to the first whitespace, no orientation suffix) to the sequence.
+pub fn read_fastq(path: &str) -> io::Result<HashMap<String, String>> {
+    let reader = utils::open_reader(path)?;
+    let mut seq_map: HashMap<String, String> = HashMap::new();
+
+    // lines() grows its buffer as needed, so long reads are read in full
+    let mut lines = reader.lines();
+    while let Some(header) = lines.next() {
+        let header = header?;
+        let header = header.trim_end();
+        // tolerate empty lines between or after records
+        if header.is_empty() {
+            continue;
+        }
+        let Some(name) = header.strip_prefix('@') else {
+            return Err(invalid_fastq(format!(
+                "invalid FASTQ format in '{}': expected header line starting with '@', got '{}'",
+                path, header
+            )));
+        };
+        let name = name
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| invalid_fastq(format!("empty read name in '{}'", path)))?
+            .to_string();
+
+        // sequence, plus and quality lines
+        let mut record_line = |what: &str| -> io::Result<String> {
+            match lines.next() {
+                Some(line) => Ok(line?.trim_end().to_string()),
+                None => Err(invalid_fastq(format!(
+                    "invalid FASTQ format in '{}': missing {} line for read '{}'",
+                    path, what, name
+                ))),
+            }
+        };
+        let seq = record_line("sequence")?;
+        let plus = record_line("plus")?;
+        if !plus.starts_with('+') {
+            return Err(invalid_fastq(format!(
+                "invalid FASTQ format in '{}': expected '+' line for read '{}', got '{}'",
+                path, name, plus
+            )));
+        }
+        record_line("quality")?;
+
+        seq_map.insert(name, seq);
+    }
+
+    Ok(seq_map)
+}
+
+/// Find reads with an identical sequence.
+/// Returns a map from every duplicate read name to the representative of its group (the smallest name),
+/// representatives themselves are not in the map.
+pub fn duplicate_reads(reads: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut groups: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, seq) in reads {
+        groups.entry(seq.as_str()).or_default().push(name.as_str());
+    }
+
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    for mut names in groups.into_values().filter(|names| names.len() > 1) {
+        names.sort_unstable();
+        let representative = names[0];
+        for name in &names[1..] {
+            aliases.insert(name.to_string(), representative.to_string());
+        }
+    }
+    aliases
+}
+
+/// Build a map keyed by oriented node ids ("<read_name>+" and "<read_name>-").
+/// The '-' entry holds the reverse complement, so sequences can be looked up directly by node id.
+pub fn oriented_sequences(reads: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut oriented = HashMap::with_capacity(2 * reads.len());
+    for (name, seq) in reads {
+        oriented.insert(format!("{}+", name), seq.clone());
+        oriented.insert(format!("{}-", name), utils::rev_comp(seq));
+    }
+    oriented
+}
@@ -0,0 +1,116 @@
+/// Run statistics module
+/// collects machine-readable statistics while the pipeline runs, so they can be written as JSON
+use crate::alignment_filtering::FilteringStats;
+use crate::create_overlap_graph::OverlapGraph;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Graph statistics at the end of a single cleanup iteration
+#[derive(Serialize)]
+pub struct IterationStats {
+    pub iteration: u32,
+    pub nodes: usize,
+    pub edges: usize,
+    pub node_to_edge_ratio: f64,
+    pub out_degree_gt1: usize,
+    pub out_degree_ge3: usize,
+}
+
+impl IterationStats {
+    /// Collect the statistics of the current graph
+    pub fn from_graph(iteration: u32, graph: &OverlapGraph) -> Self {
+        let nodes = graph.node_count();
+        let edges = graph.edge_count();
+        // avoid NaN/inf in the JSON output when all edges are gone
+        let node_to_edge_ratio = if edges == 0 {
+            0.0
+        } else {
+            nodes as f64 / edges as f64
+        };
+        Self {
+            iteration,
+            nodes,
+            edges,
+            node_to_edge_ratio,
+            out_degree_gt1: graph.nodes().filter(|(_, n)| n.edges.len() > 1).count(),
+            out_degree_ge3: graph.nodes().filter(|(_, n)| n.edges.len() >= 3).count(),
+        }
+    }
+}
+
+/// Statistics of a whole run
+#[derive(Serialize, Default)]
+pub struct RunStats {
+    // None when precomputed overlaps were used
+    pub filtering: Option<FilteringStats>,
+    pub duplicate_reads: usize,
+    pub graph_nodes: usize,
+    pub graph_edges: usize,
+    pub iterations: Vec<IterationStats>,
+    pub final_nodes: usize,
+    pub final_edges: usize,
+    pub final_components: usize,
+    pub unitigs: usize,
+    // counted once per reverse complement pair, like the metrics
+    pub circular_unitigs: usize,
+    pub singleton_reads: usize,
+    pub n50_nodes: usize,
+    pub n50_bp: usize,
+    pub metrics: Metrics,
+}
+
+/// Contiguity metrics of an assembly, lengths in base pairs
+#[derive(Serialize, Default, Clone, Debug, PartialEq)]
+pub struct Metrics {
+    pub count: usize,
+    pub total: usize,
+    pub largest: usize,
+    pub n50: usize,
+    // number of unitigs of length >= N50
+    pub l50: usize,
+    // relative to the genome size, None without a genome size or if the unitigs cover less than half of it
+    pub ng50: Option<usize>,
+    pub lg50: Option<usize>,
+}
+
+// length and 1-based rank of the first of the lengths (sorted descending) at which they add up to half of `target`
+fn half_point(sorted: &[usize], target: usize) -> Option<(usize, usize)> {
+    let mut cumulative = 0usize;
+    for (i, &len) in sorted.iter().enumerate() {
+        cumulative += len;
+        if cumulative * 2 >= target {
+            return Some((len, i + 1));
+        }
+    }
+    None
+}
+
+/// N50, L50, largest, total and count of the unitig lengths, and NG50/LG50 if `genome_size` is given
+pub fn assembly_metrics(unitig_bp_lengths: &[usize], genome_size: Option<usize>) -> Metrics {
+    let mut sorted = unitig_bp_lengths.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let total: usize = sorted.iter().sum();
+    let (n50, l50) = half_point(&sorted, total).unwrap_or((0, 0));
+    let ng = genome_size.and_then(|size| half_point(&sorted, size));
+    Metrics {
+        count: sorted.len(),
+        total,
+        largest: sorted.first().copied().unwrap_or(0),
+        n50,
+        l50,
+        ng50: ng.map(|(len, _)| len),
+        lg50: ng.map(|(_, rank)| rank),
+    }
+}
+
+impl RunStats {
+    /// Write the statistics as pretty-printed JSON
+    pub fn write_json(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::create(path)?;
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+}
@@ -1,13 +1,14 @@
-use crate::create_overlap_graph::OverlapGraph;
+use crate::create_overlap_graph::{NodeIndex, OverlapGraph};
 use crate::utils;
 /// Tip trimming module
 /// a tip node is a node that has no incoming or no outgoing edge
 /// a mergeable node is a node with exactly one incoming and one outgoing edge
 /// tip trimming procedure:
-/// 1. find tip nodes (indegree == 0) (ignore nodes with outdegree == 0, they will be handled because their reverse-complement is a tip)
-/// 2. extend the tip node with mergeable nodes
-/// 3. remove the chain if it is shorter than n nodes
-use std::collections::HashSet;
+/// 1. find tip nodes (indegree == 0) and dead ends (outdegree == 0), dead ends are handled directly
+///    so they are trimmed even if their reverse-complement was already removed
+/// 2. extend the tip node with mergeable nodes (dead ends are extended backward)
+/// 3. remove the chain if it is shorter than n nodes, or optionally shorter than a number of bases
+use std::collections::{HashMap, HashSet};
 
 /// Enum for node classification
 #[derive(PartialEq)]
@@ -17,34 +18,56 @@ enum NodeType {
     Other = 2,     // other node types
 }
 
+/// Direction of a walk: forward follows outgoing edges, backward follows incoming edges
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
 /// Return the list of outgoing targets for node n that currently exist in the graph.
 /// (This filters out edges that point to missing nodes.)
-fn target_nodes(graph: &OverlapGraph, n: &str) -> Vec<String> {
-    if let Some(node) = graph.nodes.get(n) {
+fn target_nodes(graph: &OverlapGraph, n: NodeIndex) -> Vec<NodeIndex> {
+    if let Some(node) = graph.node(n) {
         node.edges
             .iter()
-            .map(|e| e.target_id.clone())
-            .filter(|tgt| graph.nodes.contains_key(tgt))
+            .map(|e| e.target)
+            .filter(|&tgt| graph.contains(tgt))
             .collect()
     } else {
         Vec::new()
     }
 }
 
-/// Classify nodes
-fn node_classification(graph: &OverlapGraph, n: &str) -> (NodeType, Option<String>) {
-    // count incoming edges to n by checking outgoing edges of rc(n)
-    let incoming = target_nodes(graph, &utils::rc_node(n));
-    let num_in = incoming.len();
-    let outgoing = target_nodes(graph, &n);
-    let num_out = outgoing.len();
+/// Return the list of sources of the incoming edges of node n.
+fn source_nodes(graph: &OverlapGraph, n: NodeIndex) -> Vec<NodeIndex> {
+    graph
+        .node(n)
+        .map(|node| node.in_edges().to_vec())
+        .unwrap_or_default()
+}
+
+/// Classify nodes, in the direction of the walk.
+/// Walking backward, a dead end (no outgoing edges, 1 incoming edge) is classified as a tip.
+fn node_classification(
+    graph: &OverlapGraph,
+    n: NodeIndex,
+    direction: Direction,
+) -> (NodeType, Option<NodeIndex>) {
+    // the actual incoming edges are used, so a missing reverse complement doesn't matter
+    let (behind, ahead) = match direction {
+        Direction::Forward => (source_nodes(graph, n), target_nodes(graph, n)),
+        Direction::Backward => (target_nodes(graph, n), source_nodes(graph, n)),
+    };
+    let num_in = behind.len();
+    let num_out = ahead.len();
     if num_in == 0 && num_out == 1 {
-        return (NodeType::Tip, Some(outgoing[0].clone()));
+        return (NodeType::Tip, Some(ahead[0]));
     }
     if num_in == 1 && num_out == 1 {
-        return (NodeType::Mergeable, Some(outgoing[0].clone()));
+        (NodeType::Mergeable, Some(ahead[0]))
     } else {
-        return (NodeType::Other, None);
+        (NodeType::Other, None)
     }
 }
 
@@ -52,22 +75,31 @@ fn node_classification(graph: &OverlapGraph, n: &str) -> (NodeType, Option<Strin
 /// - collects visited nodes into chain (first entry is the tip node)
 /// - returns the NodeType of the termination node (Mergeable if we reached max_ext, otherwise the non-mergeable type)
 /// - returns the chain vector (the sequence of visited nodes)
-fn extend(graph: &OverlapGraph, start_n: &str, max_ext: usize) -> (NodeType, Vec<String>) {
+fn extend(
+    graph: &OverlapGraph,
+    start_n: NodeIndex,
+    max_ext: usize,
+    direction: Direction,
+) -> (NodeType, Vec<NodeIndex>) {
     // initialize
-    let mut chain: Vec<String> = Vec::new();
-    let mut n = start_n.to_string();
+    let mut chain: Vec<NodeIndex> = Vec::new();
+
+    // a chain of length 0 can't be trimmed
+    if max_ext == 0 {
+        return (NodeType::Mergeable, chain);
+    }
     let mut steps_left = max_ext;
 
     // verify first node is a tip
-    let (node_type, next_opt) = node_classification(graph, &n);
+    let (node_type, next_opt) = node_classification(graph, start_n, direction);
     if node_type != NodeType::Tip {
         return (node_type, chain);
     }
-    chain.push(n);
+    chain.push(start_n);
     steps_left -= 1;
 
     // get the next node and start extending
-    let next = match next_opt {
+    let mut cur = match next_opt {
         Some(s) => s,
         None => return (NodeType::Mergeable, chain),
     };
@@ -75,7 +107,7 @@ fn extend(graph: &OverlapGraph, start_n: &str, max_ext: usize) -> (NodeType, Vec
     // loop instead of while to guarantee a return value
     loop {
         // classify current node
-        let (node_type, next_opt) = node_classification(graph, &next);
+        let (node_type, next_opt) = node_classification(graph, cur, direction);
 
         // non-mergeable -> return
         if node_type != NodeType::Mergeable {
@@ -93,46 +125,165 @@ fn extend(graph: &OverlapGraph, start_n: &str, max_ext: usize) -> (NodeType, Vec
             None => return (NodeType::Mergeable, chain),
         };
 
+        // a cycle of mergeable nodes is not a tip
+        if chain.contains(&next) {
+            return (NodeType::Mergeable, chain);
+        }
+
         // advance
-        chain.push(next.clone());
-        n = next;
+        chain.push(cur);
+        cur = next;
         steps_left -= 1;
     }
 }
 
+/// Find tip chains of at most max_ext nodes, from both ends:
+/// nodes without incoming edges are extended forward, dead ends (nodes without outgoing edges) are extended backward.
+/// Chains that reach max_ext or run into a cycle are not tips and are skipped.
+fn find_tips(graph: &OverlapGraph, max_ext: usize) -> Vec<(Direction, Vec<NodeIndex>)> {
+    let mut tips: Vec<(Direction, Vec<NodeIndex>)> = Vec::new();
+    for n in graph.node_indices() {
+        for direction in [Direction::Forward, Direction::Backward] {
+            // check if n is a Tip in this direction (only consider tips)
+            let (tip_type, _next) = node_classification(graph, n, direction);
+            if tip_type != NodeType::Tip {
+                continue;
+            }
+
+            // try to extend from n
+            let (ext_type, chain) = extend(graph, n, max_ext, direction);
+            // if extend returned Mergeable, skip (chain may be long, not a short tip)
+            if ext_type == NodeType::Mergeable {
+                continue;
+            }
+
+            tips.push((direction, chain));
+        }
+    }
+    tips
+}
+
+/// Mean identity of the edges along a tip chain, including the edge that connects the chain to the rest of the graph.
+/// Every chain node has exactly one edge in the walk direction.
+fn mean_tip_identity(
+    graph: &OverlapGraph,
+    direction: Direction,
+    chain: &[NodeIndex],
+) -> Option<f64> {
+    let identities: Vec<f64> = chain
+        .iter()
+        .filter_map(|&n| match direction {
+            Direction::Forward => graph.node(n)?.edges.first().map(|e| e.identity),
+            Direction::Backward => {
+                let source = source_nodes(graph, n).into_iter().next()?;
+                graph
+                    .node(source)?
+                    .edges
+                    .iter()
+                    .find(|e| e.target == n)
+                    .map(|e| e.identity)
+            }
+        })
+        .collect();
+    if identities.is_empty() {
+        return None;
+    }
+    Some(identities.iter().sum::<f64>() / identities.len() as f64)
+}
+
+/// Bases spelled by a tip chain: the edge lengths between its nodes plus the length of the last read of the path.
+/// Walking forward the path ends at the node next to the junction, walking backward at the dead end itself.
+/// None if a read is missing from the FASTQ sequences.
+fn tip_bp_length(
+    graph: &OverlapGraph,
+    direction: Direction,
+    chain: &[NodeIndex],
+    fastq_seqs: &HashMap<String, String>,
+) -> Option<usize> {
+    let read_len = |n: NodeIndex| fastq_seqs.get(graph.read_name(n)).map(|seq| seq.len());
+    let mut bp = match direction {
+        Direction::Forward => read_len(*chain.last()?)?,
+        Direction::Backward => read_len(*chain.first()?)?,
+    };
+    for pair in chain.windows(2) {
+        let (from, to) = match direction {
+            Direction::Forward => (pair[0], pair[1]),
+            Direction::Backward => (pair[1], pair[0]),
+        };
+        let edge = graph.node(from)?.edges.iter().find(|e| e.target == to)?;
+        bp += edge.edge_len as usize;
+    }
+    Some(bp)
+}
+
+/// Number of reads in a set of nodes, the two orientations of a read count once.
+/// A symmetric tip is found from both ends, as a forward tip and as the dead end of its reverse complement.
+fn read_count(nodes: &HashSet<NodeIndex>) -> usize {
+    nodes
+        .iter()
+        .map(|n| n >> 1)
+        .collect::<HashSet<NodeIndex>>()
+        .len()
+}
+
 /// tip trimming: remove any tip nodes and their reverse-complements from the graph.
+/// Tips are found from both ends: nodes without incoming edges are extended forward,
+/// dead ends (nodes without outgoing edges) are extended backward.
 pub fn trim_tips(graph: &mut OverlapGraph, max_ext: usize) {
-    // initialize collection of nodes to delete
-    let mut to_delete: HashSet<String> = HashSet::new();
-
-    // iterate over a snapshot of current node keys (no mutation while iterating)
-    let keys: Vec<String> = graph.nodes.keys().cloned().collect();
-    for n in keys.into_iter() {
-        // skip nodes that may already be deleted
-        if !graph.nodes.contains_key(&n) {
-            continue;
-        }
+    // the chain is small/terminating -> mark chain nodes for deletion
+    let to_delete: HashSet<NodeIndex> = find_tips(graph, max_ext)
+        .into_iter()
+        .flat_map(|(_, chain)| chain)
+        .collect();
 
-        // check if n is a Tip (only consider tips)
-        let (tip_type, _next) = node_classification(graph, &n);
-        if tip_type != NodeType::Tip {
-            continue;
-        }
+    if !to_delete.is_empty() {
+        utils::delete_nodes_and_edges(graph, &to_delete);
+    }
+}
 
-        // try to extend from n
-        let (ext_type, chain) = extend(graph, &n, max_ext);
-        // if extend returned Mergeable, skip deletion (chain may be long, not a short tip)
-        if ext_type == NodeType::Mergeable {
-            continue;
-        }
+/// Base pair tip trimming: remove tips of at most max_ext nodes that spell fewer than max_bp bases,
+/// however many reads they contain. Tips with a read missing from `fastq_seqs` are kept.
+/// Returns the number of reads in the removed tips, both orientations of a read are removed and counted once.
+pub fn trim_short_tips(
+    graph: &mut OverlapGraph,
+    max_ext: usize,
+    max_bp: usize,
+    fastq_seqs: &HashMap<String, String>,
+) -> usize {
+    let to_delete: HashSet<NodeIndex> = find_tips(graph, max_ext)
+        .into_iter()
+        .filter(|(direction, chain)| {
+            tip_bp_length(graph, *direction, chain, fastq_seqs).is_some_and(|bp| bp < max_bp)
+        })
+        .flat_map(|(_, chain)| chain)
+        .collect();
 
-        // otherwise the chain is small/terminating -> mark chain nodes for deletion
-        for node in chain.into_iter() {
-            to_delete.insert(node);
-        }
+    let removed = read_count(&to_delete);
+    if !to_delete.is_empty() {
+        utils::delete_nodes_and_edges(graph, &to_delete);
     }
+    removed
+}
+
+/// Low identity tip trimming: remove tips of at most max_ext nodes whose mean edge identity (percent)
+/// is below min_identity, regardless of their length.
+/// Returns the number of reads in the removed tips, both orientations of a read are removed and counted once.
+pub fn trim_low_identity_tips(
+    graph: &mut OverlapGraph,
+    max_ext: usize,
+    min_identity: f64,
+) -> usize {
+    let to_delete: HashSet<NodeIndex> = find_tips(graph, max_ext)
+        .into_iter()
+        .filter(|(direction, chain)| {
+            mean_tip_identity(graph, *direction, chain).is_some_and(|id| id < min_identity)
+        })
+        .flat_map(|(_, chain)| chain)
+        .collect();
 
+    let removed = read_count(&to_delete);
     if !to_delete.is_empty() {
         utils::delete_nodes_and_edges(graph, &to_delete);
     }
+    removed
 }
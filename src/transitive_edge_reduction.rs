@@ -2,10 +2,9 @@
 /// transitive edges are redundant edges that don't add any information to the graph
 /// Say read 1 overlaps with read 2 and read 2 overlaps with read 3 and read 1 also overlaps with read 3, then this last overlap is redundant, represented by a transitive edge
 /// Algorithm based on https://doi.org/10.1093/bioinformatics/bti1114
-use crate::create_overlap_graph::OverlapGraph;
-use crate::utils;
+use crate::create_overlap_graph::{EdgeInfo, NodeIndex, OverlapGraph};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 /// Enum for node marking (Vacant, In-play, Eliminated)
 #[derive(Copy, Clone, PartialEq)]
@@ -16,131 +15,137 @@ enum Mark {
 }
 
 /// Reduce transitive edges
-pub fn reduce_transitive_edges(g: &mut OverlapGraph, fuzz: u32) {
-    // prepare node list to iterate deterministically and avoid borrow conflicts
-    let node_keys: Vec<String> = g.nodes.keys().cloned().collect();
+/// The edges of `g` are sorted ascending by length, then the reduced edges are removed from `g`.
+/// `smallest_edge_rule` enables step 4 for the shortest outgoing edge of n2, see `find_transitive_edges`.
+pub fn reduce_transitive_edges(g: &mut OverlapGraph, fuzz: u32, smallest_edge_rule: bool) {
+    // keep the edges of the graph sorted ascending by length
+    for (_, node) in g.nodes_mut() {
+        node.sort_edges();
+    }
+
+    // remove all edges in a separate pass, the reverse complement counterpart goes with every edge
+    for (from, to) in transitive_edges(g, fuzz, smallest_edge_rule) {
+        g.remove_edge_symmetric(from, to);
+    }
+}
 
+/// Transitive edges of `g` as (from, to) node ids, without changing the graph.
+/// One entry per edge, so parallel edges are all listed.
+/// With `smallest_edge_rule` an edge n1->n3 is also reduced when n2->n3 is the shortest outgoing edge of n2,
+/// whatever the lengths of the paths. On noisy data this can remove edges the length check would keep.
+pub fn find_transitive_edges(
+    g: &OverlapGraph,
+    fuzz: u32,
+    smallest_edge_rule: bool,
+) -> Vec<(String, String)> {
+    transitive_edges(g, fuzz, smallest_edge_rule)
+        .into_iter()
+        .map(|(from, to)| (g.node_id(from), g.node_id(to)))
+        .collect()
+}
+
+/// Transitive edges of `g` as (from, to) node indices, in node index order
+fn transitive_edges(
+    g: &OverlapGraph,
+    fuzz: u32,
+    smallest_edge_rule: bool,
+) -> Vec<(NodeIndex, NodeIndex)> {
     // mark: per-node status (Vacant/InPlay/Eliminated)
-    let mut mark: HashMap<String, Mark> = HashMap::with_capacity(g.nodes.len());
+    let mut mark: Vec<Mark> = vec![Mark::Vacant; g.index_bound()];
 
     // reduced set: node pairs (from, to) that should be removed
-    let mut reduced: HashSet<(String, String)> = HashSet::new();
+    let mut reduced: HashSet<(NodeIndex, NodeIndex)> = HashSet::new();
 
-    // initialize all nodes as vacant and ensure edges are sorted ascending by length
-    for n in &node_keys {
-        mark.insert(n.clone(), Mark::Vacant);
-        if let Some(node) = g.nodes.get_mut(n) {
-            node.sort_edges();
-        }
-    }
+    // outgoing edges of n1 sorted ascending by length, the marking expects the shortest n2 first
+    let mut by_len: Vec<&EdgeInfo> = Vec::new();
 
     // main loop: For every node compare nodes encountered two steps into the future with those encountered one step into the future
-    for n1 in &node_keys {
-        // skip if node not present (may have been removed) or no outgoing edges
-        let out_edges = match g.nodes.get(n1) {
-            Some(node) => &node.edges,
-            None => continue,
-        };
+    for (n1, node) in g.nodes() {
+        // skip if node has no outgoing edges
+        let out_edges = &node.edges;
         if out_edges.is_empty() {
             continue;
         }
 
         // 1) mark all direct neighbors of n1 as InPlay
         for e in out_edges.iter() {
-            mark.insert(e.target_id.clone(), Mark::InPlay);
+            mark[e.target as usize] = Mark::InPlay;
         }
 
-        // 2) compute longest outgoing edge length from n1 (last after sort) + fuzz
+        // 2) compute longest outgoing edge length from n1 + fuzz
         let longest = {
-            let last_len = out_edges.last().unwrap().edge_len as u64;
-            last_len + fuzz as u64
+            let max_len = out_edges.iter().map(|e| e.edge_len).max().unwrap_or(0) as u64;
+            max_len + fuzz as u64
         };
 
-        // 3) For each n2 (outgoing from n1), check n2->n3 edges
-        for e_n2 in out_edges.iter() {
-            let n2 = &e_n2.target_id;
+        // 3) For each n2 (outgoing from n1), shortest edge first, check n2->n3 edges
+        by_len.clear();
+        by_len.extend(out_edges.iter());
+        by_len.sort_by_key(|e| e.edge_len);
+        for e_n2 in by_len.iter() {
+            let n2 = e_n2.target;
             let len_n1n2 = e_n2.edge_len;
 
             // skip n2 if not InPlay
-            if mark.get(n2).copied() != Some(Mark::InPlay) {
+            if mark[n2 as usize] != Mark::InPlay {
                 continue;
             }
 
-            // get node n2
-            if let Some(node2) = g.nodes.get(n2) {
-                for e_n3 in node2.edges.iter() {
-                    let n3 = &e_n3.target_id;
-                    let len_n2n3 = e_n3.edge_len;
-                    // if path length n1->n2->n3 <= longest then candidate for elimination
-                    let path_len = len_n2n3 as u64 + len_n1n2 as u64;
-                    if path_len <= longest {
-                        if mark.get(n3).copied() == Some(Mark::InPlay) {
-                            mark.insert(n3.clone(), Mark::Eliminated);
-                        }
-                    }
+            for e_n3 in g.node(n2).map_or(&[][..], |n| &n.edges) {
+                let n3 = e_n3.target as usize;
+                let len_n2n3 = e_n3.edge_len;
+                // if path length n1->n2->n3 <= longest then candidate for elimination
+                let path_len = len_n2n3 as u64 + len_n1n2 as u64;
+                if path_len <= longest && mark[n3] == Mark::InPlay {
+                    mark[n3] = Mark::Eliminated;
                 }
             }
         }
 
-        // 4) Additional rule: if n2->n3 is very small (< fuzz) or is the smallest outgoing edge of n2,
-        // then eliminate n3 if it is InPlay.
+        // 4) Additional rule: if n2->n3 is very small (< fuzz) or is the smallest outgoing edge of n2
+        // (only with smallest_edge_rule), then eliminate n3 if it is InPlay.
         for e in out_edges.iter() {
-            let n2 = &e.target_id;
-            if let Some(node2) = g.nodes.get(n2) {
-                // find min outgoing length for n2, if any
-                let min_len_opt = node2.edges.iter().map(|e| e.edge_len).min();
-
-                for e_n3 in node2.edges.iter() {
-                    let n3 = &e_n3.target_id;
-                    let len_n2n3 = e_n3.edge_len;
-                    let do_eliminate = if len_n2n3 < fuzz {
-                        true
-                    } else if let Some(min_len) = min_len_opt {
-                        len_n2n3 == min_len
-                    } else {
-                        false
-                    };
-
-                    if do_eliminate && mark.get(n3).copied() == Some(Mark::InPlay) {
-                        mark.insert(n3.clone(), Mark::Eliminated);
-                    }
+            let node2_edges = g.node(e.target).map_or(&[][..], |n| &n.edges);
+            // find min outgoing length for n2, if any
+            let min_len_opt = node2_edges.iter().map(|e| e.edge_len).min();
+
+            for e_n3 in node2_edges.iter() {
+                let n3 = e_n3.target as usize;
+                let len_n2n3 = e_n3.edge_len;
+                let do_eliminate = if len_n2n3 < fuzz {
+                    true
+                } else if let Some(min_len) = min_len_opt.filter(|_| smallest_edge_rule) {
+                    len_n2n3 == min_len
+                } else {
+                    false
+                };
+
+                if do_eliminate && mark[n3] == Mark::InPlay {
+                    mark[n3] = Mark::Eliminated;
                 }
             }
         }
 
         // 5) Mark edges from n1 to eliminated nodes for removal, then reset marks of the direct neighbors to vacant
         for e_n2 in out_edges.iter() {
-            let n2 = &e_n2.target_id;
-            if mark.get(n2).copied() == Some(Mark::Eliminated) {
-                reduced.insert((n1.clone(), n2.clone()));
+            let n2 = e_n2.target;
+            if mark[n2 as usize] == Mark::Eliminated {
+                reduced.insert((n1, n2));
             }
             // reset mark back to vacant for next iteration
-            mark.insert(n2.clone(), Mark::Vacant);
+            mark[n2 as usize] = Mark::Vacant;
         }
     } // end for n1
 
-    // 6) Actually remove reduced edges from the graph (and remove reverse-complement counterparts if present)
+    // 6) Collect the reduced edges, one entry per edge so parallel edges are all listed
+    let mut edges_to_remove: Vec<(NodeIndex, NodeIndex)> = Vec::new();
 
-    // Collect all edges to remove first
-    let mut edges_to_remove: Vec<(String, String)> = Vec::new();
-
-    for n1 in g.nodes.keys() {
-        if let Some(node) = g.nodes.get(n1) {
-            for e in &node.edges {
-                let n2 = &e.target_id;
-                if reduced.contains(&(n1.clone(), n2.clone())) {
-                    // Add both the edge and its reverse complement
-                    edges_to_remove.push((n1.clone(), n2.clone()));
-                    edges_to_remove.push((utils::rc_node(n2), utils::rc_node(n1)));
-                }
+    for (n1, node) in g.nodes() {
+        for e in &node.edges {
+            if reduced.contains(&(n1, e.target)) {
+                edges_to_remove.push((n1, e.target));
             }
         }
     }
-
-    // Now remove all edges in a separate pass
-    for (from, to) in edges_to_remove {
-        if let Some(node) = g.nodes.get_mut(&from) {
-            node.remove_edge(&to);
-        }
-    }
+    edges_to_remove
 }
@@ -1,55 +1,100 @@
-use crate::create_overlap_graph::OverlapGraph;
+use crate::create_overlap_graph::{NodeIndex, OverlapGraph};
 /// General functions used across the project
+use flate2::read::MultiGzDecoder;
+use log::warn;
 use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Split a node id into its read name and orientation ('+' or '-').
+/// Returns None for malformed ids: empty, without a read name, or without an orientation suffix.
+pub fn split_node_id(id: &str) -> Option<(&str, char)> {
+    let orientation = id.chars().last().filter(|o| matches!(o, '+' | '-'))?;
+    let name = &id[..id.len() - 1];
+    (!name.is_empty()).then_some((name, orientation))
+}
 
 /// Get the reverse-complement of a node (flip trailing '+' <-> '-').
-pub fn rc_node(id: &str) -> String {
-    if let Some(last) = id.chars().last() {
-        if last == '+' {
-            let base = &id[..id.len() - 1];
-            return format!("{}-", base);
-        } else if last == '-' {
-            let base = &id[..id.len() - 1];
-            return format!("{}+", base);
-        }
-    }
-    id.to_string()
+/// Returns None for malformed ids, like split_node_id.
+pub fn rc_node(id: &str) -> Option<String> {
+    let (name, orientation) = split_node_id(id)?;
+    Some(format!(
+        "{}{}",
+        name,
+        if orientation == '+' { '-' } else { '+' }
+    ))
 }
 
 /// Delete a set of nodes (both orientations) from the graph and remove associated edges
-pub fn delete_nodes_and_edges(graph: &mut OverlapGraph, nodes_to_delete: &HashSet<String>) {
-    // Initialize set of nodes to remove
-    let mut oriented_nodes_to_delete: HashSet<String> = HashSet::new();
-
-    for node in nodes_to_delete.iter() {
-        // add to set of nodes to delete
-        oriented_nodes_to_delete.insert(node.clone());
-        // add rc counterpart
-        oriented_nodes_to_delete.insert(rc_node(node));
+pub fn delete_nodes_and_edges(graph: &mut OverlapGraph, nodes_to_delete: &HashSet<NodeIndex>) {
+    for &node in nodes_to_delete.iter() {
+        graph.remove_node_symmetric(node);
     }
+}
 
-    // Delete nodes from graph.nodes
-    for oriented_node in oriented_nodes_to_delete.iter() {
-        graph.nodes.remove(oriented_node);
+/// Open a (possibly gzipped) text file for buffered reading, `-` reads from stdin.
+/// gzip input is detected by the `.gz` extension or the gzip magic bytes (1f 8b).
+/// The input is read in a single pass, so a pipe works as well as a file.
+pub fn open_reader(path: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(BufReader::new(io::stdin().lock()))
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    let is_gzip = path.ends_with(".gz") || reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    if is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
     }
+}
 
-    // Remove edges pointing to removed nodes
-    for (_src, node) in graph.nodes.iter_mut() {
-        node.edges
-            .retain(|e| !oriented_nodes_to_delete.contains(&e.target_id));
-    }
+/// Complement of a single base, including the IUPAC ambiguity codes.
+/// The case is kept, so soft-masked (lowercase) sequence stays masked. None for unknown characters.
+fn complement(c: char) -> Option<char> {
+    let upper = match c.to_ascii_uppercase() {
+        'A' => 'T',
+        'T' | 'U' => 'A',
+        'C' => 'G',
+        'G' => 'C',
+        'N' => 'N',
+        'R' => 'Y',
+        'Y' => 'R',
+        'S' => 'S',
+        'W' => 'W',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        '-' | '.' | '*' => c,
+        _ => return None,
+    };
+    Some(if c.is_ascii_lowercase() {
+        upper.to_ascii_lowercase()
+    } else {
+        upper
+    })
 }
 
+/// Reverse complement of a nucleotide sequence.
+/// Unknown characters are passed through unchanged, a warning is logged once per run.
 pub fn rev_comp(seq: &str) -> String {
+    static WARNED: AtomicBool = AtomicBool::new(false);
     seq.chars()
         .rev()
-        .map(|c| match c {
-            'A' | 'a' => 'T',
-            'T' | 't' => 'A',
-            'C' | 'c' => 'G',
-            'G' | 'g' => 'C',
-            'N' | 'n' => 'N',
-            _ => c,
+        .map(|c| {
+            complement(c).unwrap_or_else(|| {
+                if !WARNED.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "unknown base '{}' in a read sequence, kept unchanged in the reverse complement",
+                        c.escape_default()
+                    );
+                }
+                c
+            })
         })
         .collect()
 }
@@ -0,0 +1,99 @@
+/// Alignment filtering on PAF records that need special handling: self-alignments and coordinates past the
+/// read end.
+use ilesta::alignment_filtering::{
+    AlignmentFilteringOutput, FilteringOptions, run_alignment_filtering,
+};
+use ilesta::create_overlap_graph::run_create_overlap_graph;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Both PAF lines of an alignment between 2000 bp reads on the forward strand, one from each read's perspective
+fn paf_lines(
+    query: &str,
+    query_range: (u32, u32),
+    target: &str,
+    target_range: (u32, u32),
+) -> String {
+    let line = |q: &str, (q_start, q_end): (u32, u32), t: &str, (t_start, t_end): (u32, u32)| {
+        let block = (q_end - q_start).max(t_end - t_start);
+        format!(
+            "{}\t2000\t{}\t{}\t+\t{}\t2000\t{}\t{}\t{}\t{}\t60\ttp:A:P\n",
+            q, q_start, q_end, t, t_start, t_end, block, block
+        )
+    };
+    line(query, query_range, target, target_range) + &line(target, target_range, query, query_range)
+}
+
+/// a, b, c and d tile 3500 bp with a step of 500 bp. The coverage trimming makes d contained in c
+fn tiled_reads() -> String {
+    paf_lines("a", (500, 2000), "b", (0, 1500))
+        + &paf_lines("b", (500, 2000), "c", (0, 1500))
+        + &paf_lines("c", (500, 2000), "d", (0, 1500))
+        + &paf_lines("a", (1000, 2000), "c", (0, 1000))
+        + &paf_lines("b", (1000, 2000), "d", (0, 1000))
+}
+
+/// Filter the PAF with a minimum overlap of 500 bp and a minimum coverage of 1
+fn filter(name: &str, paf: &str) -> AlignmentFilteringOutput {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.paf", name));
+    fs::write(&path, paf).unwrap();
+    let options = FilteringOptions {
+        min_overlap_length: 500,
+        min_overlap_count: 1,
+        self_overlaps_as_repeats: true,
+        ..FilteringOptions::default()
+    };
+    run_alignment_filtering(path.to_str().unwrap(), &options, &HashMap::new()).unwrap()
+}
+
+#[test]
+fn off_diagonal_self_alignments_mark_a_repeat() {
+    // b aligns to itself at a 500 bp offset, reported from both sides; c only has its trivial diagonal
+    let paf = tiled_reads()
+        + "b\t2000\t0\t1000\t+\tb\t2000\t500\t1500\t1000\t1000\t60\n"
+        + "b\t2000\t500\t1500\t+\tb\t2000\t0\t1000\t1000\t1000\t60\n"
+        + "c\t2000\t0\t2000\t+\tc\t2000\t0\t2000\t2000\t2000\t60\n";
+    let filtering = filter("self_alignments", &paf);
+    assert_eq!(filtering.stats.self_alignments_skipped, 3);
+    assert!(filtering.self_alignments["b"].is_repeat());
+    assert!(!filtering.self_alignments["c"].is_repeat());
+
+    // both orientations of b are annotated
+    let graph = run_create_overlap_graph(filtering, 0.0).unwrap();
+    for (node, is_repeat) in [
+        ("b+", true),
+        ("b-", true),
+        ("c+", false),
+        ("c-", false),
+        ("a+", false),
+    ] {
+        assert_eq!(
+            graph.node(graph.index(node).unwrap()).unwrap().is_repeat,
+            is_repeat,
+            "{}",
+            node
+        );
+    }
+}
+
+#[test]
+fn alignment_past_the_read_end_is_clamped() {
+    // the a/b alignment reports a query end 50 bp past the end of a
+    let paf = tiled_reads().replacen("a\t2000\t500\t2000\t", "a\t2000\t500\t2050\t", 1);
+    assert!(paf.contains("\t2050\t"));
+    let filtering = filter("past_read_end", &paf);
+
+    // the record is kept and merged with the line reported from b's side, the pair still overlaps
+    assert_eq!(filtering.stats.alignments_read, 10);
+    assert_eq!(filtering.stats.alignments_kept, 5);
+    let graph = run_create_overlap_graph(filtering, 0.0).unwrap();
+    let edge = graph
+        .node(graph.index("a+").unwrap())
+        .unwrap()
+        .edges
+        .iter()
+        .find(|e| graph.node_id(e.target) == "b+")
+        .expect("a+ -> b+");
+    assert_eq!(edge.edge_len, 500);
+}
@@ -0,0 +1,52 @@
+//! Helpers to build small overlap graphs for the library tests
+// every test crate uses a subset of the helpers
+#![allow(dead_code)]
+
+use ilesta::Overlap;
+use ilesta::create_overlap_graph::OverlapGraph;
+use ilesta::utils::rc_node;
+
+/// Overlap between two oriented reads, the reverse complement nodes are derived from the names
+pub fn overlap(
+    source: &str,
+    sink: &str,
+    edge_len: u32,
+    rc_edge_len: u32,
+    overlap_len: u32,
+) -> Overlap {
+    let flip = |node: &str| rc_node(node).expect("oriented node id");
+    Overlap {
+        source_name: source.to_string(),
+        sink_name: sink.to_string(),
+        rc_source_name: flip(sink),
+        rc_sink_name: flip(source),
+        edge_len,
+        rc_edge_len,
+        overlap_len,
+        identity: 99.0,
+    }
+}
+
+/// All edges of the graph as sorted (from, to) pairs
+pub fn edges(graph: &OverlapGraph) -> Vec<(String, String)> {
+    let mut edges: Vec<(String, String)> = graph
+        .nodes()
+        .flat_map(|(idx, node)| {
+            node.edges
+                .iter()
+                .map(move |e| (graph.node_id(idx), graph.node_id(e.target)))
+        })
+        .collect();
+    edges.sort();
+    edges
+}
+
+/// Sorted (from, to) pairs, to compare with `edges`
+pub fn pairs(edges: &[(&str, &str)]) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = edges
+        .iter()
+        .map(|(from, to)| (from.to_string(), to.to_string()))
+        .collect();
+    pairs.sort();
+    pairs
+}
@@ -0,0 +1,87 @@
+/// Unitig post-processing on the library API
+use ilesta::compress_graph::{CompressedGraph, Unitig, UnitigEdge, UnitigMember};
+
+/// Linear unitig over the given nodes, every member links to the next one with a 500 bp edge
+fn unitig(id: usize, nodes: &[&str]) -> Unitig {
+    let members = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| UnitigMember {
+            node_id: node.to_string(),
+            edge: nodes
+                .get(i + 1)
+                .map_or((String::new(), 0), |next| (next.to_string(), 500)),
+        })
+        .collect();
+    Unitig {
+        id,
+        members,
+        fasta_seq: Some("ACGT".to_string()),
+        is_circular: false,
+        coverage: Some(10.0),
+    }
+}
+
+fn unitig_edge(from: usize, to: usize) -> UnitigEdge {
+    UnitigEdge {
+        from,
+        to,
+        edge_len: 500,
+        overlap_len: 1500,
+        identity: 99.0,
+    }
+}
+
+/// The palindromic unitig 0 runs through x+, y+ and back through y-, x-, it is linked to unitig 1 on both sides
+fn palindromic_graph() -> CompressedGraph {
+    CompressedGraph {
+        unitigs: vec![
+            unitig(0, &["x+", "y+", "y-", "x-"]),
+            unitig(1, &["a+", "b+"]),
+        ],
+        edges: vec![unitig_edge(1, 0), unitig_edge(0, 1)],
+    }
+}
+
+fn members(unitig: &Unitig) -> Vec<(&str, &str)> {
+    unitig
+        .members
+        .iter()
+        .map(|m| (m.node_id.as_str(), m.edge.0.as_str()))
+        .collect()
+}
+
+#[test]
+fn palindromic_unitig_is_split_before_the_reverse_complement() {
+    let mut compressed = palindromic_graph();
+    assert_eq!(compressed.resolve_palindromic_unitigs(false), 1);
+
+    let ids: Vec<usize> = compressed.unitigs.iter().map(|u| u.id).collect();
+    assert_eq!(ids, [0, 1, 2]);
+    // the first part loses its edge into y-, the second part gets a fresh id
+    assert_eq!(members(&compressed.unitigs[0]), [("x+", "y+"), ("y+", "")]);
+    assert_eq!(members(&compressed.unitigs[2]), [("y-", "x-"), ("x-", "")]);
+    for part in [&compressed.unitigs[0], &compressed.unitigs[2]] {
+        assert_eq!(part.fasta_seq, None);
+        assert_eq!(part.coverage, None);
+    }
+    assert_eq!(compressed.unitigs[1].fasta_seq.as_deref(), Some("ACGT"));
+
+    // the edge into the unitig still enters its first part, the edge out of it now leaves the last part
+    let edges: Vec<(usize, usize)> = compressed.edges.iter().map(|e| (e.from, e.to)).collect();
+    assert_eq!(edges, [(1, 0), (2, 1)]);
+
+    // nothing left to resolve
+    assert_eq!(compressed.resolve_palindromic_unitigs(false), 0);
+}
+
+#[test]
+fn palindromic_unitig_is_dropped_with_its_edges() {
+    let mut compressed = palindromic_graph();
+    assert_eq!(compressed.resolve_palindromic_unitigs(true), 1);
+
+    assert_eq!(compressed.unitigs.len(), 1);
+    assert_eq!(compressed.unitigs[0].id, 1);
+    assert_eq!(members(&compressed.unitigs[0]), [("a+", "b+"), ("b+", "")]);
+    assert!(compressed.edges.is_empty());
+}
@@ -0,0 +1,73 @@
+/// Graph measurements on the library API.
+mod common;
+
+use common::overlap;
+use ilesta::create_overlap_graph::build_overlap_graph_from;
+use ilesta::graph_analysis::{tip_length_distribution_bp, to_dot};
+use std::collections::{HashMap, HashSet};
+
+/// 1000 bp reads
+fn reads(names: &[&str]) -> HashMap<String, String> {
+    names
+        .iter()
+        .map(|name| (name.to_string(), "A".repeat(1000)))
+        .collect()
+}
+
+#[test]
+fn tip_lengths_count_the_edges_and_the_last_read() {
+    // a+ -> b+ -> c+, both strands are a single tip of 400 + 400 + 1000 bp
+    let graph = build_overlap_graph_from(
+        &[
+            overlap("a+", "b+", 400, 400, 600),
+            overlap("b+", "c+", 400, 400, 600),
+        ],
+        false,
+    );
+    let mut lengths = tip_length_distribution_bp(&graph, &reads(&["a", "b", "c"]), 10);
+    lengths.sort_unstable();
+    assert_eq!(lengths, [1800, 1800]);
+
+    // d+ joins at b+, the tips a+ and d+ end before the junction, c- -> b- ends at the branching b-
+    let graph = build_overlap_graph_from(
+        &[
+            overlap("a+", "b+", 400, 400, 600),
+            overlap("b+", "c+", 400, 400, 600),
+            overlap("d+", "b+", 400, 400, 600),
+        ],
+        false,
+    );
+    let mut lengths = tip_length_distribution_bp(&graph, &reads(&["a", "b", "c", "d"]), 10);
+    lengths.sort_unstable();
+    assert_eq!(lengths, [1000, 1000, 1400]);
+
+    // a tip that ends in a read without sequence is skipped
+    let mut lengths = tip_length_distribution_bp(&graph, &reads(&["a", "b", "c"]), 10);
+    lengths.sort_unstable();
+    assert_eq!(lengths, [1000, 1400]);
+}
+
+#[test]
+fn dot_export_keeps_the_orientation_and_only_edges_inside_the_subset() {
+    // a+ -> b- -> c+, the subset {a+, b-} drops c+ and the edge b- -> c+
+    let graph = build_overlap_graph_from(
+        &[
+            overlap("a+", "b-", 400, 300, 600),
+            overlap("b-", "c+", 200, 400, 600),
+        ],
+        false,
+    );
+    let subset: HashSet<String> = ["a+", "b-"].iter().map(|n| n.to_string()).collect();
+    let expected = [
+        "digraph Subgraph {",
+        "  rankdir=LR;",
+        "  node [shape=box fontname=\"Helvetica\"];",
+        "  \"a+\";",
+        "  \"b-\";",
+        "  \"a+\" -> \"b-\" [label=\"len=400 id=99.000\"];",
+        "}",
+        "",
+    ]
+    .join("\n");
+    assert_eq!(to_dot(&graph, &subset), expected);
+}
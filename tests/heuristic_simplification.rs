@@ -0,0 +1,52 @@
+/// Heuristic simplification on the library API: edges are removed together with their reverse complement
+/// counterpart, so the bigraph stays synchronized.
+mod common;
+
+use common::{edges, overlap, pairs};
+use ilesta::create_overlap_graph::build_overlap_graph_from;
+use ilesta::graph_analysis::check_synchronization;
+use ilesta::remove_multi_edges;
+
+#[test]
+fn remove_edge_symmetric_keeps_the_graph_synchronized() {
+    let mut graph = build_overlap_graph_from(
+        &[
+            overlap("a+", "b-", 300, 300, 700),
+            overlap("b-", "c+", 300, 300, 700),
+        ],
+        false,
+    );
+    assert_eq!(check_synchronization(&graph), Ok(()));
+
+    let [a_fwd, a_rev, b_fwd, b_rev] = ["a+", "a-", "b+", "b-"].map(|id| graph.index(id).unwrap());
+    assert!(graph.remove_edge_symmetric(a_fwd, b_rev));
+    assert_eq!(check_synchronization(&graph), Ok(()));
+    assert_eq!(edges(&graph), pairs(&[("b-", "c+"), ("c-", "b+")]));
+    assert_eq!(graph.indegree(a_rev), 0);
+    assert_eq!(graph.indegree(b_rev), 0);
+
+    // the counterpart is gone as well, removing it again does nothing
+    assert!(!graph.remove_edge_symmetric(b_fwd, a_rev));
+    assert_eq!(check_synchronization(&graph), Ok(()));
+}
+
+#[test]
+fn multi_edges_keep_the_longest_overlap_on_both_strands() {
+    let mut graph = build_overlap_graph_from(
+        &[
+            overlap("a+", "b+", 600, 650, 400),
+            overlap("a+", "b+", 300, 350, 700),
+            overlap("a+", "b+", 500, 550, 500),
+        ],
+        true,
+    );
+    assert_eq!(edges(&graph).len(), 6);
+
+    assert_eq!(remove_multi_edges(&mut graph), 4);
+    assert_eq!(check_synchronization(&graph), Ok(()));
+    assert_eq!(edges(&graph), pairs(&[("a+", "b+"), ("b-", "a-")]));
+    let kept = &graph.node(graph.index("a+").unwrap()).unwrap().edges[0];
+    assert_eq!((kept.edge_len, kept.overlap_len), (300, 700));
+    let kept_rc = &graph.node(graph.index("b-").unwrap()).unwrap().edges[0];
+    assert_eq!((kept_rc.edge_len, kept_rc.overlap_len), (350, 700));
+}
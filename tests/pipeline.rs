@@ -0,0 +1,1167 @@
+/// End-to-end test of the assembler: PAF + FASTQ -> filtering -> graph -> cleanup -> unitigs
+/// A 4 kb genome is tiled by 5 reads, a variant read `x` spans the same region as r3 and forms a bubble.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const READ_LEN: usize = 2000;
+const READ_STEP: usize = 500;
+
+/// Deterministic pseudo-random genome (xorshift)
+fn genome(len: usize) -> String {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            b"ACGT"[(state % 4) as usize] as char
+        })
+        .collect()
+}
+
+fn rev_comp(seq: &str) -> String {
+    seq.chars()
+        .rev()
+        .map(|c| match c {
+            'A' => 'T',
+            'C' => 'G',
+            'G' => 'C',
+            _ => 'A',
+        })
+        .collect()
+}
+
+/// Write the reads and an all-vs-all PAF of their overlaps.
+/// Alignments involving `x` get 80% identity, so the bubble is resolved in favour of r3.
+/// `x` and r3 are not aligned to each other, they would be mutual containments.
+fn write_input(dir: &Path, genome: &str) {
+    let mut reads: Vec<(String, usize, String)> = (0..5)
+        .map(|i| {
+            let start = i * READ_STEP;
+            (
+                format!("r{}", i + 1),
+                start,
+                genome[start..start + READ_LEN].to_string(),
+            )
+        })
+        .collect();
+    let mut variant: Vec<u8> = genome[1000..1000 + READ_LEN].bytes().collect();
+    for pos in [500, 1000, 1500] {
+        variant[pos] = if variant[pos] == b'A' { b'C' } else { b'A' };
+    }
+    reads.push(("x".to_string(), 1000, String::from_utf8(variant).unwrap()));
+
+    let mut fastq = String::new();
+    for (name, _, seq) in &reads {
+        fastq.push_str(&format!(
+            "@{}\n{}\n+\n{}\n",
+            name,
+            seq,
+            "I".repeat(seq.len())
+        ));
+    }
+    fs::write(dir.join("reads.fq"), fastq).unwrap();
+
+    let mut paf = String::new();
+    for (a, start_a, _) in &reads {
+        for (b, start_b, _) in &reads {
+            // every pair once, a starts before b
+            if start_a > start_b || (start_a == start_b && a >= b) {
+                continue;
+            }
+            if (a == "r3" && b == "x") || (a == "x" && b == "r3") {
+                continue;
+            }
+            let offset = start_b - start_a;
+            if offset >= READ_LEN {
+                continue;
+            }
+            let overlap = READ_LEN - offset;
+            let matching = if a == "x" || b == "x" {
+                overlap * 4 / 5
+            } else {
+                overlap
+            };
+            for (q, q_start, t, t_start) in [(a, offset, b, 0), (b, 0, a, offset)] {
+                paf.push_str(&format!(
+                    "{}\t{}\t{}\t{}\t+\t{}\t{}\t{}\t{}\t{}\t{}\t60\ttp:A:P\n",
+                    q,
+                    READ_LEN,
+                    q_start,
+                    q_start + overlap,
+                    t,
+                    READ_LEN,
+                    t_start,
+                    t_start + overlap,
+                    matching,
+                    overlap
+                ));
+            }
+        }
+    }
+    fs::write(dir.join("ovl.paf"), paf).unwrap();
+}
+
+/// Write a GFA graph of the segments (name, sequence) and links (from, to, overlap), all on the forward strand,
+/// and the segments as reads
+fn write_gfa_input(dir: &Path, segments: &[(&str, &str)], links: &[(&str, &str, u32)]) {
+    let mut gfa = String::from("H\tVN:Z:1.0\n");
+    let mut fastq = String::new();
+    for (name, seq) in segments {
+        gfa.push_str(&format!("S\t{}\t{}\n", name, seq));
+        fastq.push_str(&format!(
+            "@{}\n{}\n+\n{}\n",
+            name,
+            seq,
+            "I".repeat(seq.len())
+        ));
+    }
+    for (from, to, overlap) in links {
+        gfa.push_str(&format!("L\t{}\t+\t{}\t+\t{}M\n", from, to, overlap));
+    }
+    fs::write(dir.join("graph.gfa"), gfa).unwrap();
+    fs::write(dir.join("reads.fq"), fastq).unwrap();
+}
+
+/// Empty scratch directory of a test, left over output of an earlier run is removed
+fn fresh_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run the binary in `dir`, so the arguments can use paths relative to it. The log is captured at info level
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_Ilesta"))
+        .args(args)
+        .current_dir(dir)
+        .env("RUST_LOG", "info")
+        .output()
+        .unwrap()
+}
+
+/// Run the assemble command on the reads in `dir`, writing to a fresh `dir/out`.
+/// `args` name the input (`-f ovl.paf`, `--input-gfa graph.gfa`, ...) and the options
+fn run_assemble(dir: &Path, args: &[&str]) -> Output {
+    let _ = fs::remove_dir_all(dir.join("out"));
+    let mut all_args = vec!["assemble", "-r", "reads.fq", "-o", "out"];
+    all_args.extend_from_slice(args);
+    run(dir, &all_args)
+}
+
+/// Captured log of a run
+fn log(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Forward strand nodes of the read graph written as dot, sorted
+fn forward_nodes(dir: &Path) -> Vec<String> {
+    let dot = fs::read_to_string(dir.join("out").join("graph.dot")).unwrap();
+    let mut nodes: Vec<String> = dot
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with('"') && !l.contains("->"))
+        .filter_map(|l| l.split('"').nth(1))
+        .filter(|n| n.ends_with('+'))
+        .map(str::to_string)
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+/// Parse a FASTA file into (header, sequence) records
+fn read_fasta(path: &Path) -> Vec<(String, String)> {
+    let mut records: Vec<(String, String)> = Vec::new();
+    for line in fs::read_to_string(path).unwrap().lines() {
+        if let Some(header) = line.strip_prefix('>') {
+            records.push((header.to_string(), String::new()));
+        } else if let Some(record) = records.last_mut() {
+            record.1.push_str(line.trim());
+        }
+    }
+    records
+}
+
+#[test]
+fn assembles_linear_reads_and_pops_bubble() {
+    let dir = fresh_dir("pipeline_linear_bubble");
+    let genome = genome(4 * READ_STEP + READ_LEN);
+    write_input(&dir, &genome);
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "-f",
+            "ovl.paf",
+            "-c",
+            "1",
+            "-l",
+            "500",
+            "--max-tip-len",
+            "0",
+            "--write-singletons",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+    let out_dir = dir.join("out");
+
+    // the path r1 -> r4 and its reverse complement, r5 loses its uncovered tail in the coverage
+    // trimming and ends up contained in r4
+    let unitigs = read_fasta(&out_dir.join("unitigs.fa"));
+    assert_eq!(unitigs.len(), 2, "unitigs: {:?}", unitigs);
+    let expected = &genome[..3 * READ_STEP + READ_LEN];
+    let mut sequences: Vec<&str> = unitigs.iter().map(|(_, seq)| seq.as_str()).collect();
+    sequences.sort_unstable_by_key(|seq| *seq != expected);
+    assert_eq!(sequences[0], expected);
+    assert_eq!(sequences[1], rev_comp(expected));
+
+    // the bubble read x was popped, r3 is kept in the unitig between r2 and r4
+    let mapping = fs::read_to_string(out_dir.join("unitigs.reads2unitig.tsv")).unwrap();
+    let positions: HashMap<&str, &str> = mapping
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            Some((*fields.first()?, *fields.get(3)?))
+        })
+        .collect();
+    let expected_positions: HashMap<&str, &str> =
+        HashMap::from([("r1", "0"), ("r2", "1"), ("r3", "2"), ("r4", "3")]);
+    assert_eq!(positions, expected_positions);
+
+    // both strands resolved into a line
+    let backbones = fs::read_to_string(out_dir.join("unitigs.backbones.tsv")).unwrap();
+    let paths: Vec<&str> = backbones
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split('\t').nth(2))
+        .collect();
+    assert_eq!(paths, ["r1+,r2+,r3+,r4+", "r4-,r3-,r2-,r1-"]);
+
+    // the popped read is a singleton, r5 is contained in r4
+    let singletons = read_fasta(&out_dir.join("unitigs.singletons.fasta"));
+    let names: Vec<&str> = singletons
+        .iter()
+        .filter_map(|(header, _)| header.split(' ').next())
+        .collect();
+    assert_eq!(names, ["x"]);
+
+    // x is aligned to every read but r3, the reads at the ends of the genome miss one neighbour
+    let degrees = fs::read_to_string(out_dir.join("unitigs.overlaps_per_read.tsv")).unwrap();
+    assert_eq!(
+        degrees,
+        "read_name\tdegree\nr1\t4\nr2\t5\nr3\t4\nr4\t5\nr5\t4\nx\t4\n"
+    );
+}
+
+/// The library entry point runs the same pipeline in memory, the output directory is never created
+#[test]
+fn library_assemble_writes_no_files() {
+    use clap::Parser;
+    use ilesta::cli::{Cli, Commands};
+
+    let dir = fresh_dir("pipeline_library");
+    let genome = genome(4 * READ_STEP + READ_LEN);
+    write_input(&dir, &genome);
+
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let (paf, reads, out, stats) = (
+        path("ovl.paf"),
+        path("reads.fq"),
+        path("out"),
+        path("stats.json"),
+    );
+    let cli = Cli::parse_from([
+        "Ilesta",
+        "assemble",
+        "-f",
+        &paf,
+        "-r",
+        &reads,
+        "-o",
+        &out,
+        "-c",
+        "1",
+        "-l",
+        "500",
+        "--max-tip-len",
+        "0",
+        "--write-singletons",
+        "--stats-json",
+        &stats,
+    ]);
+    let Commands::Assemble(args) = cli.command else {
+        panic!("not the assemble command");
+    };
+    let config: ilesta::AssembleConfig = args.as_ref().into();
+    let compressed = ilesta::assemble(&config).unwrap();
+
+    // the same two unitigs as the assemble command, r1 -> r4 and its reverse complement
+    let expected = &genome[..3 * READ_STEP + READ_LEN];
+    let mut sequences: Vec<&str> = compressed
+        .unitigs
+        .iter()
+        .filter_map(|u| u.fasta_seq.as_deref())
+        .collect();
+    sequences.sort_unstable_by_key(|seq| *seq != expected);
+    assert_eq!(sequences, [expected.to_string(), rev_comp(expected)]);
+
+    let mut files: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["ovl.paf", "reads.fq"]);
+}
+
+/// Bubble whose path overlaps sum past u32::MAX, the path scoring must not overflow.
+/// Segments only carry an LN:i tag, the unitig sequences are skipped but the cleanup runs in full.
+#[test]
+fn pops_bubble_with_huge_overlaps() {
+    let dir = fresh_dir("pipeline_huge_overlaps");
+
+    // s -> a -> t and s -> b -> t, the path through a has the larger overlaps
+    let mut gfa = String::from("H\tVN:Z:1.0\n");
+    for segment in ["s", "a", "b", "t"] {
+        gfa.push_str(&format!("S\t{}\t*\tLN:i:4000000000\n", segment));
+    }
+    for (from, to, overlap) in [
+        ("s", "a", 3_999_999_000u32),
+        ("a", "t", 3_999_999_000),
+        ("s", "b", 3_999_998_000),
+        ("b", "t", 3_999_998_000),
+    ] {
+        gfa.push_str(&format!("L\t{}\t+\t{}\t+\t{}M\n", from, to, overlap));
+    }
+    fs::write(dir.join("graph.gfa"), gfa).unwrap();
+    fs::write(dir.join("reads.fq"), "@s\nA\n+\nI\n").unwrap();
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "--input-gfa",
+            "graph.gfa",
+            "--min-support-ratio",
+            "1",
+            "--max-tip-len",
+            "0",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+
+    // b was popped, a is kept
+    let dot = fs::read_to_string(dir.join("out").join("graph.dot")).unwrap();
+    assert!(dot.contains("\"a+\""), "graph: {}", dot);
+    assert!(
+        !dot.contains("\"b+\"") && !dot.contains("\"b-\""),
+        "graph: {}",
+        dot
+    );
+}
+
+/// A branch with two equally good edges survives the weak edge removal.
+/// GFA links all get an identity of 100, so neither edge of `a` is weak.
+#[test]
+fn weak_edge_removal_keeps_similar_branches() {
+    let dir = fresh_dir("pipeline_weak_edges");
+
+    // a -> b -> b2 and a -> c -> c2, with slightly different overlaps
+    let sequence = genome(1000);
+    let segments: Vec<(&str, &str)> = ["a", "b", "b2", "c", "c2"]
+        .into_iter()
+        .map(|name| (name, sequence.as_str()))
+        .collect();
+    write_gfa_input(
+        &dir,
+        &segments,
+        &[
+            ("a", "b", 500),
+            ("a", "c", 480),
+            ("b", "b2", 500),
+            ("c", "c2", 500),
+        ],
+    );
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "--input-gfa",
+            "graph.gfa",
+            "--remove-weak-edges",
+            "--short-edge-ratio",
+            "0",
+            "--max-tip-len",
+            "0",
+            "--max-internal-ext",
+            "0",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+
+    let dot = fs::read_to_string(dir.join("out").join("graph.dot")).unwrap();
+    assert!(dot.contains("\"a+\" -> \"b+\""), "graph: {}", dot);
+    assert!(dot.contains("\"a+\" -> \"c+\""), "graph: {}", dot);
+}
+
+/// The dry run reports the transitive edges and the bubble read `x`, without writing unitigs.
+#[test]
+fn dry_run_reports_candidates_without_cleanup() {
+    let dir = fresh_dir("pipeline_dry_run");
+    let genome = genome(4 * READ_STEP + READ_LEN);
+    write_input(&dir, &genome);
+
+    let output = run_assemble(
+        &dir,
+        &["-f", "ovl.paf", "-c", "1", "-l", "500", "--dry-run"],
+    );
+    let log = log(&output);
+    assert!(output.status.success(), "log: {}", log);
+    let out_dir = dir.join("out");
+
+    assert!(
+        log.contains("Transitive edge reduction would remove 6 edges"),
+        "log: {}",
+        log
+    );
+    // x+ and x-, seen from both sides of the bubble
+    assert!(log.contains("removing 2 distinct nodes"), "log: {}", log);
+    assert!(!out_dir.join("unitigs.fa").exists());
+    assert!(!out_dir.join("unitigs.cleaned_graph.bin").exists());
+}
+
+/// Overlaps are checked against the FASTQ read lengths, an overlap as long as the shorter read is kept
+/// and one base longer is dropped. The GFA segments are longer than the reads, so the GFA reader keeps both links.
+#[test]
+fn validate_overlaps_drops_overlaps_longer_than_a_read() {
+    let dir = fresh_dir("pipeline_validate_overlaps");
+
+    let mut gfa = String::from("H\tVN:Z:1.0\n");
+    for segment in ["a", "b", "c", "d"] {
+        gfa.push_str(&format!("S\t{}\t*\tLN:i:1000\n", segment));
+    }
+    for (from, to, overlap) in [("a", "b", 600), ("c", "d", 601)] {
+        gfa.push_str(&format!("L\t{}\t+\t{}\t+\t{}M\n", from, to, overlap));
+    }
+    fs::write(dir.join("graph.gfa"), gfa).unwrap();
+    let sequence = genome(700);
+    let mut fastq = String::new();
+    for (name, len) in [("a", 700), ("b", 600), ("c", 700), ("d", 600)] {
+        fastq.push_str(&format!(
+            "@{}\n{}\n+\n{}\n",
+            name,
+            &sequence[..len],
+            "I".repeat(len)
+        ));
+    }
+    fs::write(dir.join("reads.fq"), fastq).unwrap();
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "--input-gfa",
+            "graph.gfa",
+            "--validate-overlaps",
+            "--max-tip-len",
+            "0",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+
+    let dot = fs::read_to_string(dir.join("out").join("graph.dot")).unwrap();
+    assert!(dot.contains("\"a+\" -> \"b+\""), "graph: {}", dot);
+    assert!(dot.contains("\"b-\" -> \"a-\""), "graph: {}", dot);
+    assert!(!dot.contains("\"c+\" -> \"d+\""), "graph: {}", dot);
+    assert!(!dot.contains("\"d-\" -> \"c-\""), "graph: {}", dot);
+}
+
+/// Removed nodes take their incoming edges with them: after tip trimming and the small component
+/// removal every edge in the graph output ends in a node of the graph.
+#[test]
+fn removed_nodes_leave_no_dangling_edges() {
+    let dir = fresh_dir("pipeline_dangling_edges");
+
+    // chain a -> b -> c -> d, the tip t joins the chain at b and s is isolated
+    let sequence = genome(1000);
+    let segments: Vec<(&str, &str)> = ["a", "b", "c", "d", "t", "s"]
+        .into_iter()
+        .map(|name| (name, sequence.as_str()))
+        .collect();
+    write_gfa_input(
+        &dir,
+        &segments,
+        &[
+            ("a", "b", 500),
+            ("b", "c", 500),
+            ("c", "d", 500),
+            ("t", "b", 400),
+        ],
+    );
+
+    let output = run_assemble(&dir, &["--input-gfa", "graph.gfa", "--max-tip-len", "1"]);
+    assert!(output.status.success(), "log: {}", log(&output));
+
+    let dot = fs::read_to_string(dir.join("out").join("graph.dot")).unwrap();
+    let mut nodes: Vec<&str> = Vec::new();
+    let mut edges: Vec<(&str, &str)> = Vec::new();
+    for line in dot.lines().map(str::trim) {
+        if let Some((from, to)) = line.trim_end_matches(';').split_once(" -> ") {
+            edges.push((from, to));
+        } else if line.starts_with('"') {
+            nodes.extend(line.split(' ').next());
+        }
+    }
+    assert!(
+        !nodes
+            .iter()
+            .any(|n| n.starts_with("\"t") || n.starts_with("\"s")),
+        "graph: {}",
+        dot
+    );
+    assert!(!edges.is_empty(), "graph: {}", dot);
+    for (from, to) in edges {
+        assert!(
+            nodes.contains(&from) && nodes.contains(&to),
+            "dangling edge {} -> {}",
+            from,
+            to
+        );
+    }
+}
+
+#[test]
+fn standalone_junctions_mirror_the_branching() {
+    let dir = fresh_dir("pipeline_standalone_junctions");
+
+    // a1 -> a2 -> b, and b branches into c1 -> c2 and e1 -> e2
+    let sequence = genome(1000);
+    let segments: Vec<(&str, &str)> = ["a1", "a2", "b", "c1", "c2", "e1", "e2"]
+        .into_iter()
+        .map(|name| (name, sequence.as_str()))
+        .collect();
+    write_gfa_input(
+        &dir,
+        &segments,
+        &[
+            ("a1", "a2", 500),
+            ("a2", "b", 500),
+            ("b", "c1", 500),
+            ("c1", "c2", 500),
+            ("b", "e1", 500),
+            ("e1", "e2", 500),
+        ],
+    );
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "--input-gfa",
+            "graph.gfa",
+            "--max-tip-len",
+            "1",
+            "--standalone-junctions",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+    let out_dir = dir.join("out");
+
+    // forward read -> unitig
+    let mapping = fs::read_to_string(out_dir.join("unitigs.reads2unitig.tsv")).unwrap();
+    let mut unitig_of: HashMap<&str, &str> = HashMap::new();
+    for line in mapping.lines().skip(1) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        unitig_of.insert(fields[0], fields[2]);
+    }
+    assert_eq!(unitig_of.len(), 7, "mapping: {}", mapping);
+    let b = unitig_of["b"];
+    assert_eq!(unitig_of.values().filter(|u| **u == b).count(), 1);
+    assert_eq!(unitig_of["a1"], unitig_of["a2"]);
+    assert_eq!(unitig_of["c1"], unitig_of["c2"]);
+    assert_eq!(unitig_of["e1"], unitig_of["e2"]);
+
+    let gfa = fs::read_to_string(out_dir.join("unitigs.gfa")).unwrap();
+    let mut links: Vec<(&str, &str)> = gfa
+        .lines()
+        .filter(|l| l.starts_with("L\t"))
+        .map(|l| {
+            let fields: Vec<&str> = l.split('\t').collect();
+            (fields[1], fields[3])
+        })
+        .collect();
+    links.sort();
+    let mut expected = vec![
+        (unitig_of["a1"], b),
+        (b, unitig_of["c1"]),
+        (b, unitig_of["e1"]),
+    ];
+    expected.sort();
+    assert_eq!(links, expected, "gfa: {}", gfa);
+}
+
+#[test]
+fn four_read_cycle_becomes_one_circular_unitig() {
+    let dir = fresh_dir("pipeline_four_read_cycle");
+
+    // a -> b -> c -> d -> a, every read is 1000 bp
+    let sequence = genome(4000);
+    let segments: Vec<(&str, &str)> = ["a", "b", "c", "d"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name, &sequence[i * 1000..(i + 1) * 1000]))
+        .collect();
+    write_gfa_input(
+        &dir,
+        &segments,
+        &[
+            ("a", "b", 600),
+            ("b", "c", 700),
+            ("c", "d", 500),
+            ("d", "a", 400),
+        ],
+    );
+
+    let output = run_assemble(
+        &dir,
+        &["--input-gfa", "graph.gfa", "--stats-json", "stats.json"],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+    let out_dir = dir.join("out");
+
+    // the cycle and its reverse complement are one circular unitig
+    let stats: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap();
+    assert_eq!(stats["circular_unitigs"], 1);
+    assert_eq!(stats["metrics"]["count"], 1);
+
+    // every read once, placed after the edge length (read length - overlap) of its predecessor
+    let layout = fs::read_to_string(out_dir.join("unitigs.layout.tsv")).unwrap();
+    let rows: Vec<Vec<&str>> = layout
+        .lines()
+        .skip(1)
+        .map(|l| l.split('\t').collect())
+        .filter(|f: &Vec<&str>| f[0] == "unitig_0")
+        .collect();
+    let placed: Vec<(&str, &str, &str)> = rows.iter().map(|f| (f[1], f[2], f[3])).collect();
+    assert_eq!(
+        placed,
+        vec![
+            ("a", "+", "0"),
+            ("b", "+", "400"),
+            ("c", "+", "700"),
+            ("d", "+", "1200")
+        ],
+        "layout: {}",
+        layout
+    );
+
+    // the closing overlap d -> a is not spelled twice
+    let fasta = fs::read_to_string(out_dir.join("unitigs.fa")).unwrap();
+    assert!(
+        fasta.starts_with(">unitig_0 len=1800bp circular=true\n"),
+        "fasta: {}",
+        fasta
+    );
+
+    // the cycle spells 1800 bp, the sum of its edge lengths, and is shorter than the minimum unitig length
+    let output = run_assemble(
+        &dir,
+        &[
+            "--input-gfa",
+            "graph.gfa",
+            "--min-unitig-length",
+            "2000",
+            "--stats-json",
+            "stats.json",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+    let stats: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap();
+    assert_eq!(stats["circular_unitigs"], 0);
+    assert_eq!(stats["metrics"]["count"], 0);
+    let fasta = fs::read_to_string(dir.join("out").join("unitigs.fa")).unwrap();
+    assert_eq!(fasta, "");
+}
+
+#[test]
+fn fails_cleanly_when_no_overlap_survives_filtering() {
+    let dir = fresh_dir("pipeline_nothing_survives");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    // every overlap is shorter than the minimum overlap length
+    let output = run_assemble(&dir, &["-f", "ovl.paf", "-l", "100000"]);
+    assert!(!output.status.success());
+    let stderr = log(&output);
+    assert!(
+        stderr.contains("no overlaps survived filtering"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(!dir.join("out").join("unitigs.fa").exists());
+}
+
+#[test]
+fn trims_tips_shorter_than_max_tip_bp() {
+    let dir = fresh_dir("pipeline_tip_bp");
+
+    // chain m1 -> m2 -> m3 -> m4 of 1000 bp reads, the tip t1 -> t2 -> t3 of 300 bp reads joins at m2
+    // and spells 200 + 200 + 300 = 700 bp
+    let sequence = genome(1000);
+    let segments: Vec<(&str, &str)> = [
+        ("m1", 1000),
+        ("m2", 1000),
+        ("m3", 1000),
+        ("m4", 1000),
+        ("t1", 300),
+        ("t2", 300),
+        ("t3", 300),
+    ]
+    .into_iter()
+    .map(|(name, len)| (name, &sequence[..len]))
+    .collect();
+    write_gfa_input(
+        &dir,
+        &segments,
+        &[
+            ("m1", "m2", 500),
+            ("m2", "m3", 500),
+            ("m3", "m4", 500),
+            ("t1", "t2", 100),
+            ("t2", "t3", 100),
+            ("t3", "m2", 100),
+        ],
+    );
+
+    // the node count tip trimming and the short edge removal are disabled, the read graph is written as dot
+    let graph_nodes = |extra: &[&str]| -> Vec<String> {
+        let mut args = vec![
+            "--input-gfa",
+            "graph.gfa",
+            "--max-tip-len",
+            "0",
+            "--short-edge-ratio",
+            "0",
+            "--max-tip-bp",
+            "800",
+        ];
+        args.extend_from_slice(extra);
+        let output = run_assemble(&dir, &args);
+        assert!(output.status.success(), "log: {}", log(&output));
+        forward_nodes(&dir)
+    };
+
+    // the 3 read tip is shorter than 800 bp, the ends of the chain are not
+    assert_eq!(graph_nodes(&[]), ["m1+", "m2+", "m3+", "m4+"]);
+    // the node count cap keeps tips with more reads
+    assert_eq!(
+        graph_nodes(&["--max-tip-bp-nodes", "2"]),
+        ["m1+", "m2+", "m3+", "m4+", "t1+", "t2+", "t3+"]
+    );
+}
+
+#[test]
+fn trimmed_overhangs_keep_the_read_offsets() {
+    let dir = fresh_dir("pipeline_trim_overhangs");
+    let genome = genome(4 * READ_STEP + READ_LEN);
+    write_input(&dir, &genome);
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "-f",
+            "ovl.paf",
+            "-c",
+            "1",
+            "-l",
+            "500",
+            "--max-tip-len",
+            "0",
+            "--trim-overhangs",
+            "300",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+
+    // the clipped alignments are shorter but on the same diagonal, the unitig over all 5 reads spells the genome
+    let unitigs = read_fasta(&dir.join("out").join("unitigs.fa"));
+    assert_eq!(unitigs.len(), 2, "unitigs: {:?}", unitigs);
+    assert!(
+        unitigs.iter().any(|(_, seq)| *seq == genome),
+        "unitigs: {:?}",
+        unitigs
+    );
+}
+
+#[test]
+fn overlaps_file_records_its_filtering_parameters() {
+    let dir = fresh_dir("pipeline_overlaps_header");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    let output = run(
+        &dir,
+        &[
+            "alignment-filtering",
+            "-f",
+            "ovl.paf",
+            "--output-overlaps",
+            "overlaps.bin",
+            "-c",
+            "1",
+            "-l",
+            "500",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+
+    let assemble = |overlaps: &str, min_overlap_length: &str| {
+        run_assemble(&dir, &["--overlaps", overlaps, "-l", min_overlap_length])
+    };
+
+    // the cached overlaps are used, with a warning when the thresholds changed
+    let output = assemble("overlaps.bin", "500");
+    assert!(output.status.success(), "log: {}", log(&output));
+    assert!(!log(&output).contains("other parameters"));
+    let output = assemble("overlaps.bin", "800");
+    assert!(output.status.success(), "log: {}", log(&output));
+    let stderr = log(&output);
+    assert!(
+        stderr.contains("min_overlap_length 500 (requested 800)"),
+        "stderr: {}",
+        stderr
+    );
+
+    // a file without the header is rejected
+    fs::write(dir.join("stale.bin"), [0u8; 64]).unwrap();
+    let output = assemble("stale.bin", "500");
+    assert!(!output.status.success());
+    let stderr = log(&output);
+    assert!(
+        stderr.contains("is not an overlaps file"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn splits_tangles_at_articulation_points() {
+    let dir = fresh_dir("pipeline_articulation_points");
+
+    // two contigs a -> b and c -> d of 1000 bp reads cross at the repeat read r
+    let sequence = genome(1000);
+    let segments = [
+        "a1", "a2", "a3", "b1", "b2", "b3", "c1", "c2", "c3", "d1", "d2", "d3", "r",
+    ];
+    let reads: Vec<(&str, &str)> = segments
+        .iter()
+        .map(|name| (*name, sequence.as_str()))
+        .collect();
+    let links: Vec<(&str, &str, u32)> = [
+        ("a1", "a2"),
+        ("a2", "a3"),
+        ("a3", "r"),
+        ("r", "b1"),
+        ("b1", "b2"),
+        ("b2", "b3"),
+        ("c1", "c2"),
+        ("c2", "c3"),
+        ("c3", "r"),
+        ("r", "d1"),
+        ("d1", "d2"),
+        ("d2", "d3"),
+    ]
+    .into_iter()
+    .map(|(from, to)| (from, to, 500))
+    .collect();
+    write_gfa_input(&dir, &reads, &links);
+
+    let graph_nodes = |min_nodes: &str| -> Vec<String> {
+        let output = run_assemble(
+            &dir,
+            &[
+                "--input-gfa",
+                "graph.gfa",
+                "--max-tip-len",
+                "0",
+                "--split-articulation-min-nodes",
+                min_nodes,
+            ],
+        );
+        assert!(output.status.success(), "log: {}", log(&output));
+        forward_nodes(&dir)
+    };
+
+    // the pieces on either side of r have 3 nodes, r is cut out at a threshold of 3
+    let mut contigs: Vec<String> = segments[..12].iter().map(|s| format!("{}+", s)).collect();
+    contigs.sort();
+    assert_eq!(graph_nodes("3"), contigs);
+    // a higher threshold keeps the tangle, and the paths through the reads are never cut
+    contigs.push("r+".to_string());
+    contigs.sort();
+    assert_eq!(graph_nodes("4"), contigs);
+}
+
+#[test]
+fn smallest_edge_rule_can_be_disabled() {
+    let dir = fresh_dir("pipeline_smallest_edge_rule");
+
+    // n1 -> n2 -> n3 spells 50 bp more than n1 -> n3, beyond the fuzz. n2 -> n3 is the only, so the shortest,
+    // outgoing edge of n2, which is enough for the smallest edge rule to reduce n1 -> n3
+    let sequence = genome(2000);
+    let segments: Vec<(&str, &str)> = ["n1", "n2", "n3"]
+        .into_iter()
+        .map(|name| (name, sequence.as_str()))
+        .collect();
+    write_gfa_input(
+        &dir,
+        &segments,
+        &[("n1", "n2", 1900), ("n1", "n3", 1000), ("n2", "n3", 1050)],
+    );
+
+    let dry_run = |extra: &[&str]| -> String {
+        let mut args = vec!["--input-gfa", "graph.gfa", "--dry-run"];
+        args.extend_from_slice(extra);
+        let output = run_assemble(&dir, &args);
+        assert!(output.status.success(), "log: {}", log(&output));
+        log(&output)
+    };
+
+    // n1 -> n3 and its reverse complement
+    let log = dry_run(&[]);
+    assert!(
+        log.contains("Transitive edge reduction would remove 2 edges"),
+        "log: {}",
+        log
+    );
+    let log = dry_run(&["--no-smallest-edge-rule"]);
+    assert!(
+        log.contains("Transitive edge reduction would remove 0 edges"),
+        "log: {}",
+        log
+    );
+}
+
+#[test]
+fn reports_assembly_metrics_against_the_genome_size() {
+    let dir = fresh_dir("pipeline_assembly_metrics");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    let metrics = |genome_size: &str| -> serde_json::Value {
+        let output = run_assemble(
+            &dir,
+            &[
+                "-f",
+                "ovl.paf",
+                "-c",
+                "1",
+                "-l",
+                "500",
+                "--max-tip-len",
+                "0",
+                "--genome-size",
+                genome_size,
+                "--stats-json",
+                "stats.json",
+            ],
+        );
+        assert!(output.status.success(), "log: {}", log(&output));
+        let stats: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap();
+        stats["metrics"].clone()
+    };
+
+    // the 3500 bp unitig r1 -> r4 counts once, its reverse complement is not a second unitig
+    assert_eq!(
+        metrics("4000"),
+        serde_json::json!({
+            "count": 1,
+            "total": 3500,
+            "largest": 3500,
+            "n50": 3500,
+            "l50": 1,
+            "ng50": 3500,
+            "lg50": 1
+        })
+    );
+    // less than half of the genome is assembled
+    let metrics = metrics("8000");
+    assert_eq!(metrics["n50"], 3500);
+    assert!(metrics["ng50"].is_null() && metrics["lg50"].is_null());
+}
+
+#[test]
+fn cigar_refines_overlap_boundaries() {
+    let dir = fresh_dir("pipeline_cigar");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    // replace both lines of the pairs r1/r2 and r2/r3 by alignments with a CIGAR: r1/r2 has an insertion and a
+    // deletion in the middle, r2/r3 ends in an insertion. The block lengths count the indels
+    let paf = fs::read_to_string(dir.join("ovl.paf")).unwrap();
+    let mut paf: String = paf
+        .lines()
+        .filter(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            !matches!(
+                (fields[0], fields[5]),
+                ("r1", "r2") | ("r2", "r1") | ("r2", "r3") | ("r3", "r2")
+            )
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    for line in [
+        "r1\t2000\t500\t2000\t+\tr2\t2000\t0\t1490\t1470\t1520\t60\ttp:A:P\tcg:Z:600M30I600M20D270M",
+        "r2\t2000\t0\t1490\t+\tr1\t2000\t500\t2000\t1470\t1520\t60\ttp:A:P\tcg:Z:600M30D600M20I270M",
+        "r2\t2000\t500\t2000\t+\tr3\t2000\t0\t1480\t1480\t1500\t60\ttp:A:P\tcg:Z:1480M20I",
+        "r3\t2000\t0\t1480\t+\tr2\t2000\t500\t2000\t1480\t1500\t60\ttp:A:P\tcg:Z:1480M20D",
+    ] {
+        paf.push_str(line);
+        paf.push('\n');
+    }
+    fs::write(dir.join("ovl.paf"), paf).unwrap();
+
+    let output = run(
+        &dir,
+        &[
+            "alignment-filtering",
+            "-f",
+            "ovl.paf",
+            "--output-overlaps",
+            "overlaps.bin",
+            "-c",
+            "1",
+            "-l",
+            "500",
+        ],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+    let output = run(
+        &dir,
+        &["overlaps-to-paf", "--overlaps", "overlaps.bin", "-o", "-"],
+    );
+    assert!(output.status.success(), "log: {}", log(&output));
+    let table = String::from_utf8_lossy(&output.stdout);
+    // source, sink, edge_len, rc_edge_len, overlap_len
+    let overlap = |source: &str, sink: &str| -> Vec<String> {
+        table
+            .lines()
+            .map(|line| line.split('\t').collect::<Vec<&str>>())
+            .find(|f| f[0] == source && f[2] == sink)
+            .map(|f| f[4..7].iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default()
+    };
+
+    // the overlap length is the shorter aligned span instead of the block length
+    assert_eq!(overlap("r1", "r2"), ["500", "510", "1490"]);
+    // the trailing insertion is no part of the overlap, it moves the end of the alignment on r2
+    assert_eq!(overlap("r2", "r3"), ["500", "500", "1480"]);
+    // pairs without a CIGAR keep the block length
+    assert_eq!(overlap("r3", "r4"), ["500", "500", "1500"]);
+}
+
+#[test]
+fn skipped_cleanup_steps_are_logged() {
+    let dir = fresh_dir("pipeline_skipped_steps");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "-f",
+            "ovl.paf",
+            "-c",
+            "1",
+            "-l",
+            "500",
+            "--max-tip-len",
+            "0",
+            "--no-bubble-removal",
+            "--stats-json",
+            "stats.json",
+        ],
+    );
+    let log = log(&output);
+    assert!(output.status.success(), "log: {}", log);
+
+    // one line per cleanup iteration, the cleanup stops early once the graph no longer changes
+    let stats: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap();
+    let iterations = stats["iterations"].as_array().unwrap().len();
+    assert!(iterations >= 1);
+    assert_eq!(
+        log.matches("Skipped bubble removal (--no-bubble-removal)")
+            .count(),
+        iterations,
+        "log: {}",
+        log
+    );
+    assert!(!log.contains("bubble nodes"), "log: {}", log);
+
+    // the bubble read x is kept in a unitig of its own
+    let mapping = fs::read_to_string(dir.join("out").join("unitigs.reads2unitig.tsv")).unwrap();
+    assert!(mapping.lines().any(|line| line.starts_with("x\t")));
+}
+
+#[test]
+fn stats_json_has_one_entry_per_cleanup_iteration() {
+    let dir = fresh_dir("pipeline_stats_iterations");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    let output = run_assemble(
+        &dir,
+        &[
+            "-f",
+            "ovl.paf",
+            "-c",
+            "1",
+            "-l",
+            "500",
+            "--stats-json",
+            "stats.json",
+        ],
+    );
+    let log = log(&output);
+    assert!(output.status.success(), "log: {}", log);
+
+    // "Ran 2 of at most 5 cleanup iterations"
+    let iterations_run: usize = log
+        .lines()
+        .find_map(|line| line.split("Ran ").nth(1))
+        .and_then(|rest| rest.split(' ').next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or_else(|| panic!("no iteration count in the log: {}", log));
+    let stats: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap();
+    let iterations = stats["iterations"].as_array().unwrap();
+    assert_eq!(iterations.len(), iterations_run);
+    for (i, entry) in iterations.iter().enumerate() {
+        assert_eq!(entry["iteration"], i + 1);
+    }
+    // the last iteration leaves the final graph
+    let last = iterations.last().unwrap();
+    assert_eq!(last["nodes"], stats["final_nodes"]);
+    assert_eq!(last["edges"], stats["final_edges"]);
+}
+
+/// Every run hashes the node ids with a new seed, the cleanup must not depend on the iteration order
+#[test]
+fn repeated_runs_give_identical_unitigs() {
+    let dir = fresh_dir("pipeline_deterministic");
+    write_input(&dir, &genome(4 * READ_STEP + READ_LEN));
+
+    let outputs: Vec<(String, String)> = (0..3)
+        .map(|_| {
+            let output = run_assemble(&dir, &["-f", "ovl.paf", "-c", "1", "-l", "500"]);
+            assert!(output.status.success(), "log: {}", log(&output));
+            let out_dir = dir.join("out");
+            (
+                fs::read_to_string(out_dir.join("unitigs.reads2unitig.tsv")).unwrap(),
+                fs::read_to_string(out_dir.join("unitigs.fa")).unwrap(),
+            )
+        })
+        .collect();
+    assert!(!outputs[0].0.is_empty());
+    for output in &outputs[1..] {
+        assert_eq!(*output, outputs[0]);
+    }
+}
@@ -0,0 +1,92 @@
+/// Tip trimming on the library API: short chains hanging off the graph are found from both ends.
+mod common;
+
+use common::overlap;
+use ilesta::Overlap;
+use ilesta::create_overlap_graph::{OverlapGraph, build_overlap_graph_from};
+use ilesta::graph_analysis::check_synchronization;
+use ilesta::{trim_low_identity_tips, trim_tips};
+
+/// Overlaps along a path of forward nodes, every edge with the given identity
+fn path(nodes: &[&str], identity: f64) -> Vec<Overlap> {
+    nodes
+        .windows(2)
+        .map(|pair| Overlap {
+            identity,
+            ..overlap(pair[0], pair[1], 500, 500, 1500)
+        })
+        .collect()
+}
+
+/// A junction b+ on a path of 11 reads, 5 on either side, longer than the max_ext of 4 used in the tests
+fn backbone() -> Vec<Overlap> {
+    path(
+        &[
+            "a1+", "a2+", "a3+", "a4+", "a5+", "b+", "c1+", "c2+", "c3+", "c4+", "c5+",
+        ],
+        99.0,
+    )
+}
+
+/// Backbone with a 3 read tip t1+ -> t2+ -> t3+ joining it at b+
+fn graph_with_tip(tip_identity: f64) -> OverlapGraph {
+    let mut overlaps = backbone();
+    overlaps.extend(path(&["t1+", "t2+", "t3+", "b+"], tip_identity));
+    build_overlap_graph_from(&overlaps, false)
+}
+
+fn has_tip(graph: &OverlapGraph) -> Vec<bool> {
+    ["t1+", "t2+", "t3+", "t1-", "t2-", "t3-"]
+        .iter()
+        .map(|n| graph.index(n).is_some())
+        .collect()
+}
+
+#[test]
+fn low_identity_tip_is_removed() {
+    let mut graph = graph_with_tip(80.0);
+    assert_eq!(graph.node_count(), 28);
+
+    // the tip is found from both ends (t1+ forward, t1- backward), each read is counted once
+    assert_eq!(trim_low_identity_tips(&mut graph, 4, 90.0), 3);
+    assert_eq!(has_tip(&graph), vec![false; 6]);
+    assert_eq!(graph.node_count(), 22);
+    assert_eq!(check_synchronization(&graph), Ok(()));
+}
+
+#[test]
+fn high_identity_tip_of_the_same_length_is_kept() {
+    let mut graph = graph_with_tip(99.0);
+    assert_eq!(trim_low_identity_tips(&mut graph, 4, 90.0), 0);
+    assert_eq!(has_tip(&graph), vec![true; 6]);
+    assert_eq!(graph.node_count(), 28);
+
+    // a tip of more than max_ext reads is not a tip, whatever its identity
+    let mut graph = graph_with_tip(80.0);
+    assert_eq!(trim_low_identity_tips(&mut graph, 2, 90.0), 0);
+    assert_eq!(graph.node_count(), 28);
+}
+
+#[test]
+fn dead_end_without_reverse_complement_is_trimmed() {
+    // b+ -> d1+ -> d2+ is a dangling chain, the reverse complement d2- -> d1- -> b- was already deleted
+    let mut overlaps = backbone();
+    overlaps.extend(path(&["b+", "d1+", "d2+"], 99.0));
+    let mut graph = build_overlap_graph_from(&overlaps, false);
+    for id in ["d1-", "d2-"] {
+        graph.remove_node(graph.index(id).unwrap());
+    }
+    let b_fwd = graph.index("b+").unwrap();
+    assert_eq!(graph.node(b_fwd).unwrap().edges.len(), 2);
+    assert_eq!(graph.node_count(), 24);
+
+    // the chain is found by walking backward from the dead end d2+ via its incoming edges
+    trim_tips(&mut graph, 4);
+    assert_eq!(graph.index("d1+"), None);
+    assert_eq!(graph.index("d2+"), None);
+    assert_eq!(graph.node_count(), 22);
+    let edges = &graph.node(b_fwd).unwrap().edges;
+    assert_eq!(edges.len(), 1);
+    assert_eq!(graph.node_id(edges[0].target), "c1+");
+    assert_eq!(check_synchronization(&graph), Ok(()));
+}
@@ -0,0 +1,44 @@
+/// Transitive reduction on the library API: an edge and its reverse complement counterpart are reduced together,
+/// the bigraph stays synchronized.
+mod common;
+
+use common::{edges, overlap, pairs};
+use ilesta::create_overlap_graph::build_overlap_graph_from;
+use ilesta::graph_analysis::check_synchronization;
+use ilesta::reduce_transitive_edges;
+use ilesta::transitive_edge_reduction::find_transitive_edges;
+
+#[test]
+fn transitive_edge_is_removed_with_its_reverse_complement() {
+    // 1000 bp reads a, b and c, 300 bp apart. b is reverse complemented, so the path a+ -> b- -> c+ crosses
+    // strands and its reverse complement is c- -> b+ -> a-. a+ -> c+ spells the same sequence as the path
+    let mut graph = build_overlap_graph_from(
+        &[
+            overlap("a+", "b-", 300, 300, 700),
+            overlap("b-", "c+", 300, 300, 700),
+            overlap("a+", "c+", 600, 600, 400),
+        ],
+        false,
+    );
+    assert!(check_synchronization(&graph).is_ok());
+    assert_eq!(edges(&graph).len(), 6);
+
+    // both strands of the transitive edge are found
+    let mut transitive = find_transitive_edges(&graph, 10, true);
+    transitive.sort();
+    assert_eq!(transitive, pairs(&[("a+", "c+"), ("c-", "a-")]));
+
+    reduce_transitive_edges(&mut graph, 10, true);
+    assert_eq!(check_synchronization(&graph), Ok(()));
+    assert_eq!(
+        edges(&graph),
+        pairs(&[("a+", "b-"), ("b-", "c+"), ("b+", "a-"), ("c-", "b+")])
+    );
+    for node in ["a+", "a-", "c+", "c-"] {
+        let idx = graph.index(node).unwrap();
+        assert_eq!(
+            graph.indegree(idx) + graph.node(idx).unwrap().edges.len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,45 @@
+/// Node id helpers on malformed ids: every id needs a read name and a '+' or '-' orientation suffix.
+/// Reverse complement of IUPAC codes and soft-masked sequence.
+use ilesta::utils::{rc_node, rev_comp, split_node_id};
+
+#[test]
+fn node_ids_are_split_into_read_name_and_orientation() {
+    assert_eq!(split_node_id("read1+"), Some(("read1", '+')));
+    assert_eq!(split_node_id("read1-"), Some(("read1", '-')));
+    // the suffix is the last character only, read names may contain '+' and '-'
+    assert_eq!(split_node_id("a-b++"), Some(("a-b+", '+')));
+}
+
+#[test]
+fn malformed_node_ids_are_rejected() {
+    for id in ["", "+", "-", "r", "read1", "read1*"] {
+        assert_eq!(split_node_id(id), None, "{:?}", id);
+        assert_eq!(rc_node(id), None, "{:?}", id);
+    }
+}
+
+#[test]
+fn reverse_complement_node_flips_the_orientation() {
+    assert_eq!(rc_node("read1+").as_deref(), Some("read1-"));
+    assert_eq!(rc_node("read1-").as_deref(), Some("read1+"));
+    assert_eq!(rc_node("x+").as_deref(), Some("x-"));
+    let id = "a-b+";
+    assert_eq!(rc_node(&rc_node(id).unwrap()).as_deref(), Some(id));
+}
+
+#[test]
+fn reverse_complement_handles_ambiguity_codes() {
+    // R/Y and K/M swap, S and W are their own complement
+    assert_eq!(rev_comp("RYSWKM"), "KMWSRY");
+    assert_eq!(rev_comp("BDHVN"), "NBDHV");
+    assert_eq!(rev_comp("ACGTU"), "AACGT");
+    assert_eq!(rev_comp(&rev_comp("ACGRYSWKMBDHVN")), "ACGRYSWKMBDHVN");
+}
+
+#[test]
+fn reverse_complement_keeps_the_case() {
+    assert_eq!(rev_comp("acgtNNry"), "ryNNacgt");
+    assert_eq!(rev_comp("ACgt"), "acGT");
+    // gaps pass through
+    assert_eq!(rev_comp("A-C"), "G-T");
+}